@@ -0,0 +1,419 @@
+//! Color adjustments (auto levels, brightness, contrast, saturation, hue) and
+//! sharpen/denoise filters applied to the whole image, and named presets of them.
+//!
+//! While the panel is open, slider changes are previewed on a downscaled copy of
+//! the image so dragging stays smooth. The full-resolution pass only runs once
+//! the user applies the result.
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+/// Longest edge of the copy used for live previews.
+const PREVIEW_EDGE: u32 = 1024;
+/// Gaussian radius of the unsharp mask at full resolution, in pixels.
+const SHARPEN_SIGMA: f32 = 1.5;
+/// Share of pixels at each end of the histogram that auto levels lets clip, so a
+/// few specks of pure black or white don't stop the stretch.
+const LEVELS_CLIP: f64 = 0.005;
+/// Largest stretch auto levels applies, so near-flat images don't turn into noise.
+const LEVELS_MAX_GAIN: f32 = 4.0;
+
+/// Histogram stretch computed from the image itself.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoLevels {
+    #[default]
+    Off,
+    /// Stretch each channel on its own. Also neutralizes color casts.
+    Channels,
+    /// Stretch all channels alike, keeping hue and color balance. The range is
+    /// taken over all three so saturated colors aren't pushed out of it.
+    Contrast,
+}
+
+impl AutoLevels {
+    pub const ALL: [AutoLevels; 3] = [Self::Off, Self::Channels, Self::Contrast];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Channels => "Per channel",
+            Self::Contrast => "Contrast",
+        }
+    }
+
+    /// Black point and gain for each of red, green and blue, taken from `rgba`
+    /// with `LEVELS_CLIP` of the pixels ignored at each end.
+    fn levels(self, rgba: &image::RgbaImage) -> [(f32, f32); 3] {
+        let identity = [(0.0, 1.0); 3];
+        if self == Self::Off || rgba.is_empty() {
+            return identity;
+        }
+        let mut counts = [[0u64; 256]; 3];
+        for pixel in rgba.pixels() {
+            for (channel, &c) in counts.iter_mut().zip(&pixel.0[..3]) {
+                channel[c as usize] += 1;
+            }
+        }
+        let pixels = rgba.pixels().len() as f64;
+        let range = |counts: &[u64; 256], samples: f64| {
+            let clip = (samples * LEVELS_CLIP) as u64;
+            let mut seen = 0;
+            let low = counts.iter().position(|&n| { seen += n; seen > clip }).unwrap_or(0);
+            seen = 0;
+            let high = 255 - counts.iter().rev().position(|&n| { seen += n; seen > clip }).unwrap_or(0);
+            // Already spanning the range, or a flat image with nothing to stretch
+            if high <= low {
+                return (0.0, 1.0);
+            }
+            let gain = (255.0 / (high - low) as f32).min(LEVELS_MAX_GAIN);
+            // Keep the stretched range centered where the clipped one was
+            let mid = (low + high) as f32 / 2.0;
+            ((mid - 127.5 / gain).max(0.0), gain)
+        };
+        match self {
+            Self::Off => identity,
+            Self::Channels => counts.map(|channel| range(&channel, pixels)),
+            Self::Contrast => {
+                let all = std::array::from_fn(|level| counts.iter().map(|channel| channel[level]).sum());
+                [range(&all, pixels * 3.0); 3]
+            }
+        }
+    }
+}
+
+/// A set of color adjustments. Every field is 0 when it leaves the image untouched.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct Adjustments {
+    /// Stretch of the histogram, applied before the other adjustments.
+    pub auto_levels: AutoLevels,
+    /// Added to every channel, -1 to 1.
+    pub brightness: f32,
+    /// Spread around mid-gray, -1 (flat gray) to 1 (double).
+    pub contrast: f32,
+    /// Distance from the pixel's luminance, -1 (grayscale) to 1 (double).
+    pub saturation: f32,
+    /// Rotation of the HSL hue in degrees, -180 to 180.
+    pub hue: f32,
+    /// Unsharp mask amount, 0 to 2.
+    pub sharpen: f32,
+    /// Blend toward a 3x3 median, 0 to 1.
+    pub denoise: f32,
+}
+
+impl Adjustments {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns `img` with the adjustments applied. Alpha is left as is.
+    pub fn apply(&self, img: &image::DynamicImage) -> image::DynamicImage {
+        self.apply_scaled(img, 1.0)
+    }
+
+    /// Like `apply`, for a copy of the image resized by `scale`, so the sharpen
+    /// radius covers the same detail as it would at full resolution.
+    pub fn apply_scaled(&self, img: &image::DynamicImage, scale: f32) -> image::DynamicImage {
+        let mut rgba = img.to_rgba8();
+        self.adjust_colors(&mut rgba);
+        if self.denoise > 0.0 {
+            // Before sharpening, so the noise isn't amplified first
+            let median = imageproc::filter::median_filter(&rgba, 1, 1);
+            blend(&mut rgba, &median, self.denoise);
+        }
+        if self.sharpen > 0.0 {
+            // Push each pixel away from its blurred surroundings
+            let blurred = image::imageops::blur(&rgba, (SHARPEN_SIGMA * scale).max(0.3));
+            blend(&mut rgba, &blurred, -self.sharpen);
+        }
+        image::DynamicImage::ImageRgba8(rgba)
+    }
+
+    fn adjust_colors(&self, rgba: &mut image::RgbaImage) {
+        let levels = self.auto_levels.levels(rgba);
+        for pixel in rgba.pixels_mut() {
+            let mut rgb = [0.0; 3];
+            for ((v, &c), (black, gain)) in rgb.iter_mut().zip(&pixel.0[..3]).zip(levels) {
+                let c = (c as f32 - black) * gain;
+                *v = (c / 255.0 - 0.5) * (1.0 + self.contrast) + 0.5 + self.brightness;
+            }
+            if self.saturation != 0.0 {
+                // Rec. 709 weights sum to 1, so grays are their own luminance and stay put
+                let luma = 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+                for v in &mut rgb {
+                    *v = luma + (*v - luma) * (1.0 + self.saturation);
+                }
+            }
+            if self.hue != 0.0 {
+                rgb = rotate_hue(rgb.map(|v| v.clamp(0.0, 1.0)), self.hue);
+            }
+            for (c, v) in pixel.0[..3].iter_mut().zip(rgb) {
+                *c = (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        }
+    }
+}
+
+/// Moves the color channels of `rgba` toward `target` by `amount`. Negative
+/// amounts move away from it.
+fn blend(rgba: &mut image::RgbaImage, target: &image::RgbaImage, amount: f32) {
+    for (pixel, target) in rgba.pixels_mut().zip(target.pixels()) {
+        for (c, &t) in pixel.0[..3].iter_mut().zip(&target.0[..3]) {
+            let v = *c as f32 + (t as f32 - *c as f32) * amount;
+            *c = v.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+/// Rotates the hue of an RGB color (channels 0-1) by `degrees` in HSL space.
+/// Grays have no hue and come back unchanged.
+fn rotate_hue([r, g, b]: [f32; 3], degrees: f32) -> [f32; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    if delta <= f32::EPSILON {
+        return [r, g, b];
+    }
+
+    let lightness = (max + min) / 2.0;
+    let saturation = delta / (1.0 - (2.0 * lightness - 1.0).abs());
+    let hue = if max == r {
+        ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+    let hue = (hue * 60.0 + degrees).rem_euclid(360.0);
+
+    // Back from HSL
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    [r + m, g + m, b + m]
+}
+
+/// Adjustments saved under a name for reuse on other images.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AdjustPreset {
+    pub name: String,
+    pub adjustments: Adjustments,
+}
+
+/// What the user chose in the adjust panel.
+pub enum AdjustAction {
+    /// Apply these adjustments to the full image.
+    Apply(Adjustments),
+    Close,
+}
+
+/// The "Adjust" window: sliders with a live preview, and the preset list.
+pub struct AdjustPanel {
+    pub adjustments: Adjustments,
+    source: image::DynamicImage,
+    /// Size of `source` relative to the image.
+    scale: f32,
+    /// Preview of `adjustments` on `source`, shown in place of the image. `None` while they are identity.
+    pub preview: Option<egui::TextureHandle>,
+    preset_name: String,
+    /// Sampling of the preview, matching the image it stands in for.
+    options: egui::TextureOptions,
+}
+
+impl AdjustPanel {
+    /// Opens the panel for `img`, keeping a downscaled copy for previews.
+    pub fn new(img: &image::DynamicImage, options: egui::TextureOptions) -> Self {
+        let source = if img.width().max(img.height()) > PREVIEW_EDGE {
+            img.resize(PREVIEW_EDGE, PREVIEW_EDGE, image::imageops::FilterType::Triangle)
+        } else {
+            img.clone()
+        };
+        Self {
+            adjustments: Adjustments::default(),
+            scale: source.width() as f32 / img.width() as f32,
+            source,
+            preview: None,
+            preset_name: String::new(),
+            options,
+        }
+    }
+
+    /// Draws the window. `presets_changed` is set when a preset was saved or removed.
+    pub fn show(&mut self, ctx: &egui::Context, presets: &mut Vec<AdjustPreset>, presets_changed: &mut bool) -> Option<AdjustAction> {
+        let mut action = None;
+        let mut open = true;
+        let before = self.adjustments;
+
+        egui::Window::new("Adjust")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::Grid::new("adjust_grid").num_columns(2).show(ui, |ui| {
+                    ui.label("Auto levels:");
+                    ui.horizontal(|ui| {
+                        for levels in AutoLevels::ALL {
+                            ui.selectable_value(&mut self.adjustments.auto_levels, levels, levels.label());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Stretch the darkest and brightest tones to the full range. Per channel also corrects color casts; contrast keeps the colors as they are");
+                    ui.end_row();
+
+                    ui.label("Brightness:");
+                    ui.add(egui::Slider::new(&mut self.adjustments.brightness, -1.0..=1.0));
+                    ui.end_row();
+
+                    ui.label("Contrast:");
+                    ui.add(egui::Slider::new(&mut self.adjustments.contrast, -1.0..=1.0));
+                    ui.end_row();
+
+                    ui.label("Saturation:");
+                    ui.add(egui::Slider::new(&mut self.adjustments.saturation, -1.0..=1.0));
+                    ui.end_row();
+
+                    ui.label("Hue:");
+                    ui.add(egui::Slider::new(&mut self.adjustments.hue, -180.0..=180.0).suffix("°"));
+                    ui.end_row();
+
+                    ui.label("Sharpen:");
+                    ui.add(egui::Slider::new(&mut self.adjustments.sharpen, 0.0..=2.0));
+                    ui.end_row();
+
+                    ui.label("Denoise:");
+                    ui.add(egui::Slider::new(&mut self.adjustments.denoise, 0.0..=1.0));
+                    ui.end_row();
+                });
+
+                ui.horizontal(|ui| {
+                    let identity = self.adjustments.is_identity();
+                    if ui.add_enabled(!identity, egui::Button::new("Apply")).clicked() {
+                        action = Some(AdjustAction::Apply(self.adjustments));
+                    }
+                    if ui.add_enabled(!identity, egui::Button::new("Reset")).clicked() {
+                        self.adjustments = Adjustments::default();
+                    }
+                });
+
+                ui.separator();
+                ui.label("Presets");
+                let mut remove = None;
+                for (i, preset) in presets.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.button(&preset.name).on_hover_text("Apply to this image").clicked() {
+                            action = Some(AdjustAction::Apply(preset.adjustments));
+                        }
+                        if ui.small_button("🗑").on_hover_text("Remove preset").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = remove {
+                    presets.remove(i);
+                    *presets_changed = true;
+                }
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.preset_name).hint_text("Preset name").desired_width(120.0));
+                    let name = self.preset_name.trim();
+                    if ui.add_enabled(!name.is_empty() && !self.adjustments.is_identity(), egui::Button::new("Save"))
+                        .on_hover_text("Save the current sliders as a preset")
+                        .clicked()
+                    {
+                        // Saving under an existing name replaces that preset
+                        let preset = AdjustPreset { name: name.to_string(), adjustments: self.adjustments };
+                        match presets.iter_mut().find(|p| p.name == preset.name) {
+                            Some(existing) => *existing = preset,
+                            None => presets.push(preset),
+                        }
+                        self.preset_name.clear();
+                        *presets_changed = true;
+                    }
+                });
+            });
+
+        if self.adjustments != before {
+            self.update_preview(ctx);
+        }
+        if !open {
+            action = Some(AdjustAction::Close);
+        }
+        action
+    }
+
+    fn update_preview(&mut self, ctx: &egui::Context) {
+        if self.adjustments.is_identity() {
+            self.preview = None;
+            return;
+        }
+        let rgba = self.adjustments.apply_scaled(&self.source, self.scale).to_rgba8();
+        let size = [rgba.width() as usize, rgba.height() as usize];
+        let color_image = egui::ColorImage::from_rgba_unmultiplied(size, rgba.as_raw());
+        match &mut self.preview {
+            Some(texture) => texture.set(color_image, self.options),
+            None => self.preview = Some(ctx.load_texture("adjust_preview", color_image, self.options)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adjusted(adjustments: Adjustments, rgba: &image::RgbaImage) -> image::RgbaImage {
+        let mut out = rgba.clone();
+        adjustments.adjust_colors(&mut out);
+        out
+    }
+
+    #[test]
+    fn saturation_and_hue_leave_grays_alone() {
+        let grays = image::RgbaImage::from_fn(256, 1, |x, _| image::Rgba([x as u8, x as u8, x as u8, 255]));
+        for saturation in [-1.0, -0.4, 0.5, 1.0] {
+            let adjustments = Adjustments { saturation, ..Default::default() };
+            assert_eq!(adjusted(adjustments, &grays), grays, "saturation {}", saturation);
+        }
+        for hue in [-180.0, -90.0, 45.0, 120.0, 180.0] {
+            let adjustments = Adjustments { hue, saturation: 0.5, ..Default::default() };
+            assert_eq!(adjusted(adjustments, &grays), grays, "hue {}", hue);
+        }
+    }
+
+    #[test]
+    fn auto_levels_leave_a_full_range_image_alone() {
+        // Ramps with solid black and white ends well past the clipped share
+        let ramp = image::RgbaImage::from_fn(300, 1, |x, _| {
+            let v = (x as i32 - 22).clamp(0, 255) as u8;
+            image::Rgba([v, 255 - v, v, 255])
+        });
+        for auto_levels in [AutoLevels::Channels, AutoLevels::Contrast] {
+            assert_eq!(auto_levels.levels(&ramp), [(0.0, 1.0); 3]);
+            assert_eq!(adjusted(Adjustments { auto_levels, ..Default::default() }, &ramp), ramp);
+        }
+    }
+
+    #[test]
+    fn auto_levels_cap_the_stretch_of_a_near_flat_image() {
+        // 120 to 130 would need a gain of 25.5 to fill the range
+        let flat = image::RgbaImage::from_fn(110, 1, |x, _| {
+            let v = 120 + (x / 10) as u8;
+            image::Rgba([v, v, v, 255])
+        });
+        for auto_levels in [AutoLevels::Channels, AutoLevels::Contrast] {
+            assert!(auto_levels.levels(&flat).iter().all(|&(_, gain)| gain == LEVELS_MAX_GAIN));
+            let out = adjusted(Adjustments { auto_levels, ..Default::default() }, &flat);
+            let (low, high) = (out.get_pixel(0, 0)[0], out.get_pixel(109, 0)[0]);
+            // Ten levels stretched four times, around mid-gray
+            assert!((high - low).abs_diff(40) <= 1, "{} to {}", low, high);
+            assert!(((low as u32 + high as u32) / 2).abs_diff(127) <= 1, "{} to {}", low, high);
+        }
+    }
+}
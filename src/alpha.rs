@@ -0,0 +1,165 @@
+//! Images whose decoded pixels carry premultiplied alpha.
+//!
+//! The viewer works in straight alpha throughout, but some files store color
+//! already multiplied by alpha and the decoders pass it through unchanged:
+//! TIFFs that declare "associated" alpha, and icons written by tools that put
+//! premultiplied pixels in their bitmaps. Shown as straight alpha, their soft
+//! edges come out dark, so they are converted once after decoding.
+
+use image::DynamicImage;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// TIFF ExtraSamples tag, and its value for associated (premultiplied) alpha.
+const EXTRA_SAMPLES_TAG: u16 = 338;
+const ASSOCIATED_ALPHA: u32 = 1;
+
+/// Translucent pixels an icon needs before its premultiplication is guessed at.
+const MIN_TRANSLUCENT_PIXELS: usize = 16;
+
+/// Whether `img`, decoded from `path`, holds premultiplied color.
+pub fn is_premultiplied(path: &Path, img: &DynamicImage) -> bool {
+    if !img.color().has_alpha() {
+        return false;
+    }
+    let extension = path.extension().and_then(|ext| ext.to_str()).map(str::to_lowercase);
+    match extension.as_deref() {
+        Some("tif" | "tiff") => tiff_has_associated_alpha(path),
+        // Icons don't record it, so go by the pixels
+        Some("ico") => looks_premultiplied(img),
+        _ => false,
+    }
+}
+
+/// Divides color by alpha in place, turning premultiplied pixels into straight ones.
+pub fn unpremultiply(img: &mut DynamicImage) {
+    match img {
+        DynamicImage::ImageRgba8(buf) => {
+            for px in buf.pixels_mut() {
+                let a = px[3] as u32;
+                for c in &mut px.0[..3] {
+                    *c = unpremultiply_channel(*c as u32, a, u8::MAX as u32) as u8;
+                }
+            }
+        }
+        DynamicImage::ImageLumaA8(buf) => {
+            for px in buf.pixels_mut() {
+                px[0] = unpremultiply_channel(px[0] as u32, px[1] as u32, u8::MAX as u32) as u8;
+            }
+        }
+        DynamicImage::ImageRgba16(buf) => {
+            for px in buf.pixels_mut() {
+                let a = px[3] as u32;
+                for c in &mut px.0[..3] {
+                    *c = unpremultiply_channel(*c as u32, a, u16::MAX as u32) as u16;
+                }
+            }
+        }
+        DynamicImage::ImageLumaA16(buf) => {
+            for px in buf.pixels_mut() {
+                px[0] = unpremultiply_channel(px[0] as u32, px[1] as u32, u16::MAX as u32) as u16;
+            }
+        }
+        DynamicImage::ImageRgba32F(buf) => {
+            for px in buf.pixels_mut() {
+                let a = px[3];
+                if a > 0.0 {
+                    for c in &mut px.0[..3] {
+                        *c /= a;
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `c` scaled up by `max / a`, rounded and clamped to `max`. Fully transparent
+/// pixels keep their (meaningless) color.
+fn unpremultiply_channel(c: u32, a: u32, max: u32) -> u32 {
+    if a == 0 {
+        return c;
+    }
+    ((c * max + a / 2) / a).min(max)
+}
+
+fn tiff_has_associated_alpha(path: &Path) -> bool {
+    let Ok(file) = File::open(path) else { return false };
+    let Ok(exif) = exif::Reader::new().read_from_container(&mut BufReader::new(file)) else { return false };
+    let tag = exif::Tag(exif::Context::Tiff, EXTRA_SAMPLES_TAG);
+    exif.get_field(tag, exif::In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .is_some_and(|value| value == ASSOCIATED_ALPHA)
+}
+
+/// Premultiplied color never exceeds its alpha, while straight-alpha edges
+/// usually keep their full color. Also wants some edges bright relative to
+/// their alpha, so an icon whose only soft pixels are a dark shadow isn't
+/// brightened by mistake.
+fn looks_premultiplied(img: &DynamicImage) -> bool {
+    let rgba = img.to_rgba8();
+    let mut translucent = 0;
+    let mut bright_edges = 0;
+    for px in rgba.pixels() {
+        let [r, g, b, a] = px.0;
+        let brightest = r.max(g).max(b);
+        if brightest > a {
+            return false;
+        }
+        if a > 0 && a < u8::MAX {
+            translucent += 1;
+            if brightest as u32 * 4 >= a as u32 * 3 {
+                bright_edges += 1;
+            }
+        }
+    }
+    translucent >= MIN_TRANSLUCENT_PIXELS && bright_edges > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An opaque middle with a soft edge of `edge(alpha)` pixels on either side.
+    fn icon(edge: impl Fn(u8) -> [u8; 4]) -> DynamicImage {
+        DynamicImage::ImageRgba8(image::RgbaImage::from_fn(48, 1, |x, _| match x {
+            16..=31 => image::Rgba([200, 40, 40, 255]),
+            _ => image::Rgba(edge((x % 16 * 16 + 8) as u8)),
+        }))
+    }
+
+    #[test]
+    fn premultiplied_edge_is_detected() {
+        assert!(looks_premultiplied(&icon(|a| [a, a, a, a])));
+        assert!(looks_premultiplied(&icon(|a| [a, a / 2, 0, a])));
+    }
+
+    #[test]
+    fn straight_alpha_edge_is_not() {
+        assert!(!looks_premultiplied(&icon(|a| [255, 255, 255, a])));
+        // A single straight pixel is enough to rule it out
+        let mut img = icon(|a| [a, a, a, a]).to_rgba8();
+        img.put_pixel(0, 0, image::Rgba([255, 0, 0, 8]));
+        assert!(!looks_premultiplied(&DynamicImage::ImageRgba8(img)));
+    }
+
+    #[test]
+    fn shadow_only_icon_is_left_alone() {
+        // A black shadow fits premultiplied and straight alpha alike
+        assert!(!looks_premultiplied(&icon(|a| [0, 0, 0, a])));
+        assert!(!looks_premultiplied(&icon(|a| [a / 8, a / 8, a / 8, a])));
+    }
+
+    #[test]
+    fn unpremultiply_rounds_to_nearest() {
+        assert_eq!(unpremultiply_channel(64, 128, 255), 128);
+        assert_eq!(unpremultiply_channel(1, 3, 255), 85);
+        assert_eq!(unpremultiply_channel(2, 3, 255), 170);
+        assert_eq!(unpremultiply_channel(1, 2, u16::MAX as u32), 32768);
+        // Opaque pixels come back as they were, and overshoots are clamped
+        assert!((0..=255).all(|c| unpremultiply_channel(c, 255, 255) == c));
+        assert_eq!(unpremultiply_channel(200, 100, 255), 255);
+        assert_eq!(unpremultiply_channel(37, 0, 255), 37);
+    }
+}
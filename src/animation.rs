@@ -0,0 +1,270 @@
+//! Multi-frame image decoding and playback (GIF, animated WebP, APNG).
+//!
+//! `image::open` only yields the first frame, so animated files are decoded a
+//! second time here into a frame list. The intended loop count isn't exposed by
+//! `image`, so it is read from the container with the underlying codec crates.
+
+use eframe::egui;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::Duration;
+
+use image::AnimationDecoder;
+
+/// Frame delays at or below this are treated as "as fast as possible" by browsers,
+/// which play them at `DEFAULT_FRAME_DELAY` instead. We do the same.
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(10);
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+/// How many times an animation is meant to play.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LoopCount {
+    Infinite,
+    /// Total number of plays, including the first.
+    Finite(u32),
+}
+
+impl std::fmt::Display for LoopCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoopCount::Infinite => write!(f, "Infinite"),
+            LoopCount::Finite(1) => write!(f, "Once"),
+            LoopCount::Finite(n) => write!(f, "{} times", n),
+        }
+    }
+}
+
+/// Decoded frames of an animation and its playback position.
+#[derive(Clone)]
+pub struct AnimationPlayer {
+    frames: Vec<(egui::ColorImage, Duration)>,
+    pub loop_count: LoopCount,
+    /// Keep looping even when the file asks for a finite number of plays.
+    pub loop_anyway: bool,
+    pub playing: bool,
+    current_frame: usize,
+    /// Time spent on the current frame.
+    elapsed: Duration,
+    plays_completed: u32,
+}
+
+impl AnimationPlayer {
+    /// Decodes `path` as an animation. Returns `None` for still images and single-frame files.
+    pub fn load(path: &Path) -> Option<Self> {
+        let format = image::ImageReader::open(path).ok()?.with_guessed_format().ok()?.format()?;
+        let reader = || File::open(path).ok().map(BufReader::new);
+
+        let (frames, loop_count) = match format {
+            image::ImageFormat::Gif => {
+                let frames = image::codecs::gif::GifDecoder::new(reader()?).ok()?.into_frames().collect_frames().ok()?;
+                // GIF stores the number of *repeats*, so a value of n means n + 1 plays
+                let loop_count = match gif::DecodeOptions::new().read_info(reader()?).ok()?.repeat() {
+                    gif::Repeat::Infinite => LoopCount::Infinite,
+                    gif::Repeat::Finite(n) => LoopCount::Finite(n as u32 + 1),
+                };
+                (frames, loop_count)
+            }
+            image::ImageFormat::WebP => {
+                let decoder = image::codecs::webp::WebPDecoder::new(reader()?).ok()?;
+                if !decoder.has_animation() {
+                    return None;
+                }
+                let frames = decoder.into_frames().collect_frames().ok()?;
+                let loop_count = match image_webp::WebPDecoder::new(reader()?).ok()?.loop_count() {
+                    image_webp::LoopCount::Forever => LoopCount::Infinite,
+                    image_webp::LoopCount::Times(n) => LoopCount::Finite(n.get() as u32),
+                };
+                (frames, loop_count)
+            }
+            image::ImageFormat::Png => {
+                let decoder = image::codecs::png::PngDecoder::new(reader()?).ok()?;
+                if !decoder.is_apng().ok()? {
+                    return None;
+                }
+                let frames = decoder.apng().ok()?.into_frames().collect_frames().ok()?;
+                let plays = png::Decoder::new(reader()?).read_info().ok()?
+                    .info().animation_control.map_or(0, |ac| ac.num_plays);
+                let loop_count = if plays == 0 { LoopCount::Infinite } else { LoopCount::Finite(plays) };
+                (frames, loop_count)
+            }
+            _ => return None,
+        };
+
+        if frames.len() < 2 {
+            return None;
+        }
+
+        let frames = frames.into_iter()
+            .map(|frame| {
+                let delay = Duration::from(frame.delay());
+                let delay = if delay <= MIN_FRAME_DELAY { DEFAULT_FRAME_DELAY } else { delay };
+                let buffer = frame.into_buffer();
+                let size = [buffer.width() as usize, buffer.height() as usize];
+                (egui::ColorImage::from_rgba_unmultiplied(size, buffer.as_raw()), delay)
+            })
+            .collect();
+
+        Some(Self {
+            frames,
+            loop_count,
+            loop_anyway: false,
+            playing: true,
+            current_frame: 0,
+            elapsed: Duration::ZERO,
+            plays_completed: 0,
+        })
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Approximate bytes held by the decoded frames.
+    pub fn memory_bytes(&self) -> usize {
+        self.frames.iter().map(|(frame, _)| frame.pixels.len() * 4).sum()
+    }
+
+    pub fn current_frame(&self) -> usize {
+        self.current_frame
+    }
+
+    /// Pixels of the frame currently shown.
+    pub fn current_image(&self) -> &egui::ColorImage {
+        &self.frames[self.current_frame].0
+    }
+
+    /// True once a finite animation has played all its loops.
+    pub fn is_finished(&self) -> bool {
+        match self.loop_count {
+            LoopCount::Finite(n) if !self.loop_anyway => self.plays_completed >= n,
+            _ => false,
+        }
+    }
+
+    /// Advances playback by `dt`. Returns true if the displayed frame changed.
+    pub fn advance(&mut self, dt: Duration) -> bool {
+        if !self.playing || self.is_finished() {
+            return false;
+        }
+
+        let start = self.current_frame;
+        self.elapsed += dt;
+        while self.elapsed >= self.frames[self.current_frame].1 {
+            self.elapsed -= self.frames[self.current_frame].1;
+            if self.current_frame + 1 < self.frames.len() {
+                self.current_frame += 1;
+            } else {
+                self.plays_completed += 1;
+                if self.is_finished() {
+                    // Finite animations rest on their last frame
+                    self.elapsed = Duration::ZERO;
+                    break;
+                }
+                self.current_frame = 0;
+            }
+        }
+        self.current_frame != start
+    }
+
+    /// Rotates every frame 90° clockwise, matching `DynamicImage::rotate90`.
+    pub fn rotate90(&mut self) {
+        for (frame, _) in &mut self.frames {
+            let [w, h] = frame.size;
+            let mut pixels = vec![egui::Color32::TRANSPARENT; w * h];
+            for y in 0..h {
+                for x in 0..w {
+                    // (x, y) lands at column h - 1 - y, row x of the h-wide result
+                    pixels[x * h + (h - 1 - y)] = frame.pixels[y * w + x];
+                }
+            }
+            *frame = egui::ColorImage { size: [h, w], pixels };
+        }
+    }
+
+    /// Rotates every frame 90° counter-clockwise, matching `DynamicImage::rotate270`.
+    pub fn rotate270(&mut self) {
+        for (frame, _) in &mut self.frames {
+            let [w, h] = frame.size;
+            let mut pixels = vec![egui::Color32::TRANSPARENT; w * h];
+            for y in 0..h {
+                for x in 0..w {
+                    // (x, y) lands at column y, row w - 1 - x of the h-wide result
+                    pixels[(w - 1 - x) * h + y] = frame.pixels[y * w + x];
+                }
+            }
+            *frame = egui::ColorImage { size: [h, w], pixels };
+        }
+    }
+
+    /// Replaces every frame with `f` of it, e.g. to apply a color adjustment.
+    pub fn map_frames(&mut self, f: impl Fn(&image::DynamicImage) -> image::DynamicImage) {
+        let mapped: Vec<_> = self.frames_rgba()
+            .map(|(frame, _)| f(&image::DynamicImage::ImageRgba8(frame)).to_rgba8())
+            .collect();
+        for ((frame, _), buffer) in self.frames.iter_mut().zip(mapped) {
+            let size = [buffer.width() as usize, buffer.height() as usize];
+            *frame = egui::ColorImage::from_rgba_unmultiplied(size, buffer.as_raw());
+        }
+    }
+
+    /// Frames as straight-alpha RGBA with their delays, e.g. for re-encoding.
+    pub fn frames_rgba(&self) -> impl Iterator<Item = (image::RgbaImage, Duration)> + '_ {
+        self.frames.iter().map(|(frame, delay)| {
+            let raw = frame.pixels.iter().flat_map(|c| c.to_srgba_unmultiplied()).collect();
+            let buffer = image::RgbaImage::from_raw(frame.size[0] as u32, frame.size[1] as u32, raw)
+                .expect("frame buffer matches its size");
+            (buffer, *delay)
+        })
+    }
+
+    /// Total length of one play.
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|(_, delay)| *delay).sum()
+    }
+
+    /// Playback time since the start of the first frame.
+    pub fn position(&self) -> Duration {
+        self.frames[..self.current_frame].iter().map(|(_, delay)| *delay).sum::<Duration>() + self.elapsed
+    }
+
+    /// Shows frame `idx` from the start of its delay. A finished animation can
+    /// play again from there.
+    pub fn seek(&mut self, idx: usize) {
+        self.current_frame = idx.min(self.frames.len() - 1);
+        self.elapsed = Duration::ZERO;
+        self.plays_completed = 0;
+    }
+
+    /// Pauses and moves one frame forward or back, wrapping around.
+    pub fn step(&mut self, forward: bool) {
+        let n = self.frames.len();
+        self.playing = false;
+        self.seek(if forward { (self.current_frame + 1) % n } else { (self.current_frame + n - 1) % n });
+    }
+
+    /// Play/pause. Playing a finished animation starts it over.
+    pub fn toggle_playing(&mut self) {
+        if self.is_finished() {
+            self.restart();
+        } else {
+            self.playing = !self.playing;
+        }
+    }
+
+    /// Time until the next frame change, or `None` if playback is stopped.
+    pub fn time_until_next_frame(&self) -> Option<Duration> {
+        if !self.playing || self.is_finished() {
+            return None;
+        }
+        Some(self.frames[self.current_frame].1.saturating_sub(self.elapsed))
+    }
+
+    /// Starts playback over from the first frame.
+    pub fn restart(&mut self) {
+        self.current_frame = 0;
+        self.elapsed = Duration::ZERO;
+        self.plays_completed = 0;
+        self.playing = true;
+    }
+}
@@ -0,0 +1,110 @@
+//! Rotating or flipping many files at once, from the gallery selection.
+//!
+//! Files are rewritten one after another on a background thread. JPEGs only get
+//! their EXIF orientation changed, so their pixels are never re-encoded; other
+//! formats are decoded, transformed and saved with the export settings.
+
+use crate::animation::AnimationPlayer;
+use crate::config::Config;
+use crate::export;
+use crate::orientation::{self, Transform};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+
+/// A batch of files being transformed in the background.
+pub struct BatchJob {
+    pub transform: Transform,
+    pub total: usize,
+    /// Files written so far.
+    pub done: Vec<PathBuf>,
+    /// Files that couldn't be transformed, with the reason.
+    pub failed: Vec<(PathBuf, String)>,
+    rx: mpsc::Receiver<(PathBuf, Result<(), String>)>,
+    cancelled: Arc<AtomicBool>,
+    /// The worker has exited, having finished or been cancelled.
+    stopped: bool,
+}
+
+impl BatchJob {
+    /// Starts applying `transform` to each of `paths`.
+    pub fn start(paths: Vec<PathBuf>, transform: Transform, config: Config) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let worker_cancelled = cancelled.clone();
+        let total = paths.len();
+        std::thread::spawn(move || {
+            for path in paths {
+                if worker_cancelled.load(Ordering::Relaxed) {
+                    break;
+                }
+                let result = transform_file(&path, transform, &config);
+                if tx.send((path, result)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { transform, total, done: Vec::new(), failed: Vec::new(), rx, cancelled, stopped: false }
+    }
+
+    /// Collects results that arrived since the last call. Returns the files
+    /// rewritten in the meantime.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        loop {
+            match self.rx.try_recv() {
+                Ok((path, Ok(()))) => {
+                    self.done.push(path.clone());
+                    changed.push(path);
+                }
+                Ok((path, Err(e))) => self.failed.push((path, e)),
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.stopped = true;
+                    break;
+                }
+            }
+        }
+        changed
+    }
+
+    pub fn processed(&self) -> usize {
+        self.done.len() + self.failed.len()
+    }
+
+    /// Stops after the file being written now. Files already written stay changed.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// True once every file is handled, or the worker stopped after a cancel.
+    pub fn is_finished(&self) -> bool {
+        self.stopped
+    }
+}
+
+impl Drop for BatchJob {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+fn transform_file(path: &Path, transform: Transform, config: &Config) -> Result<(), String> {
+    if export::is_jpeg_path(path) && orientation::transform_jpeg_losslessly(path, &[transform])? {
+        return Ok(());
+    }
+    if let Some(mut animation) = AnimationPlayer::load(path) {
+        if !export::is_gif_path(path) {
+            return Err("Animated WebP and PNG files can't be saved".to_string());
+        }
+        animation.map_frames(|frame| transform.apply(frame));
+        let loop_count = animation.loop_count;
+        return export::save_gif_animation(animation.frames_rgba(), loop_count, path);
+    }
+    let img = orientation::open(path).map_err(|e| e.to_string())?;
+    export::save_image(&transform.apply(&img), path, config)
+}
@@ -0,0 +1,187 @@
+//! The blurred backdrop behind overlays, and the settings rows that tune it.
+//!
+//! Changes to the blur in Settings are staged: a swatch paints the top-bar
+//! gradient over the current image with the staged values, and they replace
+//! the real backdrop only when applied.
+
+use crate::config::{self, BlurFilter, Config};
+use eframe::egui;
+use egui::epaint::{Mesh, Vertex};
+
+/// Size of the preview swatch in points.
+const SWATCH_SIZE: egui::Vec2 = egui::vec2(280.0, 96.0);
+/// Height of the top-bar strip painted in the swatch.
+const SWATCH_BAR_HEIGHT: f32 = 44.0;
+
+/// Everything the backdrop is generated from.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BlurSettings {
+    pub resolution: u32,
+    pub filter: BlurFilter,
+    pub radius: f32,
+}
+
+impl BlurSettings {
+    pub fn from_config(config: &Config) -> Self {
+        Self { resolution: config.blur_resolution, filter: config.blur_filter, radius: config.blur_radius }
+    }
+
+    fn store(self, config: &mut Config) {
+        config.blur_resolution = self.resolution;
+        config.blur_filter = self.filter;
+        config.blur_radius = self.radius;
+    }
+}
+
+/// Blurs a thumbnail of `img` for use as an overlay backdrop.
+pub fn blur_image(img: &image::DynamicImage, settings: BlurSettings) -> egui::ColorImage {
+    // Downscale for performance first. Nearest aliases visibly through the blur;
+    // a smoothing filter costs little at these sizes but stays configurable.
+    let res = settings.resolution;
+    let thumb = img.resize(res, res, settings.filter.filter_type());
+    // Scale the radius with the source so the look stays the same. A true gaussian
+    // at that radius is too slow, so larger sources use the box approximation.
+    let sigma = settings.radius * res as f32 / 256.0;
+    let blurred = if res <= 256 { thumb.blur(sigma) } else { thumb.fast_blur(sigma) };
+    let rgba = blurred.to_rgba8();
+    egui::ColorImage::from_rgba_unmultiplied([rgba.width() as usize, rgba.height() as usize], rgba.as_raw())
+}
+
+/// Paints the part of `blur_tex` under `rect`, where `img_rect` is the on-screen
+/// area of the whole image, fading out towards `gradient_dir`.
+pub fn paint_gradient(painter: &egui::Painter, rect: egui::Rect, opacity: f32,
+                      blur_tex: &egui::TextureHandle, img_rect: egui::Rect,
+                      gradient_dir: &str) {
+    let intersect = rect.intersect(img_rect);
+    if !intersect.is_positive() { return; }
+
+    let uv_min = egui::pos2(
+        (intersect.min.x - img_rect.min.x) / img_rect.width(),
+        (intersect.min.y - img_rect.min.y) / img_rect.height(),
+    );
+    let uv_max = egui::pos2(
+        (intersect.max.x - img_rect.min.x) / img_rect.width(),
+        (intersect.max.y - img_rect.min.y) / img_rect.height(),
+    );
+
+    let col_full = egui::Color32::WHITE.linear_multiply(opacity);
+    let col_fade = egui::Color32::TRANSPARENT;
+
+    let mut mesh = Mesh::with_texture(blur_tex.id());
+
+    // Build gradient mesh based on direction
+    let (tl, tr, br, bl) = match gradient_dir {
+        "down" => (col_full, col_full, col_fade, col_fade),
+        "left" => (col_fade, col_full, col_full, col_fade),
+        "right" => (col_full, col_fade, col_fade, col_full),
+        _ => (col_full, col_full, col_full, col_full),
+    };
+
+    mesh.vertices.push(Vertex { pos: intersect.left_top(), uv: egui::pos2(uv_min.x, uv_min.y), color: tl });
+    mesh.vertices.push(Vertex { pos: intersect.right_top(), uv: egui::pos2(uv_max.x, uv_min.y), color: tr });
+    mesh.vertices.push(Vertex { pos: intersect.right_bottom(), uv: egui::pos2(uv_max.x, uv_max.y), color: br });
+    mesh.vertices.push(Vertex { pos: intersect.left_bottom(), uv: egui::pos2(uv_min.x, uv_max.y), color: bl });
+    mesh.add_triangle(0, 1, 2);
+    mesh.add_triangle(0, 2, 3);
+
+    painter.add(mesh);
+}
+
+/// The blur rows of the Settings window, holding the values not applied yet.
+pub struct BlurPicker {
+    draft: BlurSettings,
+    preview: Option<egui::TextureHandle>,
+    /// Settings `preview` was made with; cleared when the image changes.
+    preview_of: Option<BlurSettings>,
+}
+
+impl BlurPicker {
+    pub fn new(config: &Config) -> Self {
+        Self { draft: BlurSettings::from_config(config), preview: None, preview_of: None }
+    }
+
+    /// Makes the swatch regenerate, e.g. after another image was opened.
+    pub fn invalidate(&mut self) {
+        self.preview_of = None;
+    }
+
+    /// Adds the blur rows to a two-column grid. `image` and `texture` are the
+    /// current image and its on-screen texture. Returns true when the staged
+    /// settings were written to `config`.
+    pub fn show(&mut self, ui: &mut egui::Ui, image: Option<&image::DynamicImage>,
+                texture: Option<&egui::TextureHandle>, config: &mut Config) -> bool {
+        ui.label("Blur quality:");
+        ui.horizontal(|ui| {
+            for &res in config::BLUR_RESOLUTIONS {
+                ui.selectable_value(&mut self.draft.resolution, res, res.to_string());
+            }
+        });
+        ui.end_row();
+
+        ui.label("Blur filter:");
+        ui.horizontal(|ui| {
+            for filter in BlurFilter::ALL {
+                ui.selectable_value(&mut self.draft.filter, filter, filter.label());
+            }
+        })
+        .response
+        .on_hover_text("Fast can shimmer on detailed images; use it on slow machines");
+        ui.end_row();
+
+        ui.label("Blur radius:");
+        ui.add(egui::Slider::new(&mut self.draft.radius, config::BLUR_RADII).step_by(1.0).suffix(" px"));
+        ui.end_row();
+
+        ui.label("");
+        ui.vertical(|ui| self.paint_swatch(ui, image, texture, config.thumbnail_texture_options()));
+        ui.end_row();
+
+        let applied = BlurSettings::from_config(config);
+        let mut stored = false;
+        ui.label("");
+        ui.horizontal(|ui| {
+            let staged = self.draft != applied;
+            if ui.add_enabled(staged, egui::Button::new("Apply blur")).clicked() {
+                self.draft.store(config);
+                stored = true;
+            }
+            if ui.add_enabled(staged, egui::Button::new("Revert")).clicked() {
+                self.draft = applied;
+            }
+        });
+        ui.end_row();
+        stored
+    }
+
+    /// Paints the image with the top-bar gradient made from the staged settings.
+    fn paint_swatch(&mut self, ui: &mut egui::Ui, image: Option<&image::DynamicImage>,
+                    texture: Option<&egui::TextureHandle>, options: egui::TextureOptions) {
+        let (Some(image), Some(texture)) = (image, texture) else {
+            ui.label(egui::RichText::new("Open an image to preview the blur").color(egui::Color32::GRAY));
+            return;
+        };
+        if self.preview_of != Some(self.draft) {
+            self.preview_of = Some(self.draft);
+            let blurred = blur_image(image, self.draft);
+            match &mut self.preview {
+                Some(preview) => preview.set(blurred, options),
+                None => self.preview = Some(ui.ctx().load_texture("blur_preview", blurred, options)),
+            }
+        }
+        let Some(preview) = &self.preview else { return };
+
+        let (swatch, _) = ui.allocate_exact_size(SWATCH_SIZE, egui::Sense::hover());
+        let painter = ui.painter_at(swatch);
+        // Cover the swatch with the image like a zoomed-in view would
+        let size = texture.size_vec2();
+        let scale = (swatch.width() / size.x).max(swatch.height() / size.y);
+        let img_rect = egui::Rect::from_center_size(swatch.center(), size * scale);
+        painter.image(texture.id(), img_rect, egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)), egui::Color32::WHITE);
+
+        let bar = egui::Rect::from_min_size(swatch.min, egui::vec2(swatch.width(), SWATCH_BAR_HEIGHT));
+        paint_gradient(&painter, bar, 1.0, preview, img_rect, "down");
+        painter.text(bar.left_center() + egui::vec2(10.0, 0.0), egui::Align2::LEFT_CENTER, "Overlay text",
+                     egui::FontId::proportional(14.0), egui::Color32::WHITE);
+        ui.painter().rect_stroke(swatch, 2.0, egui::Stroke::new(1.0, ui.visuals().widgets.noninteractive.bg_stroke.color));
+    }
+}
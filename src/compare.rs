@@ -0,0 +1,173 @@
+//! A/B comparison of the image against a reference taken from the clipboard.
+//!
+//! The reference is aligned at the top-left corner and drawn at the image's
+//! scale, so images of different sizes still line up pixel for pixel where they
+//! overlap. Overlay mode fades the reference over the image; difference mode
+//! shows the per-channel difference, black where the two match.
+
+use eframe::egui;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CompareMode {
+    Overlay,
+    Difference,
+}
+
+impl CompareMode {
+    pub const ALL: [CompareMode; 2] = [Self::Overlay, Self::Difference];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Overlay => "Overlay",
+            Self::Difference => "Difference",
+        }
+    }
+}
+
+/// What the user chose in the compare panel.
+pub enum CompareAction {
+    /// Take a new reference from the clipboard.
+    Reload,
+    Close,
+}
+
+/// How far the image is from the reference where they overlap.
+struct DiffStats {
+    overlap: [u32; 2],
+    differing: u64,
+    /// Largest difference of any channel, 0-255.
+    max_delta: u8,
+}
+
+/// The "Compare" window and the textures drawn over the image.
+pub struct ComparePanel {
+    pub mode: CompareMode,
+    /// Opacity of the reference in overlay mode, 0-1.
+    pub opacity: f32,
+    reference: image::RgbaImage,
+    reference_texture: egui::TextureHandle,
+    diff_texture: egui::TextureHandle,
+    image_size: [u32; 2],
+    stats: DiffStats,
+}
+
+impl ComparePanel {
+    /// Compares `image` (the image as shown, drawings included) with `reference`.
+    pub fn new(ctx: &egui::Context, reference: image::RgbaImage, image: &image::RgbaImage, options: egui::TextureOptions) -> Self {
+        let reference_texture = ctx.load_texture("compare_reference", to_color_image(&reference), options);
+        let (diff, stats) = difference(image, &reference);
+        let diff_texture = ctx.load_texture("compare_diff", to_color_image(&diff), options);
+        Self {
+            mode: CompareMode::Overlay,
+            opacity: 0.5,
+            reference,
+            reference_texture,
+            diff_texture,
+            image_size: [image.width(), image.height()],
+            stats,
+        }
+    }
+
+    /// Recomputes the difference after the image changed.
+    pub fn set_image(&mut self, image: &image::RgbaImage, options: egui::TextureOptions) {
+        let (diff, stats) = difference(image, &self.reference);
+        self.diff_texture.set(to_color_image(&diff), options);
+        self.image_size = [image.width(), image.height()];
+        self.stats = stats;
+    }
+
+    /// Texture to draw over the image, its size in image pixels, and the tint to draw it with.
+    pub fn layer(&self) -> (&egui::TextureHandle, egui::Vec2, egui::Color32) {
+        match self.mode {
+            CompareMode::Overlay => {
+                (&self.reference_texture, self.reference_texture.size_vec2(), egui::Color32::WHITE.gamma_multiply(self.opacity))
+            }
+            CompareMode::Difference => (&self.diff_texture, self.diff_texture.size_vec2(), egui::Color32::WHITE),
+        }
+    }
+
+    /// Draws the window.
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<CompareAction> {
+        let mut action = None;
+        let mut open = true;
+
+        egui::Window::new("Compare")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for mode in CompareMode::ALL {
+                        ui.selectable_value(&mut self.mode, mode, mode.label());
+                    }
+                });
+                if self.mode == CompareMode::Overlay {
+                    ui.add(egui::Slider::new(&mut self.opacity, 0.0..=1.0).text("Reference"));
+                }
+
+                let [w, h] = self.image_size;
+                let (rw, rh) = self.reference.dimensions();
+                if [w, h] != [rw, rh] {
+                    ui.label(
+                        egui::RichText::new(format!("Sizes differ: image {} x {}, reference {} x {}. Aligned at the top-left.", w, h, rw, rh))
+                            .color(egui::Color32::YELLOW),
+                    );
+                }
+                let stats = &self.stats;
+                let [ow, oh] = stats.overlap;
+                if stats.differing == 0 {
+                    ui.label(format!("Identical over {} x {}", ow, oh));
+                } else {
+                    let share = stats.differing as f64 / (ow as f64 * oh as f64) * 100.0;
+                    ui.label(format!("{} pixels differ ({:.2}%), by up to {}", stats.differing, share, stats.max_delta));
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("Reload").on_hover_text("Take a new reference from the clipboard").clicked() {
+                        action = Some(CompareAction::Reload);
+                    }
+                });
+            });
+
+        if !open {
+            action = Some(CompareAction::Close);
+        }
+        action
+    }
+}
+
+/// Per-channel difference of `image` and `reference`, aligned at the top-left and
+/// sized to cover both. Where only the image exists the result is transparent so
+/// the image shows through; where only the reference exists, it is shown faded.
+fn difference(image: &image::RgbaImage, reference: &image::RgbaImage) -> (image::RgbaImage, DiffStats) {
+    let (w, h) = (image.width().max(reference.width()), image.height().max(reference.height()));
+    let overlap = [image.width().min(reference.width()), image.height().min(reference.height())];
+    let mut out = image::RgbaImage::new(w, h);
+    let mut differing = 0;
+    let mut max_delta = 0;
+
+    for (x, y, pixel) in out.enumerate_pixels_mut() {
+        let a = image.get_pixel_checked(x, y);
+        let b = reference.get_pixel_checked(x, y);
+        *pixel = match (a, b) {
+            (Some(a), Some(b)) => {
+                let delta: [u8; 4] = std::array::from_fn(|i| a.0[i].abs_diff(b.0[i]));
+                let largest = delta.iter().copied().max().unwrap_or(0);
+                if largest > 0 {
+                    differing += 1;
+                    max_delta = max_delta.max(largest);
+                }
+                // Alpha differences show up as gray so transparent edits aren't invisible
+                let [r, g, b, alpha] = delta;
+                image::Rgba([r.max(alpha), g.max(alpha), b.max(alpha), 255])
+            }
+            (None, Some(b)) => image::Rgba([b.0[0], b.0[1], b.0[2], b.0[3] / 2]),
+            _ => image::Rgba([0, 0, 0, 0]),
+        };
+    }
+    (out, DiffStats { overlap, differing, max_delta })
+}
+
+fn to_color_image(rgba: &image::RgbaImage) -> egui::ColorImage {
+    egui::ColorImage::from_rgba_unmultiplied([rgba.width() as usize, rgba.height() as usize], rgba.as_raw())
+}
@@ -0,0 +1,486 @@
+//! Persistent user settings.
+//!
+//! Settings are stored as TOML in `$XDG_CONFIG_HOME/sakura/config.toml`
+//! (falling back to `~/.config/sakura/config.toml`). A missing or malformed file
+//! yields the defaults so a bad edit never keeps the viewer from starting.
+
+use crate::adjust::AdjustPreset;
+use crate::export::ChromaSubsampling;
+use crate::viewer::DrawingTool;
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Maximum number of entries kept in the recently-opened list.
+pub const MAX_RECENT_FILES: usize = 10;
+
+/// Selectable resolutions for the thumbnail the overlay blur is generated from.
+pub const BLUR_RESOLUTIONS: &[u32] = &[256, 512, 1024];
+
+/// Range of the overlay blur radius, in pixels of a 256-pixel blur source.
+pub const BLUR_RADII: std::ops::RangeInclusive<f32> = 10.0..=120.0;
+
+/// Choices for the largest image edge kept in memory; 0 means no limit.
+pub const MAX_DIMENSION_CHOICES: &[u32] = &[4096, 8192, 16384, 0];
+
+/// Selectable magnifications of the loupe, relative to the current view.
+pub const LOUPE_MAGNIFICATIONS: &[u32] = &[2, 4, 8, 16];
+
+/// Upper end of the pencil spacing slider, in image pixels.
+pub const MAX_PENCIL_MIN_DISTANCE: f32 = 10.0;
+
+/// Most pixel-edit snapshots the undo history can be set to keep.
+pub const MAX_UNDO_IMAGE_SNAPSHOTS: usize = 100;
+
+/// Most drawing steps the undo history can be set to keep.
+pub const MAX_UNDO_DRAWING_STEPS: usize = 10000;
+
+/// Range of the zoom factor applied per scroll notch.
+pub const SCROLL_ZOOM_STEPS: std::ops::RangeInclusive<f32> = 1.01..=1.5;
+
+/// Filter used to shrink the image before blurring it for the overlay backdrop.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum BlurFilter {
+    /// Cheapest, but aliasing can show through the blur as shimmering blotches.
+    Nearest,
+    /// Smooth and close to free at blur-source sizes.
+    Triangle,
+    /// Sharpest resample, slightly slower.
+    Lanczos3,
+}
+
+impl BlurFilter {
+    pub const ALL: [BlurFilter; 3] = [Self::Nearest, Self::Triangle, Self::Lanczos3];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Nearest => "Fast",
+            Self::Triangle => "Smooth",
+            Self::Lanczos3 => "Best",
+        }
+    }
+
+    pub fn filter_type(self) -> image::imageops::FilterType {
+        match self {
+            Self::Nearest => image::imageops::FilterType::Nearest,
+            Self::Triangle => image::imageops::FilterType::Triangle,
+            Self::Lanczos3 => image::imageops::FilterType::Lanczos3,
+        }
+    }
+}
+
+/// How a texture is sampled when drawn at other than its native size.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum TextureFilter {
+    /// Bilinear; soft, good for photos.
+    Smooth,
+    /// Nearest pixel; crisp edges for pixel art and inspecting detail.
+    Sharp,
+}
+
+impl TextureFilter {
+    pub const ALL: [TextureFilter; 2] = [Self::Smooth, Self::Sharp];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Smooth => "Smooth",
+            Self::Sharp => "Sharp",
+        }
+    }
+
+    fn egui_filter(self) -> egui::TextureFilter {
+        match self {
+            Self::Smooth => egui::TextureFilter::Linear,
+            Self::Sharp => egui::TextureFilter::Nearest,
+        }
+    }
+}
+
+/// Zoom a newly opened image starts at.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum OpenZoom {
+    /// One image pixel per screen pixel; tiny images are enlarged to stay visible.
+    Native,
+    /// The whole image in the window. Only larger images are shrunk.
+    Fit,
+    /// The window covered by the image, cropping what sticks out.
+    Fill,
+}
+
+impl OpenZoom {
+    pub const ALL: [OpenZoom; 3] = [Self::Native, Self::Fit, Self::Fill];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Native => "Native",
+            Self::Fit => "Fit",
+            Self::Fill => "Fill",
+        }
+    }
+
+    pub fn hint(self) -> &'static str {
+        match self {
+            Self::Native => "Show images at their own size",
+            Self::Fit => "Shrink images larger than the window to fit it",
+            Self::Fill => "Scale images to cover the window, centered",
+        }
+    }
+}
+
+/// What the viewer does after a while without input.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum IdleAction {
+    Nothing,
+    /// Darken the window.
+    Dim,
+    /// Step through the folder, like a photo frame.
+    Slideshow,
+}
+
+impl IdleAction {
+    pub const ALL: [IdleAction; 3] = [Self::Nothing, Self::Dim, Self::Slideshow];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Nothing => "Nothing",
+            Self::Dim => "Dim",
+            Self::Slideshow => "Slideshow",
+        }
+    }
+}
+
+/// Corner of the window the info overlay sits in.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum HudCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl HudCorner {
+    pub const ALL: [HudCorner; 4] = [Self::TopLeft, Self::TopRight, Self::BottomLeft, Self::BottomRight];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::TopLeft => "Top left",
+            Self::TopRight => "Top right",
+            Self::BottomLeft => "Bottom left",
+            Self::BottomRight => "Bottom right",
+        }
+    }
+
+    pub fn align(self) -> egui::Align2 {
+        match self {
+            Self::TopLeft => egui::Align2::LEFT_TOP,
+            Self::TopRight => egui::Align2::RIGHT_TOP,
+            Self::BottomLeft => egui::Align2::LEFT_BOTTOM,
+            Self::BottomRight => egui::Align2::RIGHT_BOTTOM,
+        }
+    }
+}
+
+/// What the corner info overlay shows.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(default)]
+pub struct HudItems {
+    /// File name.
+    pub name: bool,
+    /// Place in the folder, as "3 / 40".
+    pub position: bool,
+    /// Current zoom.
+    pub zoom: bool,
+    /// Image size in pixels.
+    pub resolution: bool,
+}
+
+impl Default for HudItems {
+    fn default() -> Self {
+        Self { name: true, position: true, zoom: true, resolution: false }
+    }
+}
+
+/// How the standalone window presents itself to the window manager.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowMode {
+    /// Pick from the desktop session: floating on tiling compositors, resizable elsewhere.
+    #[default]
+    Auto,
+    /// Fixed size that follows each image. Tiling compositors like Hyprland float
+    /// non-resizable windows, much like sxiv/nsxiv.
+    Floating,
+    /// An ordinary window the user sizes, as on GNOME, KDE and most X11 desktops.
+    Resizable,
+    /// Like floating, without title bar or borders.
+    Borderless,
+}
+
+impl WindowMode {
+    pub const ALL: [WindowMode; 4] = [Self::Auto, Self::Floating, Self::Resizable, Self::Borderless];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Auto => "Automatic",
+            Self::Floating => "Floating",
+            Self::Resizable => "Resizable",
+            Self::Borderless => "Borderless",
+        }
+    }
+
+    /// Parses the name used on the command line and in the config file.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| format!("{:?}", mode).eq_ignore_ascii_case(name))
+    }
+
+    /// The mode saved in the config file.
+    pub fn configured() -> Self {
+        Config::load().window_mode
+    }
+
+    /// `self`, or for `Auto` the mode suiting the running session.
+    pub fn resolve(self) -> Self {
+        if self != Self::Auto {
+            return self;
+        }
+        // Tiling compositors float fixed-size windows; elsewhere that just blocks resizing
+        let tiling_socket = ["HYPRLAND_INSTANCE_SIGNATURE", "SWAYSOCK", "I3SOCK"]
+            .iter()
+            .any(|var| std::env::var_os(var).is_some());
+        let tiling_desktop = std::env::var("XDG_CURRENT_DESKTOP").is_ok_and(|desktop| {
+            desktop.split(':').any(|d| ["hyprland", "sway", "i3"].contains(&d.to_lowercase().as_str()))
+        });
+        if tiling_socket || tiling_desktop { Self::Floating } else { Self::Resizable }
+    }
+
+    /// Whether the window is resized to fit each image.
+    pub fn fits_images(self) -> bool {
+        matches!(self.resolve(), Self::Floating | Self::Borderless)
+    }
+}
+
+/// User settings persisted between sessions.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// Recently opened images, newest first.
+    pub recent_files: Vec<PathBuf>,
+    /// Window behavior, applied at startup. `--window-mode` overrides it.
+    pub window_mode: WindowMode,
+    /// Folder of the last opened image, where the open dialog starts.
+    pub last_directory: Option<PathBuf>,
+    /// Longest edge of the thumbnail blurred behind overlays. Higher is smoother but slower.
+    pub blur_resolution: u32,
+    /// Downscale filter for the blur source.
+    pub blur_filter: BlurFilter,
+    /// Radius of the overlay blur in pixels of a 256-pixel source; scaled with `blur_resolution`.
+    pub blur_radius: f32,
+    /// Sampling of the image when zoomed in past 100%.
+    pub zoom_in_filter: TextureFilter,
+    /// Sampling of the image when zoomed out below 100%.
+    pub zoom_out_filter: TextureFilter,
+    /// Sampling of gallery thumbnails and the overlay blur.
+    pub thumbnail_filter: TextureFilter,
+    /// JPEG encoder quality, 1-100.
+    pub jpeg_quality: u8,
+    /// JPEG chroma subsampling. 4:4:4 keeps colored text and edges crisp.
+    pub jpeg_subsampling: ChromaSubsampling,
+    /// Write PNGs with at most 256 colors as indexed images to keep them small.
+    pub png_indexed: bool,
+    /// Also store drawings in saved PNGs, so they can be edited again after reopening.
+    pub embed_drawings: bool,
+    /// JPEG quality of images copied as data URLs, 1-100. Lower keeps pasted URLs short.
+    pub data_url_quality: u8,
+    /// Ask before closing, or leaving the strip on another image, with unsaved
+    /// drawings. When off, they are discarded.
+    pub confirm_close_unsaved: bool,
+    /// Show the new file after converting instead of staying on the original.
+    pub open_after_convert: bool,
+    /// Left click shows the next image and right click the previous, outside drawing mode.
+    pub click_to_advance: bool,
+    /// Step to the next image once the one shown has been sent over LocalSend.
+    pub advance_after_send: bool,
+    /// Ask before a conversion replaces an existing file.
+    pub confirm_overwrite: bool,
+    /// Ask before a save or conversion keeps only one frame of an animation.
+    pub confirm_flatten_animation: bool,
+    /// Ask before a conversion loses transparency, colors or bit depth.
+    pub confirm_lossy_convert: bool,
+    /// Color transparent pixels are flattened onto for formats without alpha.
+    pub flatten_background: [u8; 3],
+    /// Ask before reverting discards unsaved edits.
+    pub confirm_revert: bool,
+    /// Ask before Delete moves the image to the trash.
+    pub confirm_delete: bool,
+    /// Pixel edits that can be undone. Each keeps a full copy of the image, so
+    /// big images want few. 0 keeps none.
+    pub undo_image_snapshots: usize,
+    /// Drawing steps that can be undone. They are cheap, so many can be kept.
+    pub undo_drawing_steps: usize,
+    /// Images with a longer edge are downsampled after decoding. 0 disables the limit.
+    pub max_image_dimension: u32,
+    /// How much the loupe enlarges the view under the cursor.
+    pub loupe_magnification: u32,
+    /// Zoom each image opens at.
+    pub open_zoom: OpenZoom,
+    /// What happens after `idle_minutes` without input. Any input wakes the viewer.
+    pub idle_action: IdleAction,
+    /// Minutes without input before `idle_action`, and before frame mode goes back to its slideshow.
+    pub idle_minutes: u32,
+    /// Seconds each image shows in the slideshow.
+    pub slideshow_seconds: u32,
+    /// Keep a small info overlay in a corner of the window, whether or not the top bar shows.
+    pub show_hud: bool,
+    /// Corner the info overlay sits in.
+    pub hud_corner: HudCorner,
+    /// What the info overlay shows.
+    pub hud_items: HudItems,
+    /// Zoom factor per scroll notch. Lower suits trackpads, which send many small scrolls.
+    pub scroll_zoom_step: f32,
+    /// Scrolling down zooms in, for natural-scrolling setups.
+    pub invert_scroll_zoom: bool,
+    /// Scroll pans up and down the image, and scrolling on past its top or bottom
+    /// shows the previous or next image. Ctrl+scroll zooms.
+    pub scroll_pages: bool,
+    /// Pencil points closer than this many image pixels to the previous one are dropped.
+    pub pencil_min_distance: f32,
+    /// Briefly outline the area changed by a pixel edit, such as applied adjustments.
+    pub highlight_edits: bool,
+    /// Drawing tool selected at startup.
+    pub drawing_tool: DrawingTool,
+    /// Start with drawing mode on, for quick markup.
+    pub start_in_drawing_mode: bool,
+    /// Drawing color at startup.
+    pub drawing_color: [u8; 3],
+    /// Pencil and shape size at startup.
+    pub drawing_size: f32,
+    /// Locale for numbers and dates, e.g. "de_DE". Empty follows the system.
+    pub locale: String,
+    /// Named color adjustments offered in the adjust panel.
+    pub adjust_presets: Vec<AdjustPreset>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            recent_files: Vec::new(),
+            window_mode: WindowMode::Auto,
+            last_directory: None,
+            blur_resolution: 256,
+            blur_filter: BlurFilter::Triangle,
+            blur_radius: 60.0,
+            zoom_in_filter: TextureFilter::Smooth,
+            zoom_out_filter: TextureFilter::Smooth,
+            thumbnail_filter: TextureFilter::Smooth,
+            jpeg_quality: 90,
+            jpeg_subsampling: ChromaSubsampling::Yuv444,
+            png_indexed: false,
+            embed_drawings: false,
+            data_url_quality: 80,
+            confirm_close_unsaved: true,
+            open_after_convert: true,
+            click_to_advance: false,
+            advance_after_send: false,
+            confirm_overwrite: true,
+            confirm_flatten_animation: true,
+            confirm_lossy_convert: true,
+            flatten_background: [255, 255, 255],
+            confirm_revert: true,
+            confirm_delete: true,
+            undo_image_snapshots: 10,
+            undo_drawing_steps: 500,
+            max_image_dimension: 16384,
+            loupe_magnification: 4,
+            open_zoom: OpenZoom::Fit,
+            idle_action: IdleAction::Nothing,
+            idle_minutes: 5,
+            slideshow_seconds: 10,
+            show_hud: false,
+            hud_corner: HudCorner::BottomLeft,
+            hud_items: HudItems::default(),
+            scroll_zoom_step: 1.15,
+            invert_scroll_zoom: false,
+            scroll_pages: false,
+            pencil_min_distance: 1.0,
+            highlight_edits: true,
+            drawing_tool: DrawingTool::Pencil,
+            start_in_drawing_mode: false,
+            drawing_color: [255, 0, 0],
+            drawing_size: 5.0,
+            locale: String::new(),
+            adjust_presets: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config from disk, falling back to defaults on any error.
+    pub fn load() -> Self {
+        config_path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|text| toml::from_str::<Config>(&text).ok())
+            .map(|mut config| {
+                config.prune_recent_files();
+                config
+            })
+            .unwrap_or_default()
+    }
+
+    /// Texture options for the image itself. The GPU picks the zoom-in or zoom-out
+    /// filter by how large the image is drawn, so zooming needs no re-upload.
+    pub fn display_texture_options(&self) -> egui::TextureOptions {
+        egui::TextureOptions {
+            magnification: self.zoom_in_filter.egui_filter(),
+            minification: self.zoom_out_filter.egui_filter(),
+            ..Default::default()
+        }
+    }
+
+    /// Texture options for thumbnails and the overlay blur.
+    pub fn thumbnail_texture_options(&self) -> egui::TextureOptions {
+        let filter = self.thumbnail_filter.egui_filter();
+        egui::TextureOptions { magnification: filter, minification: filter, ..Default::default() }
+    }
+
+    /// Writes the config to disk, creating the config directory if needed.
+    pub fn save(&self) -> Result<(), String> {
+        let path = config_path().ok_or("No config directory available")?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let text = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| e.to_string())
+    }
+
+    /// Moves `path` to the front of the recent files list, capped at `MAX_RECENT_FILES`.
+    pub fn add_recent_file(&mut self, path: &Path) {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.recent_files.retain(|p| p != &path);
+        self.recent_files.insert(0, path);
+        self.recent_files.truncate(MAX_RECENT_FILES);
+    }
+
+    /// Drops recent entries whose files no longer exist, and any duplicates.
+    pub fn prune_recent_files(&mut self) {
+        let mut seen = Vec::new();
+        self.recent_files.retain(|p| {
+            let keep = p.is_file() && !seen.contains(p);
+            seen.push(p.clone());
+            keep
+        });
+        self.recent_files.truncate(MAX_RECENT_FILES);
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("sakura").join("config.toml"))
+}
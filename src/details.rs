@@ -0,0 +1,169 @@
+//! Extra image details for the expanded info panel: EXIF fields, print
+//! resolution and a color histogram.
+//!
+//! Only read when the user asks for the expanded view. EXIF is parsed with
+//! `kamadak-exif`, which finds it in JPEG, PNG, WebP and TIFF files. The
+//! resolution comes from EXIF when present, otherwise from the PNG `pHYs`
+//! chunk or the JPEG JFIF header.
+
+use eframe::egui;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Pixels sampled at most for the histogram. Larger images are strided.
+const HISTOGRAM_SAMPLES: u64 = 1 << 20;
+
+/// EXIF tags shown, in display order. The camera model, exposure, focal length
+/// and capture time are read with the basic info instead, see `exif_value`.
+const EXIF_TAGS: &[(exif::Tag, &str)] = &[
+    (exif::Tag::Make, "Camera make"),
+    (exif::Tag::LensModel, "Lens"),
+    (exif::Tag::Flash, "Flash"),
+    (exif::Tag::Orientation, "Orientation"),
+    (exif::Tag::Software, "Software"),
+    (exif::Tag::Artist, "Artist"),
+    (exif::Tag::Copyright, "Copyright"),
+    (exif::Tag::GPSLatitude, "Latitude"),
+    (exif::Tag::GPSLongitude, "Longitude"),
+];
+
+/// Details read for the expanded info panel.
+#[derive(Clone)]
+pub struct ImageDetails {
+    /// Label and value of each EXIF field present.
+    pub exif: Vec<(&'static str, String)>,
+    /// Horizontal and vertical dots per inch, if the file records them.
+    pub dpi: Option<(f64, f64)>,
+    pub histogram: Histogram,
+}
+
+impl ImageDetails {
+    /// Reads the details of `img`, decoded from `path` if it came from a file.
+    pub fn read(path: Option<&Path>, img: &image::DynamicImage) -> Self {
+        let exif = path.and_then(read_exif);
+        let exif_dpi = exif.as_ref().and_then(exif_dpi);
+        Self {
+            exif: exif.as_ref().map(exif_fields).unwrap_or_default(),
+            dpi: exif_dpi.or_else(|| path.and_then(container_dpi)),
+            histogram: Histogram::of(img),
+        }
+    }
+}
+
+/// Counts of each 8-bit level per channel.
+#[derive(Clone)]
+pub struct Histogram {
+    /// Red, green, blue and luma counts.
+    pub channels: [[u32; 256]; 4],
+}
+
+impl Histogram {
+    fn of(img: &image::DynamicImage) -> Self {
+        let rgb = img.to_rgb8();
+        let pixels = rgb.width() as u64 * rgb.height() as u64;
+        let step = (pixels / HISTOGRAM_SAMPLES).max(1) as usize;
+        let mut channels = [[0u32; 256]; 4];
+        for pixel in rgb.pixels().step_by(step) {
+            let [r, g, b] = pixel.0;
+            channels[0][r as usize] += 1;
+            channels[1][g as usize] += 1;
+            channels[2][b as usize] += 1;
+            let luma = 0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32;
+            channels[3][luma.round() as usize] += 1;
+        }
+        Self { channels }
+    }
+
+    /// Draws the red, green and blue curves over a gray luma area.
+    pub fn show(&self, ui: &mut egui::Ui, size: egui::Vec2) {
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, egui::Color32::from_black_alpha(120));
+
+        // Scale to the tallest bin, ignoring pure black and white, which often spike
+        let peak = self.channels.iter()
+            .flat_map(|counts| counts[1..255].iter().copied())
+            .max()
+            .unwrap_or(0)
+            .max(1) as f32;
+        let point = |level: usize, count: u32| {
+            let x = rect.left() + level as f32 / 255.0 * rect.width();
+            let y = rect.bottom() - (count as f32 / peak).min(1.0) * rect.height();
+            egui::pos2(x, y)
+        };
+
+        for (level, &count) in self.channels[3].iter().enumerate() {
+            let top = point(level, count);
+            painter.line_segment([top, egui::pos2(top.x, rect.bottom())], egui::Stroke::new(rect.width() / 256.0 + 0.5, egui::Color32::from_gray(90)));
+        }
+        let colors = [egui::Color32::from_rgb(230, 70, 70), egui::Color32::from_rgb(70, 200, 70), egui::Color32::from_rgb(80, 120, 240)];
+        for (counts, color) in self.channels[..3].iter().zip(colors) {
+            let line = counts.iter().enumerate().map(|(level, &count)| point(level, count)).collect();
+            painter.add(egui::Shape::line(line, egui::Stroke::new(1.0, color)));
+        }
+    }
+}
+
+/// The EXIF data of `path`, if it has any.
+pub fn read_exif(path: &Path) -> Option<exif::Exif> {
+    let file = File::open(path).ok()?;
+    exif::Reader::new().read_from_container(&mut BufReader::new(file)).ok()
+}
+
+/// Field `tag` of the primary image as shown to the user, with its unit, e.g.
+/// "f/2.8" or "1/250 s". None when it is missing or empty.
+pub fn exif_value(exif: &exif::Exif, tag: exif::Tag) -> Option<String> {
+    let field = exif.get_field(tag, exif::In::PRIMARY)?;
+    let value = field.display_value().with_unit(exif).to_string();
+    // Strings come quoted, and unset ones as empty quotes
+    let value = value.trim_matches('"').trim().to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+fn exif_fields(exif: &exif::Exif) -> Vec<(&'static str, String)> {
+    EXIF_TAGS.iter()
+        .filter_map(|&(tag, label)| Some((label, exif_value(exif, tag)?)))
+        .collect()
+}
+
+fn exif_dpi(exif: &exif::Exif) -> Option<(f64, f64)> {
+    let rational = |tag| match exif.get_field(tag, exif::In::PRIMARY)?.value {
+        exif::Value::Rational(ref v) => v.first().map(|r| r.to_f64()),
+        _ => None,
+    };
+    let per_unit = (rational(exif::Tag::XResolution)?, rational(exif::Tag::YResolution)?);
+    let unit = exif.get_field(exif::Tag::ResolutionUnit, exif::In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .unwrap_or(2);
+    match unit {
+        2 => Some(per_unit),
+        3 => Some((per_unit.0 * 2.54, per_unit.1 * 2.54)),
+        _ => None,
+    }
+}
+
+/// Resolution stored by the file format itself, outside EXIF.
+fn container_dpi(path: &Path) -> Option<(f64, f64)> {
+    let mut header = [0u8; 18];
+    File::open(path).ok()?.read_exact(&mut header).ok()?;
+
+    if header.starts_with(b"\x89PNG") {
+        let reader = png::Decoder::new(BufReader::new(File::open(path).ok()?)).read_info().ok()?;
+        let dims = reader.info().pixel_dims?;
+        // Pixels per meter; an unspecified unit only gives the aspect ratio
+        return (dims.unit == png::Unit::Meter)
+            .then_some((dims.xppu as f64 * 0.0254, dims.yppu as f64 * 0.0254));
+    }
+    // JPEG starting with a JFIF APP0 segment: units, then 16-bit X and Y densities
+    if header.starts_with(&[0xFF, 0xD8, 0xFF, 0xE0]) && &header[6..11] == b"JFIF\0" {
+        let x = u16::from_be_bytes([header[14], header[15]]) as f64;
+        let y = u16::from_be_bytes([header[16], header[17]]) as f64;
+        return match header[13] {
+            1 => Some((x, y)),
+            2 => Some((x * 2.54, y * 2.54)),
+            _ => None,
+        };
+    }
+    None
+}
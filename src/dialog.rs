@@ -0,0 +1,27 @@
+//! Keyboard handling shared by dialog windows.
+//!
+//! Escape closes the dialog in front and Enter picks a dialog's default
+//! action. Escape is taken from the frame's input before any viewer shortcut
+//! reads it, so it can't also leave drawing mode or close the gallery behind
+//! the dialog. Modal dialogs also keep Tab focus on their own widgets.
+
+use eframe::egui;
+
+/// Takes Escape from this frame's input. Returns whether it was pressed.
+pub fn take_escape(ctx: &egui::Context) -> bool {
+    ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape))
+}
+
+/// Whether Enter was pressed this frame. Check it after the dialog's widgets,
+/// and only when none of its buttons was clicked: a button reached with Tab is
+/// pressed by Enter itself.
+pub fn enter_pressed(ctx: &egui::Context) -> bool {
+    ctx.input(|i| i.key_pressed(egui::Key::Enter))
+}
+
+/// Makes the window behind `response` modal from the next frame on: widgets
+/// behind it can't be clicked or focused, so Tab cycles within it. The window
+/// should use `egui::Order::Foreground` to stay above the overlay bars.
+pub fn trap_focus(ctx: &egui::Context, response: &egui::Response) {
+    ctx.memory_mut(|m| m.set_modal_layer(response.layer_id));
+}
@@ -0,0 +1,201 @@
+//! Listing the images of a folder without blocking the first paint.
+//!
+//! Huge folders take a while to enumerate, so the sorted list is built on a
+//! background thread. Until it arrives, `neighbor` steps through the folder by
+//! name with a single streaming pass that keeps nothing but the best candidate.
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use crate::IMAGE_EXTENSIONS;
+
+/// A folder listing running in the background.
+pub struct DirScan {
+    rx: mpsc::Receiver<Vec<PathBuf>>,
+}
+
+impl DirScan {
+    /// Starts listing the images in `dir`.
+    pub fn start(dir: &Path) -> Self {
+        let dir = dir.to_path_buf();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = tx.send(list_images(&dir));
+        });
+        Self { rx }
+    }
+
+    /// The sorted listing, once it is ready.
+    pub fn poll(&self) -> Option<Vec<PathBuf>> {
+        self.rx.try_recv().ok()
+    }
+}
+
+/// Canonical form of `path` as it appears in a listing: the canonical folder joined
+/// with the file name. Unlike canonicalizing the file, this keeps symlinked images
+/// under their own name.
+pub fn list_key(path: &Path) -> Option<PathBuf> {
+    Some(folder_of(path).canonicalize().ok()?.join(path.file_name()?))
+}
+
+/// All images in `dir`, sorted by name.
+fn list_images(dir: &Path) -> Vec<PathBuf> {
+    let Ok(dir) = dir.canonicalize() else { return Vec::new() };
+    let mut images: Vec<PathBuf> = image_names(&dir).map(|name| dir.join(name)).collect();
+    images.sort();
+    images
+}
+
+/// The image after (or before) `current` in its folder by name, wrapping around.
+pub fn neighbor(current: &Path, forward: bool) -> Option<PathBuf> {
+    let dir = folder_of(current);
+    let name = current.file_name()?;
+
+    // Nearest name past `current`, plus the wrap-around target at the other end
+    let mut nearest: Option<OsString> = None;
+    let mut wrap: Option<OsString> = None;
+    for candidate in image_names(dir) {
+        if candidate == name {
+            continue;
+        }
+        let past = if forward { candidate.as_os_str() > name } else { candidate.as_os_str() < name };
+        let better = |best: &Option<OsString>| {
+            best.as_ref().is_none_or(|b| if forward { candidate < *b } else { candidate > *b })
+        };
+        if past {
+            if better(&nearest) {
+                nearest = Some(candidate);
+            }
+        } else if better(&wrap) {
+            wrap = Some(candidate);
+        }
+    }
+    nearest.or(wrap).map(|n| dir.join(n))
+}
+
+/// Folder holding `path`, "." for a bare file name.
+pub fn folder_of(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// File names of the images directly inside `dir`. Uses the file type from the
+/// directory entry, so no per-file stat is needed on most filesystems.
+fn image_names(dir: &Path) -> impl Iterator<Item = OsString> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let is_file = match entry.file_type() {
+                Ok(t) if t.is_symlink() => entry.path().is_file(),
+                Ok(t) => t.is_file(),
+                Err(_) => false,
+            };
+            is_file && has_image_extension(Path::new(&entry.file_name()))
+        })
+        .map(|entry| entry.file_name())
+}
+
+fn has_image_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh folder under the system temp dir, removed again on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("sakura-dir-scan-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir.canonicalize().unwrap())
+        }
+
+        fn with_images(name: &str, names: &[&str]) -> Self {
+            let dir = Self::new(name);
+            for name in names {
+                fs::write(dir.0.join(name), b"").unwrap();
+            }
+            dir
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// `path` spelled relative to the working directory.
+    fn relative_to_cwd(path: &Path) -> PathBuf {
+        let cwd = std::env::current_dir().unwrap().canonicalize().unwrap();
+        let mut relative: PathBuf = cwd.components().skip(1).map(|_| "..").collect();
+        relative.push(path.strip_prefix("/").unwrap());
+        relative
+    }
+
+    #[test]
+    fn relative_path_finds_its_entry() {
+        let dir = TempDir::with_images("relative", &["a.png", "b.png", "c.png"]);
+        let list = list_images(&dir.0);
+        let relative = relative_to_cwd(&dir.0.join("b.png"));
+        assert!(relative.is_relative());
+        assert_eq!(list.binary_search(&list_key(&relative).unwrap()), Ok(1));
+
+        let dotted = dir.0.join(".").join("c.png");
+        assert_eq!(list.binary_search(&list_key(&dotted).unwrap()), Ok(2));
+    }
+
+    #[test]
+    fn bare_file_name_is_in_the_working_directory() {
+        assert_eq!(folder_of(Path::new("pic.png")), Path::new("."));
+        let cwd = std::env::current_dir().unwrap().canonicalize().unwrap();
+        assert_eq!(list_key(Path::new("pic.png")), Some(cwd.join("pic.png")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_folder_finds_the_real_entry() {
+        let dir = TempDir::with_images("linked-folder", &["a.png", "b.png"]);
+        let links = TempDir::new("linked-folder-links");
+        let link = links.0.join("photos");
+        std::os::unix::fs::symlink(&dir.0, &link).unwrap();
+
+        let list = list_images(&link);
+        assert_eq!(list, list_images(&dir.0));
+        assert_eq!(list.binary_search(&list_key(&link.join("b.png")).unwrap()), Ok(1));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_file_keeps_its_own_name() {
+        let dir = TempDir::with_images("linked-file", &["a.png", "c.png"]);
+        std::os::unix::fs::symlink(dir.0.join("c.png"), dir.0.join("b.png")).unwrap();
+        std::os::unix::fs::symlink(dir.0.join("a.png"), dir.0.join("alias")).unwrap();
+
+        let list = list_images(&dir.0);
+        assert_eq!(list, [dir.0.join("a.png"), dir.0.join("b.png"), dir.0.join("c.png")]);
+        assert_eq!(list.binary_search(&list_key(&dir.0.join("b.png")).unwrap()), Ok(1));
+        // Skipped for its name, so it isn't found and gets slotted in by name
+        assert_eq!(list.binary_search(&list_key(&dir.0.join("alias")).unwrap()), Err(1));
+    }
+
+    #[test]
+    fn neighbor_steps_by_name_and_wraps() {
+        let dir = TempDir::with_images("neighbor", &["a.png", "b.jpg", "c.png", "notes.txt"]);
+        assert_eq!(neighbor(&dir.0.join("a.png"), true), Some(dir.0.join("b.jpg")));
+        assert_eq!(neighbor(&dir.0.join("c.png"), true), Some(dir.0.join("a.png")));
+        assert_eq!(neighbor(&dir.0.join("a.png"), false), Some(dir.0.join("c.png")));
+    }
+}
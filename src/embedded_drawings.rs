@@ -0,0 +1,103 @@
+//! Drawings stored inside the PNGs they were saved into.
+//!
+//! Saving burns the drawings into the pixels, so other viewers show the
+//! annotated image. With `embed_drawings` on, the drawings also go into an iTXt
+//! chunk (the UTF-8 form of zTXt, since text drawings can be in any script),
+//! together with the pixels they cover as they were before burning in. Opening
+//! such a file puts those pixels back and the drawings on top as objects again,
+//! so they can be moved, edited and removed instead of staying baked in.
+//!
+//! Files without the chunk, or with one that doesn't fit the image (say, after
+//! another editor cropped it), open as plain images.
+
+use base64::Engine;
+use image::DynamicImage;
+use png::text_metadata::{EncodableTextChunk, ITXtChunk};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+use std::path::Path;
+
+/// Keyword of the chunk holding the drawings.
+const KEYWORD: &str = "svoy:drawings";
+/// Version of the chunk contents, bumped when they change incompatibly.
+const VERSION: u32 = 1;
+/// Largest chunk text read back. The covered pixels can make it big, but not
+/// without bound.
+const MAX_TEXT_LEN: usize = 64 * 1024 * 1024;
+/// Bytes before the first chunk after IHDR: the signature and the IHDR chunk.
+const IHDR_END: usize = 8 + 4 + 4 + 13 + 4;
+
+#[derive(Serialize, Deserialize)]
+struct Payload<T> {
+    version: u32,
+    /// Size of the image the drawings were saved on.
+    width: u32,
+    height: u32,
+    /// Pixels under the drawings before they were burned in.
+    base: Option<BasePatch>,
+    drawings: T,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BasePatch {
+    x: u32,
+    y: u32,
+    /// Base64 PNG.
+    png: String,
+}
+
+/// Adds `drawings`, saved onto `base` over the `region` (x, y, width, height)
+/// it covers, to the PNG at `path`.
+pub fn embed<D: Serialize>(path: &Path, drawings: &[D], base: &DynamicImage, region: Option<[u32; 4]>) -> Result<(), String> {
+    let base_patch = match region {
+        Some([x, y, w, h]) => {
+            let patch = base.crop_imm(x, y, w, h);
+            let mut bytes = Vec::new();
+            patch.write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png).map_err(|e| e.to_string())?;
+            Some(BasePatch { x, y, png: base64::engine::general_purpose::STANDARD.encode(bytes) })
+        }
+        None => None,
+    };
+    let payload = Payload { version: VERSION, width: base.width(), height: base.height(), base: base_patch, drawings };
+    let text = toml::to_string(&payload).map_err(|e| e.to_string())?;
+
+    let mut chunk = ITXtChunk::new(KEYWORD, text);
+    chunk.compressed = true;
+    let mut encoded = Vec::new();
+    chunk.encode(&mut encoded).map_err(|e| e.to_string())?;
+
+    // Right after IHDR, so reading it back doesn't need to decode the image
+    let mut bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    if bytes.len() < IHDR_END || &bytes[12..16] != b"IHDR" {
+        return Err("not a PNG file".to_string());
+    }
+    bytes.splice(IHDR_END..IHDR_END, encoded);
+    std::fs::write(path, bytes).map_err(|e| e.to_string())
+}
+
+/// Reads the drawings embedded in the PNG at `path`, decoded as `img`, and puts
+/// back the pixels they covered. `None` when the file has no usable drawings.
+pub fn extract<D: DeserializeOwned>(path: &Path, img: &DynamicImage) -> Option<(DynamicImage, Vec<D>)> {
+    let file = File::open(path).ok()?;
+    let reader = png::Decoder::new(BufReader::new(file)).read_info().ok()?;
+    let mut chunk = reader.info().utf8_text.iter().find(|chunk| chunk.keyword == KEYWORD)?.clone();
+    chunk.decompress_text_with_limit(MAX_TEXT_LEN).ok()?;
+    let payload: Payload<Vec<D>> = toml::from_str(&chunk.get_text().ok()?).ok()?;
+    if payload.version != VERSION || (payload.width, payload.height) != (img.width(), img.height()) {
+        return None;
+    }
+
+    let mut rgba = img.to_rgba8();
+    if let Some(base) = payload.base {
+        let bytes = base64::engine::general_purpose::STANDARD.decode(base.png).ok()?;
+        let patch = image::load_from_memory_with_format(&bytes, image::ImageFormat::Png).ok()?.to_rgba8();
+        if base.x as u64 + patch.width() as u64 > rgba.width() as u64
+            || base.y as u64 + patch.height() as u64 > rgba.height() as u64
+        {
+            return None;
+        }
+        image::imageops::replace(&mut rgba, &patch, base.x as i64, base.y as i64);
+    }
+    Some((DynamicImage::ImageRgba8(rgba), payload.drawings))
+}
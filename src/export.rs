@@ -0,0 +1,520 @@
+//! Writing images to disk with the user's encoder settings.
+//!
+//! PNG and other formats go through `image`'s default encoders. JPEG uses the
+//! `jpeg-encoder` crate instead, since `image`'s encoder doesn't expose chroma
+//! subsampling and 4:2:0 visibly smears colored text in screenshots. PNGs with at
+//! most 256 distinct colors can optionally be written as indexed (palette) images,
+//! which keeps icons and sprites small instead of expanding them to RGBA.
+//! Images can be copied as base64 `data:` URLs too, for embedding in web pages.
+//! With the `pdf` feature, an image can also be written as a one-page PDF with
+//! a caption, for reports.
+//!
+//! Before a save, `check_free_space` compares a rough estimate of the output
+//! size with the space left on the volume, so a full disk is caught before the
+//! file is truncated rather than halfway through writing it. `write_block` does
+//! the same for files and folders that can't be written at all, telling a
+//! read-only file apart from read-only media and missing permissions.
+
+use crate::animation::LoopCount;
+use crate::config::Config;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::Duration;
+
+/// Most colors a PNG palette can hold.
+const MAX_PALETTE_COLORS: usize = 256;
+
+/// Headroom on top of the estimated output size, for headers, metadata and the
+/// filesystem's own bookkeeping.
+const FREE_SPACE_MARGIN: u64 = 1024 * 1024;
+
+/// Chroma subsampling used for JPEG output.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum ChromaSubsampling {
+    /// Full color resolution, best for screenshots and text.
+    #[serde(rename = "4:4:4")]
+    Yuv444,
+    /// Half horizontal color resolution.
+    #[serde(rename = "4:2:2")]
+    Yuv422,
+    /// Quarter color resolution, smallest files, fine for photos.
+    #[serde(rename = "4:2:0")]
+    Yuv420,
+}
+
+impl ChromaSubsampling {
+    pub const ALL: [ChromaSubsampling; 3] = [Self::Yuv444, Self::Yuv422, Self::Yuv420];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Yuv444 => "4:4:4",
+            Self::Yuv422 => "4:2:2",
+            Self::Yuv420 => "4:2:0",
+        }
+    }
+
+    fn sampling_factor(self) -> jpeg_encoder::SamplingFactor {
+        match self {
+            Self::Yuv444 => jpeg_encoder::SamplingFactor::R_4_4_4,
+            Self::Yuv422 => jpeg_encoder::SamplingFactor::R_4_2_2,
+            Self::Yuv420 => jpeg_encoder::SamplingFactor::R_4_2_0,
+        }
+    }
+}
+
+/// Returns true if `path` has a JPEG extension.
+pub fn is_jpeg_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|ext| matches!(ext.to_lowercase().as_str(), "jpg" | "jpeg"))
+}
+
+/// Returns true if `path` has a PNG extension.
+pub fn is_png_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+}
+
+/// Returns true if `path` has a GIF extension.
+pub fn is_gif_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"))
+}
+
+/// Rough size of `frames` images of `width` x `height` written to `path` with
+/// the encoder settings in `config`. Aims at a typical photo rather than the worst
+/// case, so only saves that clearly won't fit are stopped.
+pub fn estimated_size(width: u32, height: u32, frames: usize, path: &Path, config: &Config) -> u64 {
+    let pixels = width as f64 * height as f64 * frames.max(1) as f64;
+    let bytes_per_pixel = if is_jpeg_path(path) {
+        // About 0.35 at quality 50 and 1 at quality 100
+        let quality = config.jpeg_quality.clamp(1, 100) as f64 / 100.0;
+        0.15 + 0.85 * quality * quality
+    } else if is_gif_path(path) || (is_png_path(path) && config.png_indexed) {
+        1.0
+    } else {
+        // Lossless RGBA; compression usually wins some of it back
+        4.0
+    };
+    (pixels * bytes_per_pixel) as u64
+}
+
+/// Errors with the estimated and available sizes when writing about `needed`
+/// bytes to `path` won't fit. Overwriting a file frees its space first. Passes
+/// when the free space can't be read.
+pub fn check_free_space(path: &Path, needed: u64) -> Result<(), (u64, u64)> {
+    let Some(free) = free_space(folder_of(path)) else { return Ok(()) };
+    let replaced = std::fs::metadata(path).map_or(0, |meta| meta.len());
+    let available = free + replaced;
+    if needed + FREE_SPACE_MARGIN > available {
+        Err((needed, available))
+    } else {
+        Ok(())
+    }
+}
+
+/// Bytes an unprivileged process can still write on the volume holding `dir`.
+#[cfg(unix)]
+fn free_space(dir: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stats` is a valid out-pointer
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+        return None;
+    }
+    Some(stats.f_bavail as u64 * stats.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn free_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+/// Why a file can't be written.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WriteBlock {
+    /// The file is marked read-only by its owner, the current user.
+    ReadOnlyFile,
+    /// The file or folder is on read-only media.
+    ReadOnlyVolume,
+    /// The file or folder belongs to someone else.
+    NoPermission,
+}
+
+impl WriteBlock {
+    /// Says what keeps `path` from being written, e.g. "photo.png is marked read-only".
+    pub fn describe(self, path: &Path) -> String {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        match self {
+            Self::ReadOnlyFile => format!("{} is marked read-only", name),
+            Self::ReadOnlyVolume => format!("{} is on a read-only drive", name),
+            Self::NoPermission if path.exists() => format!("you don't have permission to change {}", name),
+            Self::NoPermission => {
+                let folder = folder_of(path);
+                let folder = folder.file_name().map_or_else(|| folder.to_string_lossy(), |name| name.to_string_lossy());
+                format!("you don't have permission to add files to {}", folder)
+            }
+        }
+    }
+}
+
+/// What keeps `path` from being written, if anything: the file itself when it
+/// exists, otherwise the folder it would be created in. Other problems, like a
+/// missing folder, show up when writing.
+pub fn write_block(path: &Path) -> Option<WriteBlock> {
+    let exists = path.exists();
+    let err = write_access_error(if exists { path } else { folder_of(path) })?;
+    match err.kind() {
+        std::io::ErrorKind::ReadOnlyFilesystem => Some(WriteBlock::ReadOnlyVolume),
+        std::io::ErrorKind::PermissionDenied if exists && owns_read_only(path) => Some(WriteBlock::ReadOnlyFile),
+        std::io::ErrorKind::PermissionDenied => Some(WriteBlock::NoPermission),
+        _ => None,
+    }
+}
+
+/// Lets the owner write to `path` again.
+pub fn make_writable(path: &Path) -> Result<(), String> {
+    let mut permissions = std::fs::metadata(path).map_err(|e| e.to_string())?.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(permissions.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    permissions.set_readonly(false);
+    std::fs::set_permissions(path, permissions).map_err(|e| e.to_string())
+}
+
+/// The folder `path` is in, `.` for bare file names.
+fn folder_of(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Why the current user can't write to `path`, without opening it.
+#[cfg(unix)]
+fn write_access_error(path: &Path) -> Option<std::io::Error> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: `path` is NUL-terminated
+    if unsafe { libc::access(path.as_ptr(), libc::W_OK) } == 0 {
+        return None;
+    }
+    Some(std::io::Error::last_os_error())
+}
+
+/// Only the read-only attribute of files can be checked here.
+#[cfg(not(unix))]
+fn write_access_error(path: &Path) -> Option<std::io::Error> {
+    let meta = std::fs::metadata(path).ok()?;
+    (meta.is_file() && meta.permissions().readonly()).then(|| std::io::ErrorKind::PermissionDenied.into())
+}
+
+/// Whether `path` is the current user's and has its write permission off, so
+/// they can turn it back on.
+#[cfg(unix)]
+fn owns_read_only(path: &Path) -> bool {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    let Ok(meta) = std::fs::metadata(path) else { return false };
+    // SAFETY: geteuid has no preconditions
+    meta.uid() == unsafe { libc::geteuid() } && meta.permissions().mode() & 0o200 == 0
+}
+
+#[cfg(not(unix))]
+fn owns_read_only(path: &Path) -> bool {
+    std::fs::metadata(path).is_ok_and(|meta| meta.permissions().readonly())
+}
+
+/// Encodes `frames` as an animated GIF that plays `loop_count` times.
+pub fn save_gif_animation(
+    frames: impl IntoIterator<Item = (image::RgbaImage, Duration)>,
+    loop_count: LoopCount,
+    path: &Path,
+) -> Result<(), String> {
+    use image::codecs::gif::{GifEncoder, Repeat};
+
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = GifEncoder::new(BufWriter::new(file));
+    // GIF counts repeats after the first play
+    let repeat = match loop_count {
+        LoopCount::Infinite => Repeat::Infinite,
+        LoopCount::Finite(plays) => Repeat::Finite(plays.saturating_sub(1).min(u16::MAX as u32) as u16),
+    };
+    encoder.set_repeat(repeat).map_err(|e| e.to_string())?;
+    let frames = frames.into_iter().map(|(buffer, delay)| {
+        image::Frame::from_parts(buffer, 0, 0, image::Delay::from_saturating_duration(delay))
+    });
+    encoder.encode_frames(frames).map_err(|e| e.to_string())
+}
+
+/// Saves `img` to `path`, picking the encoder from the extension.
+pub fn save_image(img: &image::DynamicImage, path: &Path, config: &Config) -> Result<(), String> {
+    if is_jpeg_path(path) {
+        let img = if img.color().has_alpha() { flatten_alpha(img, config.flatten_background) } else { img.clone() };
+        save_jpeg(&img, path, config.jpeg_quality, config.jpeg_subsampling)
+    } else if is_png_path(path) && config.png_indexed {
+        let rgba = img.to_rgba8();
+        match palettize(&rgba) {
+            Some((palette, indices)) => save_png_indexed(&palette, &indices, rgba.width(), rgba.height(), path),
+            // Too many colors for a palette, keep full RGBA
+            None => img.save(path).map_err(|e| e.to_string()),
+        }
+    } else {
+        img.save(path).map_err(|e| e.to_string())
+    }
+}
+
+/// `img` composited over an opaque `background`, for formats without alpha.
+pub fn flatten_alpha(img: &image::DynamicImage, background: [u8; 3]) -> image::DynamicImage {
+    let mut rgba = img.to_rgba8();
+    for pixel in rgba.pixels_mut() {
+        let alpha = pixel.0[3] as u16;
+        for (c, bg) in pixel.0[..3].iter_mut().zip(background) {
+            *c = ((*c as u16 * alpha + bg as u16 * (255 - alpha)) / 255) as u8;
+        }
+        pixel.0[3] = 255;
+    }
+    image::DynamicImage::ImageRgb8(image::DynamicImage::ImageRgba8(rgba).to_rgb8())
+}
+
+/// What writing `rgba`, decoded from an image of `source` color type, to `path`
+/// would lose: one sentence per loss naming what goes and how much of it.
+/// Empty when the target format keeps everything.
+pub fn conversion_losses(source: image::ColorType, rgba: &image::RgbaImage, path: &Path) -> Vec<String> {
+    let mut losses = Vec::new();
+    let total = rgba.width() as u64 * rgba.height() as u64;
+    let share = |n: u64| n as f64 / total.max(1) as f64 * 100.0;
+
+    let bits = source.bits_per_pixel() / source.channel_count() as u16;
+    if bits > 8 {
+        losses.push(format!("{}-bit color depth is reduced to 8 bits per channel.", bits));
+    }
+    if is_jpeg_path(path) {
+        let translucent = rgba.pixels().filter(|p| p.0[3] < 255).count() as u64;
+        if translucent > 0 {
+            losses.push(format!(
+                "Transparency: {} pixels ({:.1}%) are flattened onto the background color.",
+                translucent,
+                share(translucent)
+            ));
+        }
+    }
+    if is_gif_path(path) {
+        let mut colors = HashSet::new();
+        for pixel in rgba.pixels() {
+            colors.insert(pixel.0);
+            if colors.len() > MAX_PALETTE_COLORS {
+                break;
+            }
+        }
+        if colors.len() > MAX_PALETTE_COLORS {
+            losses.push(format!("Colors: more than {} distinct colors are reduced to a {}-color palette.", MAX_PALETTE_COLORS, MAX_PALETTE_COLORS));
+        }
+        let partial = rgba.pixels().filter(|p| p.0[3] > 0 && p.0[3] < 255).count() as u64;
+        if partial > 0 {
+            losses.push(format!(
+                "Partial transparency: {} semi-transparent pixels ({:.1}%) become fully opaque or transparent.",
+                partial,
+                share(partial)
+            ));
+        }
+    }
+    losses
+}
+
+/// Splits `rgba` into a palette and per-pixel indices, or `None` if it has more
+/// than `MAX_PALETTE_COLORS` distinct colors.
+fn palettize(rgba: &image::RgbaImage) -> Option<(Vec<[u8; 4]>, Vec<u8>)> {
+    let mut palette = Vec::new();
+    let mut lookup: HashMap<[u8; 4], u8> = HashMap::new();
+    let mut indices = Vec::with_capacity(rgba.width() as usize * rgba.height() as usize);
+    for pixel in rgba.pixels() {
+        let index = match lookup.get(&pixel.0) {
+            Some(&index) => index,
+            None => {
+                if palette.len() == MAX_PALETTE_COLORS {
+                    return None;
+                }
+                let index = palette.len() as u8;
+                palette.push(pixel.0);
+                lookup.insert(pixel.0, index);
+                index
+            }
+        };
+        indices.push(index);
+    }
+    Some((palette, indices))
+}
+
+/// Writes an indexed PNG at the smallest bit depth that fits the palette, with a
+/// tRNS chunk when any color is not fully opaque.
+fn save_png_indexed(palette: &[[u8; 4]], indices: &[u8], width: u32, height: u32, path: &Path) -> Result<(), String> {
+    let (depth, bits) = match palette.len() {
+        0..=2 => (png::BitDepth::One, 1),
+        3..=4 => (png::BitDepth::Two, 2),
+        5..=16 => (png::BitDepth::Four, 4),
+        _ => (png::BitDepth::Eight, 8),
+    };
+
+    // Pack indices MSB-first; each row starts on a byte boundary
+    let per_byte = 8 / bits;
+    let row_bytes = (width as usize).div_ceil(per_byte);
+    let mut data = vec![0u8; row_bytes * height as usize];
+    for (row, row_indices) in indices.chunks(width as usize).enumerate() {
+        for (x, &index) in row_indices.iter().enumerate() {
+            let shift = 8 - bits * (x % per_byte + 1);
+            data[row * row_bytes + x / per_byte] |= index << shift;
+        }
+    }
+
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(depth);
+    encoder.set_palette(palette.iter().flat_map(|c| [c[0], c[1], c[2]]).collect::<Vec<u8>>());
+    if palette.iter().any(|c| c[3] != 255) {
+        // Trailing opaque entries may be omitted from tRNS
+        let len = palette.iter().rposition(|c| c[3] != 255).map_or(0, |i| i + 1);
+        encoder.set_trns(palette[..len].iter().map(|c| c[3]).collect::<Vec<u8>>());
+    }
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&data).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())
+}
+
+/// Encodes `img` as JPEG with an explicit quality and chroma subsampling.
+pub fn save_jpeg(img: &image::DynamicImage, path: &Path, quality: u8, subsampling: ChromaSubsampling) -> Result<(), String> {
+    let bytes = encode_jpeg(img, quality, subsampling)?;
+    std::fs::write(path, bytes).map_err(|e| e.to_string())
+}
+
+fn encode_jpeg(img: &image::DynamicImage, quality: u8, subsampling: ChromaSubsampling) -> Result<Vec<u8>, String> {
+    let (width, height) = (img.width(), img.height());
+    if width > u16::MAX as u32 || height > u16::MAX as u32 {
+        return Err(format!("JPEG cannot store images larger than {} pixels per side", u16::MAX));
+    }
+
+    let rgb = img.to_rgb8();
+    let mut bytes = Vec::new();
+    let mut encoder = jpeg_encoder::Encoder::new(&mut bytes, quality.clamp(1, 100));
+    encoder.set_sampling_factor(subsampling.sampling_factor());
+    encoder
+        .encode(rgb.as_raw(), width as u16, height as u16, jpeg_encoder::ColorType::Rgb)
+        .map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+/// `img` as a `data:` URL for pasting into HTML or Markdown. `format` is PNG or
+/// JPEG; JPEG uses `jpeg_quality` and the subsampling and background in `config`.
+pub fn data_url(img: &image::DynamicImage, format: image::ImageFormat, jpeg_quality: u8, config: &Config) -> Result<String, String> {
+    use base64::Engine;
+
+    let bytes = match format {
+        image::ImageFormat::Jpeg => {
+            let img = if img.color().has_alpha() { flatten_alpha(img, config.flatten_background) } else { img.clone() };
+            encode_jpeg(&img, jpeg_quality, config.jpeg_subsampling)?
+        }
+        image::ImageFormat::Png => {
+            let mut bytes = Vec::new();
+            img.write_to(&mut std::io::Cursor::new(&mut bytes), format).map_err(|e| e.to_string())?;
+            bytes
+        }
+        _ => return Err(format!("{:?} data URLs aren't supported", format)),
+    };
+    let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+    Ok(format!("data:{};base64,{}", format.to_mime_type(), encoded))
+}
+
+/// Longest edge of the image on a PDF page, in millimeters (about an A4 long side).
+#[cfg(feature = "pdf")]
+const PDF_MAX_EDGE_MM: f32 = 270.0;
+/// Resolution the image is placed at when that fits within `PDF_MAX_EDGE_MM`.
+#[cfg(feature = "pdf")]
+const PDF_DPI: f32 = 150.0;
+
+/// Writes `rgba` as a one-page PDF with `caption` lines under it. The page is
+/// sized to the image, so its aspect ratio is kept. Transparency is flattened
+/// onto white.
+#[cfg(feature = "pdf")]
+pub fn save_pdf(rgba: &image::RgbaImage, title: &str, caption: &[String], path: &Path) -> Result<(), String> {
+    use printpdf::{BuiltinFont, ColorBits, ColorSpace, Image, ImageTransform, ImageXObject, Mm, PdfDocument, Px};
+
+    const MARGIN: f32 = 10.0;
+    const FONT_SIZE: f32 = 9.0;
+    const LINE_HEIGHT: f32 = 4.5;
+
+    let (w, h) = rgba.dimensions();
+    let mm_per_px = (25.4 / PDF_DPI).min(PDF_MAX_EDGE_MM / w.max(h) as f32);
+    let (image_w, image_h) = (w as f32 * mm_per_px, h as f32 * mm_per_px);
+    let caption_h = if caption.is_empty() { 0.0 } else { caption.len() as f32 * LINE_HEIGHT + MARGIN / 2.0 };
+
+    let page_w = image_w + 2.0 * MARGIN;
+    let page_h = image_h + caption_h + 2.0 * MARGIN;
+    let (doc, page, layer) = PdfDocument::new(title, Mm(page_w), Mm(page_h), "Image");
+    let layer = doc.get_page(page).get_layer(layer);
+
+    let rgb: Vec<u8> = rgba.pixels()
+        .flat_map(|p| {
+            let alpha = p.0[3] as u16;
+            let over_white = |c: u8| ((c as u16 * alpha + 255 * (255 - alpha)) / 255) as u8;
+            [over_white(p.0[0]), over_white(p.0[1]), over_white(p.0[2])]
+        })
+        .collect();
+    let xobject = ImageXObject {
+        width: Px(w as usize),
+        height: Px(h as usize),
+        color_space: ColorSpace::Rgb,
+        bits_per_component: ColorBits::Bit8,
+        interpolate: true,
+        image_data: rgb,
+        image_filter: None,
+        smask: None,
+        clipping_bbox: None,
+    };
+    // PDF coordinates start at the bottom-left, so the image sits above the caption
+    Image::from(xobject).add_to_layer(layer.clone(), ImageTransform {
+        translate_x: Some(Mm(MARGIN)),
+        translate_y: Some(Mm(MARGIN + caption_h)),
+        dpi: Some(25.4 / mm_per_px),
+        ..Default::default()
+    });
+
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(|e| e.to_string())?;
+    for (i, line) in caption.iter().enumerate() {
+        let y = MARGIN + caption_h - MARGIN / 2.0 - (i as f32 + 1.0) * LINE_HEIGHT + 1.0;
+        layer.use_text(line.as_str(), FONT_SIZE, Mm(MARGIN), Mm(y), &font);
+    }
+
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    doc.save(&mut BufWriter::new(file)).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_chroma_keeps_fine_color_detail() {
+        // One-pixel red and blue checker: all of its detail is in the color
+        let checker = image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(32, 32, |x, y| {
+            if (x + y) % 2 == 0 { image::Rgb([255, 0, 0]) } else { image::Rgb([0, 0, 255]) }
+        }));
+        let error = |subsampling| {
+            let bytes = encode_jpeg(&checker, 90, subsampling).unwrap();
+            let decoded = image::load_from_memory(&bytes).unwrap().to_rgb8();
+            decoded.pixels().zip(checker.to_rgb8().pixels())
+                .map(|(a, b)| a.0.iter().zip(b.0).map(|(&a, b)| a.abs_diff(b) as u64).sum::<u64>())
+                .sum::<u64>()
+        };
+        let (full, quarter) = (error(ChromaSubsampling::Yuv444), error(ChromaSubsampling::Yuv420));
+        assert!(full * 4 < quarter, "4:4:4 error {} vs 4:2:0 error {}", full, quarter);
+    }
+}
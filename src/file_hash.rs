@@ -0,0 +1,77 @@
+//! SHA-256 of the file on disk, for deduplicating and verifying images.
+//!
+//! Large files take a while to read, so the hash is computed on a background
+//! thread. The thread gives up early once nobody is waiting for the result.
+
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::SystemTime;
+
+/// Result of hashing a file, tagged with the version of the file it describes.
+#[derive(Clone)]
+pub struct FileDigest {
+    /// Lowercase hex digest, or why the file couldn't be read.
+    pub hex: Result<String, String>,
+    /// Modification time of the file when hashing started.
+    pub modified: Option<SystemTime>,
+}
+
+/// A hash being computed in the background.
+pub struct FileHash {
+    rx: mpsc::Receiver<FileDigest>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl FileHash {
+    /// Starts hashing `path`.
+    pub fn start(path: &Path) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let worker_path = path.to_path_buf();
+        let worker_cancelled = cancelled.clone();
+        std::thread::spawn(move || {
+            let modified = modified_time(&worker_path);
+            let hex = sha256_file(&worker_path, &worker_cancelled);
+            let _ = tx.send(FileDigest { hex, modified });
+        });
+        Self { rx, cancelled }
+    }
+
+    /// The digest, once it is ready.
+    pub fn poll(&self) -> Option<FileDigest> {
+        self.rx.try_recv().ok()
+    }
+}
+
+impl Drop for FileHash {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Modification time of `path`, if the filesystem reports one.
+pub fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn sha256_file(path: &Path, cancelled: &AtomicBool) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 1 << 20];
+    loop {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err("Cancelled".to_string());
+        }
+        match file.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => hasher.update(&buf[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
@@ -0,0 +1,109 @@
+//! Filmstrip along the bottom of the single-image view: a row of thumbnails of
+//! the folder with the current image highlighted. Clicking one jumps to it.
+//!
+//! Only the cells in view are drawn and requested from the shared
+//! `ThumbnailCache`, and thumbnails far from the current image are evicted
+//! again. The strip scrolls to keep the current image in the middle.
+
+use crate::thumbnails::{self, ThumbnailCache};
+use eframe::egui;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Edge length of a cell.
+const CELL_SIZE: f32 = 72.0;
+const CELL_PADDING: f32 = 4.0;
+const MARGIN: f32 = 6.0;
+/// Height the strip takes at the bottom of the window.
+pub const HEIGHT: f32 = CELL_SIZE + 2.0 * MARGIN;
+/// Images either side of the current one whose thumbnails stay cached.
+const KEEP: usize = 40;
+
+#[derive(Default)]
+pub struct Filmstrip {
+    /// Image the strip last scrolled to, so it scrolls again when that changes.
+    centered_on: Option<usize>,
+}
+
+impl Filmstrip {
+    /// Draws the strip for `paths`, with `current` shown. Returns the index of
+    /// a clicked thumbnail. Missing thumbnails are only requested with `load`,
+    /// so scrubbing past cells doesn't queue decodes for all of them.
+    pub fn show(&mut self, ctx: &egui::Context, paths: &[PathBuf], current: usize, thumbnails: &mut ThumbnailCache, load: bool) -> Option<usize> {
+        if paths.is_empty() {
+            return None;
+        }
+        if thumbnails.poll(ctx) || thumbnails.is_loading() {
+            ctx.request_repaint();
+        }
+        let current = current.min(paths.len() - 1);
+        let width = ctx.screen_rect().width();
+        let mut clicked = None;
+        let mut visible = 0..0;
+
+        egui::Area::new(egui::Id::new("filmstrip"))
+            .anchor(egui::Align2::LEFT_BOTTOM, egui::Vec2::ZERO)
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                egui::Frame::none()
+                    .fill(egui::Color32::from_black_alpha(160))
+                    .inner_margin(MARGIN)
+                    .show(ui, |ui| {
+                        ui.set_width(width - 2.0 * MARGIN);
+                        let mut area = egui::ScrollArea::horizontal()
+                            .auto_shrink([false, true])
+                            .scroll_bar_visibility(egui::scroll_area::ScrollBarVisibility::AlwaysHidden);
+                        if self.centered_on != Some(current) {
+                            self.centered_on = Some(current);
+                            let offset = (current as f32 + 0.5) * CELL_SIZE - ui.available_width() / 2.0;
+                            area = area.horizontal_scroll_offset(offset.max(0.0));
+                        }
+                        area.show_viewport(ui, |ui, viewport| {
+                            let (strip, _) = ui.allocate_exact_size(egui::vec2(paths.len() as f32 * CELL_SIZE, CELL_SIZE), egui::Sense::hover());
+                            let first = (viewport.min.x / CELL_SIZE).floor().max(0.0) as usize;
+                            let last = ((viewport.max.x / CELL_SIZE).ceil() as usize).min(paths.len());
+                            visible = first..last;
+                            for idx in visible.clone() {
+                                let cell = egui::Rect::from_min_size(strip.min + egui::vec2(idx as f32 * CELL_SIZE, 0.0), egui::Vec2::splat(CELL_SIZE));
+                                let response = ui.interact(cell, ui.id().with(("filmstrip_cell", idx)), egui::Sense::click());
+                                paint_cell(ui, cell, &paths[idx], thumbnails, load, idx == current, response.hovered());
+                                let name = paths[idx].file_name().unwrap_or_default().to_string_lossy();
+                                if response.on_hover_text(name).clicked() {
+                                    clicked = Some(idx);
+                                }
+                            }
+                        });
+                    });
+            });
+
+        // Bound memory by dropping thumbnails far from the current image and the view
+        let near = current.saturating_sub(KEEP)..(current + KEEP + 1).min(paths.len());
+        let keep: HashSet<&Path> = paths[near].iter().chain(&paths[visible]).map(|p| p.as_path()).collect();
+        thumbnails.retain(|p| keep.contains(p));
+
+        clicked
+    }
+}
+
+fn paint_cell(ui: &egui::Ui, rect: egui::Rect, path: &Path, thumbnails: &mut ThumbnailCache, load: bool, current: bool, hovered: bool) {
+    let painter = ui.painter();
+    let inner = rect.shrink(CELL_PADDING / 2.0);
+    if current {
+        painter.rect_filled(inner, 4.0, egui::Color32::from_white_alpha(40));
+        painter.rect_stroke(inner, 4.0, egui::Stroke::new(2.0, egui::Color32::WHITE));
+    } else if hovered {
+        painter.rect_filled(inner, 4.0, egui::Color32::from_white_alpha(20));
+    }
+
+    let thumbnail = if load { thumbnails.get(path) } else { thumbnails.cached(path) };
+    match thumbnail {
+        Some(tex) => {
+            let img_rect = thumbnails::fit_in_cell(inner, tex.size_vec2(), CELL_PADDING);
+            let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+            painter.image(tex.id(), img_rect, uv, egui::Color32::WHITE);
+        }
+        None => {
+            painter.circle_filled(inner.center(), 2.0, egui::Color32::GRAY);
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Fonts for text annotations, with fallbacks for emoji and CJK text.
+//!
+//! On screen, egui's default fonts already include a monochrome emoji font; a
+//! CJK font from the system is added behind them when one is installed.
+//! Burned-in text uses the same chain: the bundled font, egui's emoji font,
+//! then the system CJK font, taking each character from the first that has it.
+//! Monospace text puts egui's own monospace font in front, so it keeps the
+//! fixed-width look it has on screen.
+
+use ab_glyph::{Font, FontArc, FontRef, GlyphId, PxScale, ScaleFont};
+use eframe::egui;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// CJK font files to look for, most preferred first, in lowercase.
+const CJK_FONT_FILES: &[&str] = &[
+    // Linux distributions
+    "notosanscjk-regular.ttc",
+    "notosanscjksc-regular.otf",
+    "notosanscjk-vf.ttc",
+    "sourcehansans-regular.ttc",
+    "wqy-microhei.ttc",
+    "wqy-zenhei.ttc",
+    "droidsansfallbackfull.ttf",
+    // macOS
+    "hiragino sans gb.ttc",
+    "pingfang.ttc",
+    "applegothic.ttf",
+    // Windows
+    "msyh.ttc",
+    "yugothr.ttc",
+    "msgothic.ttc",
+    "malgun.ttf",
+];
+
+/// Folder levels searched below each font directory.
+const FONT_DIR_DEPTH: usize = 4;
+
+/// Adds the system CJK font as a fallback to egui's families. The search runs
+/// in the background, so text drawn before it finishes may show boxes once.
+pub fn install(ctx: &egui::Context) {
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        let Some(data) = system_cjk_font() else { return };
+        let font = egui::FontData::from_static(data);
+        let families = [egui::FontFamily::Proportional, egui::FontFamily::Monospace]
+            .into_iter()
+            .map(|family| egui::epaint::text::InsertFontFamily { family, priority: egui::epaint::text::FontPriority::Lowest })
+            .collect();
+        ctx.add_font(egui::epaint::text::FontInsert::new("cjk-fallback", font, families));
+        ctx.request_repaint();
+    });
+}
+
+/// Draws `text` on one line with its top-left at (`x`, `y`), blending it over
+/// what is there like `imageproc::drawing::draw_text_mut`.
+pub fn draw_text(canvas: &mut image::RgbaImage, color: image::Rgba<u8>, x: i32, y: i32, scale: PxScale, text: &str, monospace: bool) {
+    let fonts = annotation_fonts(monospace);
+    let Some(primary) = fonts.first() else { return };
+    let baseline = primary.as_scaled(scale).ascent();
+    let (width, height) = (canvas.width() as i32, canvas.height() as i32);
+
+    let mut caret = 0.0;
+    let mut last: Option<(usize, GlyphId)> = None;
+    for c in text.chars() {
+        let index = fonts.iter().position(|font| font.glyph_id(c).0 != 0).unwrap_or(0);
+        let font = fonts[index].as_scaled(scale);
+        let id = font.glyph_id(c);
+        // Kerning only applies between glyphs of the same font
+        if let Some((_, last_id)) = last.filter(|&(last_index, _)| last_index == index) {
+            caret += font.kern(last_id, id);
+        }
+        last = Some((index, id));
+        let glyph = id.with_scale_and_position(scale, ab_glyph::point(caret, baseline));
+        caret += font.h_advance(id);
+
+        let Some(outline) = font.outline_glyph(glyph) else { continue };
+        let bounds = outline.px_bounds();
+        outline.draw(|gx, gy, coverage| {
+            let px = x + bounds.min.x.round() as i32 + gx as i32;
+            let py = y + bounds.min.y.round() as i32 + gy as i32;
+            if (0..width).contains(&px) && (0..height).contains(&py) {
+                let coverage = coverage.clamp(0.0, 1.0);
+                let pixel = canvas.get_pixel_mut(px as u32, py as u32);
+                *pixel = imageproc::pixelops::weighted_sum(*pixel, color, 1.0 - coverage, coverage);
+            }
+        });
+    }
+}
+
+/// The fonts burned-in text is drawn with, in fallback order.
+fn annotation_fonts(monospace: bool) -> &'static [FontArc] {
+    static PROPORTIONAL: OnceLock<Vec<FontArc>> = OnceLock::new();
+    static MONOSPACE: OnceLock<Vec<FontArc>> = OnceLock::new();
+    if monospace {
+        return MONOSPACE.get_or_init(|| {
+            egui_font("Hack").into_iter().chain(annotation_fonts(false).iter().cloned()).collect()
+        });
+    }
+    PROPORTIONAL.get_or_init(|| {
+        let mut fonts = Vec::new();
+        if let Ok(font) = FontRef::try_from_slice(include_bytes!("../materials/font.ttf")) {
+            fonts.push(FontArc::new(font));
+        }
+        fonts.extend(egui_font("NotoEmoji-Regular"));
+        if let Some(data) = system_cjk_font() {
+            fonts.extend(FontRef::try_from_slice(data).ok().map(FontArc::new));
+        }
+        fonts
+    })
+}
+
+/// One of the fonts egui ships with, by its name in `FontDefinitions`.
+fn egui_font(name: &str) -> Option<FontArc> {
+    egui::FontDefinitions::default().font_data.get(name)
+        .and_then(|data| FontArc::try_from_vec(data.font.to_vec()).ok())
+}
+
+/// Contents of the preferred CJK font installed, read once. Collections use
+/// their first face.
+fn system_cjk_font() -> Option<&'static [u8]> {
+    static FONT: OnceLock<Option<Vec<u8>>> = OnceLock::new();
+    let data = FONT.get_or_init(|| {
+        let mut found = Vec::new();
+        for dir in font_dirs() {
+            collect_font_files(&dir, FONT_DIR_DEPTH, &mut found);
+        }
+        CJK_FONT_FILES.iter()
+            .filter_map(|&wanted| found.iter().find(|(name, _)| name == wanted))
+            .filter_map(|(_, path)| std::fs::read(path).ok())
+            .find(|data| FontRef::try_from_slice(data).is_ok())
+    });
+    data.as_deref()
+}
+
+fn font_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = ["/usr/share/fonts", "/usr/local/share/fonts", "/System/Library/Fonts", "/Library/Fonts"]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+    if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+        dirs.extend([home.join(".local/share/fonts"), home.join(".fonts"), home.join("Library/Fonts")]);
+    }
+    if let Some(windir) = std::env::var_os("WINDIR").map(PathBuf::from) {
+        dirs.push(windir.join("Fonts"));
+    }
+    dirs
+}
+
+/// Adds the font files under `dir` to `found` as (lowercase file name, path).
+fn collect_font_files(dir: &Path, depth: usize, found: &mut Vec<(String, PathBuf)>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+            if depth > 0 {
+                collect_font_files(&path, depth - 1, found);
+            }
+        } else if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+            found.push((name.to_lowercase(), path));
+        }
+    }
+}
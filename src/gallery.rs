@@ -0,0 +1,213 @@
+//! Contact-sheet view showing the whole folder as a grid of thumbnails.
+//!
+//! Rows are virtualized, so only visible thumbnails are requested from the shared
+//! `ThumbnailCache`, and thumbnails far from the viewport are evicted again.
+//!
+//! Besides the keyboard cursor, files can be marked with Ctrl+click, Shift+click
+//! or Space, and the marked files rotated or flipped together.
+
+use crate::orientation::Transform;
+use crate::thumbnails::{self, ThumbnailCache};
+use eframe::egui;
+use std::collections::{BTreeSet, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Target edge length of a grid cell; cells stretch to fill the width.
+const CELL_SIZE: f32 = 180.0;
+const CELL_PADDING: f32 = 8.0;
+/// Rows above/below the viewport whose thumbnails stay cached.
+const KEEP_ROWS: usize = 3;
+
+/// What the user chose in the grid.
+pub enum GalleryAction {
+    /// Open the image at this index.
+    Open(usize),
+    /// Rotate or flip the files at these indices.
+    Transform(Vec<usize>, Transform),
+}
+
+/// Selection and scroll state of the grid.
+pub struct Gallery {
+    pub selected: usize,
+    /// Indices of the files marked for a batch action.
+    pub marked: BTreeSet<usize>,
+    /// Where a Shift+click range starts: the last file clicked or toggled.
+    anchor: usize,
+    scroll_to_selected: bool,
+    visible_rows: Range<usize>,
+}
+
+impl Gallery {
+    pub fn new(selected: usize) -> Self {
+        Self {
+            selected,
+            marked: BTreeSet::new(),
+            anchor: selected,
+            scroll_to_selected: true,
+            visible_rows: 0..0,
+        }
+    }
+
+    /// Moves the selection to `idx` and scrolls to it.
+    pub fn select(&mut self, idx: usize) {
+        self.selected = idx;
+        self.anchor = idx;
+        self.scroll_to_selected = true;
+    }
+
+    /// Draws the grid. `busy` disables the batch actions while an earlier one runs.
+    pub fn show(&mut self, ui: &mut egui::Ui, paths: &[PathBuf], thumbnails: &mut ThumbnailCache, busy: bool) -> Option<GalleryAction> {
+        let ctx = ui.ctx().clone();
+        thumbnails.poll(&ctx);
+        if thumbnails.is_loading() {
+            ctx.request_repaint();
+        }
+
+        let n = paths.len();
+        if n == 0 {
+            return None;
+        }
+        self.selected = self.selected.min(n - 1);
+        self.marked.retain(|&idx| idx < n);
+
+        let mut action = None;
+        if !self.marked.is_empty() {
+            action = self.show_action_bar(ui, busy);
+        }
+
+        let width = ui.available_width();
+        let columns = ((width / CELL_SIZE).floor() as usize).max(1);
+        let rows = n.div_ceil(columns);
+        let cell = width / columns as f32;
+
+        // Keyboard navigation within the grid
+        let before = self.selected;
+        ui.input(|i| {
+            if i.key_pressed(egui::Key::ArrowRight) { self.selected = (self.selected + 1).min(n - 1); }
+            if i.key_pressed(egui::Key::ArrowLeft) { self.selected = self.selected.saturating_sub(1); }
+            if i.key_pressed(egui::Key::ArrowDown) && self.selected + columns < n { self.selected += columns; }
+            if i.key_pressed(egui::Key::ArrowUp) { self.selected = self.selected.checked_sub(columns).unwrap_or(self.selected); }
+            if i.key_pressed(egui::Key::Home) { self.selected = 0; }
+            if i.key_pressed(egui::Key::End) { self.selected = n - 1; }
+            if i.key_pressed(egui::Key::Enter) { action = Some(GalleryAction::Open(self.selected)); }
+            if i.key_pressed(egui::Key::Space) { self.toggle_mark(self.selected); }
+            if i.modifiers.command && i.key_pressed(egui::Key::A) { self.marked = (0..n).collect(); }
+        });
+        if self.selected != before {
+            self.scroll_to_selected = true;
+        }
+
+        let mut area = egui::ScrollArea::vertical().auto_shrink([false, false]);
+        if std::mem::take(&mut self.scroll_to_selected) {
+            // Scroll just enough to bring the selected row fully into view. The last
+            // visible row may be cut off, so it counts as out of view too.
+            let row = self.selected / columns;
+            let viewport_h = ui.available_height();
+            if self.visible_rows.is_empty() || row < self.visible_rows.start {
+                area = area.vertical_scroll_offset(row as f32 * cell);
+            } else if row + 1 >= self.visible_rows.end {
+                area = area.vertical_scroll_offset(((row + 1) as f32 * cell - viewport_h).max(0.0));
+            }
+        }
+
+        area.show_rows(ui, cell, rows, |ui, row_range| {
+            self.visible_rows = row_range.clone();
+            for row in row_range {
+                ui.horizontal(|ui| {
+                    for col in 0..columns {
+                        let idx = row * columns + col;
+                        if idx >= n {
+                            break;
+                        }
+                        let (rect, response) = ui.allocate_exact_size(egui::vec2(cell, cell), egui::Sense::click());
+                        self.paint_cell(ui, rect, idx, &paths[idx], thumbnails, response.hovered());
+                        let name = paths[idx].file_name().unwrap_or_default().to_string_lossy();
+                        let response = response.on_hover_text(name);
+                        if response.clicked() {
+                            let modifiers = ui.input(|i| i.modifiers);
+                            self.selected = idx;
+                            if modifiers.shift {
+                                let range = self.anchor.min(idx)..=self.anchor.max(idx);
+                                self.marked.extend(range);
+                            } else if modifiers.command {
+                                self.toggle_mark(idx);
+                            } else {
+                                action = Some(GalleryAction::Open(idx));
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        // Bound memory by dropping thumbnails well outside the viewport
+        let keep_start = self.visible_rows.start.saturating_sub(KEEP_ROWS) * columns;
+        let keep_end = ((self.visible_rows.end + KEEP_ROWS) * columns).min(n);
+        let keep: HashSet<&Path> = paths[keep_start..keep_end].iter().map(|p| p.as_path()).collect();
+        thumbnails.retain(|p| keep.contains(p));
+
+        action
+    }
+
+    fn toggle_mark(&mut self, idx: usize) {
+        if !self.marked.remove(&idx) {
+            self.marked.insert(idx);
+        }
+        self.anchor = idx;
+    }
+
+    /// The bar above the grid offering actions on the marked files.
+    fn show_action_bar(&mut self, ui: &mut egui::Ui, busy: bool) -> Option<GalleryAction> {
+        let mut action = None;
+        egui::Frame::none().inner_margin(egui::Margin::symmetric(8.0, 6.0)).show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.spacing_mut().item_spacing.x = 6.0;
+                ui.label(format!("{} selected", self.marked.len()));
+                ui.separator();
+                ui.add_enabled_ui(!busy, |ui| {
+                    for transform in Transform::ALL {
+                        if ui.button(transform.label()).clicked() {
+                            action = Some(GalleryAction::Transform(self.marked.iter().copied().collect(), transform));
+                        }
+                    }
+                });
+                ui.separator();
+                if ui.button("Clear").on_hover_text("Unmark all files").clicked() {
+                    self.marked.clear();
+                }
+            });
+        });
+        action
+    }
+
+    fn paint_cell(&self, ui: &egui::Ui, rect: egui::Rect, idx: usize, path: &Path, thumbnails: &mut ThumbnailCache, hovered: bool) {
+        let painter = ui.painter();
+        let inner = rect.shrink(CELL_PADDING / 2.0);
+
+        let marked = self.marked.contains(&idx);
+        if marked {
+            painter.rect_filled(inner, 6.0, ui.visuals().selection.bg_fill.gamma_multiply(0.6));
+        }
+        if idx == self.selected {
+            if !marked {
+                painter.rect_filled(inner, 6.0, egui::Color32::from_white_alpha(40));
+            }
+            painter.rect_stroke(inner, 6.0, egui::Stroke::new(2.0, egui::Color32::WHITE));
+        } else if hovered && !marked {
+            painter.rect_filled(inner, 6.0, egui::Color32::from_white_alpha(20));
+        }
+
+        match thumbnails.get(path) {
+            Some(tex) => {
+                let img_rect = thumbnails::fit_in_cell(inner, tex.size_vec2(), CELL_PADDING);
+                let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                painter.image(tex.id(), img_rect, uv, egui::Color32::WHITE);
+            }
+            None => {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                painter.text(inner.center(), egui::Align2::CENTER_CENTER, name, egui::FontId::proportional(11.0), egui::Color32::GRAY);
+            }
+        }
+    }
+}
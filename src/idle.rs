@@ -0,0 +1,90 @@
+//! What the viewer does after a while without input, for photo-frame use:
+//! dim the window or run a slideshow through the folder.
+//!
+//! Any input wakes it. The events of that frame are dropped, so a key pressed
+//! to wake the viewer doesn't also act on it.
+
+use eframe::egui;
+use std::time::{Duration, Instant};
+
+pub struct IdleWatch {
+    last_input: Instant,
+    /// When the slideshow last moved on.
+    last_step: Instant,
+    idle: bool,
+}
+
+impl IdleWatch {
+    /// Starts awake, or already idle, as frame mode does.
+    pub fn new(idle: bool) -> Self {
+        Self { last_input: Instant::now(), last_step: Instant::now(), idle }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    /// Wakes on this frame's input, or goes idle once there was none for
+    /// `after`; `None` never goes idle. Call before anything reads the input.
+    pub fn update(&mut self, ctx: &egui::Context, after: Option<Duration>) {
+        if ctx.input(|i| i.events.iter().any(is_user_input)) {
+            self.last_input = Instant::now();
+            if self.idle {
+                self.idle = false;
+                ctx.input_mut(|i| i.events.clear());
+            }
+            return;
+        }
+        let Some(after) = after else {
+            self.idle = false;
+            return;
+        };
+        if !self.idle {
+            let quiet = self.last_input.elapsed();
+            if quiet >= after {
+                self.idle = true;
+                self.last_step = Instant::now();
+            } else {
+                ctx.request_repaint_after(after - quiet);
+            }
+        }
+    }
+
+    /// Whether the slideshow should show the next image, once per `interval`
+    /// while idle.
+    pub fn slideshow_step(&mut self, ctx: &egui::Context, interval: Duration) -> bool {
+        if !self.idle {
+            return false;
+        }
+        let shown = self.last_step.elapsed();
+        if shown >= interval {
+            self.last_step = Instant::now();
+            ctx.request_repaint_after(interval);
+            true
+        } else {
+            ctx.request_repaint_after(interval - shown);
+            false
+        }
+    }
+
+    /// Darkens the whole window.
+    pub fn paint_dim(ctx: &egui::Context) {
+        let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Tooltip, egui::Id::new("idle_dim")));
+        painter.rect_filled(ctx.screen_rect(), 0.0, egui::Color32::from_black_alpha(220));
+    }
+}
+
+/// Whether `event` came from the user rather than the window system.
+fn is_user_input(event: &egui::Event) -> bool {
+    matches!(
+        event,
+        egui::Event::Key { .. }
+            | egui::Event::Text(_)
+            | egui::Event::Paste(_)
+            | egui::Event::PointerMoved(_)
+            | egui::Event::PointerButton { .. }
+            | egui::Event::MouseWheel { .. }
+            | egui::Event::Zoom(_)
+            | egui::Event::Touch { .. }
+    )
+}
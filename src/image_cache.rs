@@ -0,0 +1,130 @@
+//! Decoded images kept in memory, so stepping back and forth doesn't decode
+//! them again, and the neighbors of the current image decoded ahead of time on
+//! a background thread.
+//!
+//! Entries remember the modification time and size of their file and are
+//! dropped when either changed, so an image rewritten on disk, by the viewer or
+//! anything else, is decoded afresh. The cache holds a few images, within
+//! `MAX_BYTES`, and drops the least recently used first.
+
+use crate::orientation;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::SystemTime;
+
+/// Most images kept.
+const MAX_IMAGES: usize = 5;
+/// Most decoded bytes kept. A single larger image isn't cached at all.
+const MAX_BYTES: usize = 768 * 1024 * 1024;
+
+/// Modification time and size, to tell whether a file changed.
+type Stamp = (SystemTime, u64);
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+struct Entry {
+    path: PathBuf,
+    stamp: Stamp,
+    image: image::DynamicImage,
+}
+
+pub struct ImageCache {
+    /// Least recently used first.
+    entries: Vec<Entry>,
+    /// Paths queued on the worker but not back yet.
+    pending: HashSet<PathBuf>,
+    request_tx: mpsc::Sender<PathBuf>,
+    result_rx: mpsc::Receiver<(PathBuf, Option<Stamp>, Option<image::DynamicImage>)>,
+}
+
+impl ImageCache {
+    /// Creates the cache and spawns its decode worker.
+    pub fn new() -> Self {
+        let (request_tx, request_rx) = mpsc::channel::<PathBuf>();
+        let (result_tx, result_rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            while let Ok(path) = request_rx.recv() {
+                // Stamped before decoding, so a write during it counts as a change
+                let stamp = stamp(&path);
+                let image = orientation::open(&path).ok();
+                if result_tx.send((path, stamp, image)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self { entries: Vec::new(), pending: HashSet::new(), request_tx, result_rx }
+    }
+
+    /// A copy of the decoded image at `path`, if it is cached and the file is
+    /// unchanged. Waits for it if it is being decoded ahead right now.
+    pub fn get(&mut self, path: &Path) -> Option<image::DynamicImage> {
+        self.poll();
+        while self.pending.contains(path) {
+            let Ok(result) = self.result_rx.recv() else { break };
+            self.receive(result);
+        }
+        let idx = self.entries.iter().position(|entry| entry.path == path)?;
+        let entry = self.entries.remove(idx);
+        if stamp(path) != Some(entry.stamp) {
+            return None;
+        }
+        let image = entry.image.clone();
+        self.entries.push(entry);
+        Some(image)
+    }
+
+    /// Keeps a copy of `image`, just decoded from `path`.
+    pub fn insert(&mut self, path: &Path, image: &image::DynamicImage) {
+        if let Some(stamp) = stamp(path) {
+            self.store(Entry { path: path.to_path_buf(), stamp, image: image.clone() });
+        }
+    }
+
+    /// Decodes `paths` in the background, unless they are cached already.
+    pub fn prefetch<'a>(&mut self, paths: impl IntoIterator<Item = &'a Path>) {
+        self.poll();
+        for path in paths {
+            let cached = self.entries.iter().any(|entry| entry.path == path && stamp(path) == Some(entry.stamp));
+            if !cached && self.pending.insert(path.to_path_buf()) {
+                let _ = self.request_tx.send(path.to_path_buf());
+            }
+        }
+    }
+
+    /// Forgets the image at `path`, e.g. after the file was rewritten.
+    pub fn invalidate(&mut self, path: &Path) {
+        self.entries.retain(|entry| entry.path != path);
+    }
+
+    /// Takes in images the worker finished.
+    fn poll(&mut self) {
+        while let Ok(result) = self.result_rx.try_recv() {
+            self.receive(result);
+        }
+    }
+
+    fn receive(&mut self, (path, stamp, image): (PathBuf, Option<Stamp>, Option<image::DynamicImage>)) {
+        self.pending.remove(&path);
+        if let (Some(stamp), Some(image)) = (stamp, image) {
+            self.store(Entry { path, stamp, image });
+        }
+    }
+
+    fn store(&mut self, entry: Entry) {
+        self.invalidate(&entry.path);
+        if entry.image.as_bytes().len() > MAX_BYTES {
+            return;
+        }
+        self.entries.push(entry);
+        let bytes = |entries: &[Entry]| entries.iter().map(|entry| entry.image.as_bytes().len()).sum::<usize>();
+        while self.entries.len() > MAX_IMAGES || bytes(&self.entries) > MAX_BYTES {
+            self.entries.remove(0);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! sakura, a fast and simple image viewer.
+//!
+//! The `sakura` binary runs `ImageViewer` as a standalone window. Other eframe
+//! apps can embed it with `ViewerOptions { embedded: true, .. }` and call
+//! `ImageViewer::show` from their own `update`.
+
+mod adjust;
+mod alpha;
+mod animation;
+mod batch;
+mod blur;
+mod compare;
+mod config;
+mod details;
+mod dialog;
+mod dir_scan;
+mod embedded_drawings;
+mod export;
+mod file_hash;
+mod filmstrip;
+mod fonts;
+mod gallery;
+mod idle;
+mod image_cache;
+mod locale;
+mod navigation;
+mod orientation;
+mod perf;
+mod playlist;
+mod resize;
+mod session;
+mod share_logic;
+mod strip;
+mod thumbnails;
+mod type_ahead;
+mod viewer;
+
+pub use config::WindowMode;
+pub use playlist::{is_playlist, read_playlist, Playlist};
+pub use viewer::{clamp_to_screen, DrawingTool, ImageViewer, ViewerOptions, MIN_WINDOW_HEIGHT, MIN_WINDOW_WIDTH};
+
+/// Extensions of the files the viewer browses and opens.
+pub const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "bmp", "ico"];
@@ -0,0 +1,159 @@
+//! Number and date formatting that follows the user's locale.
+//!
+//! The locale is read from `LC_ALL`, then `LC_NUMERIC` / `LC_TIME`, then `LANG`,
+//! the same way POSIX programs pick it, unless the config forces one. Only the
+//! conventions the viewer displays are localized (decimal and grouping separators,
+//! date order, 12/24-hour clock), looked up from a small table by language and
+//! region. Anything unknown falls back to US English.
+
+use std::time::SystemTime;
+
+/// Locales offered in the settings, besides following the system.
+pub const LOCALE_CHOICES: &[&str] = &["en_US", "en_GB", "de_DE", "fr_FR", "es_ES", "ja_JP", "sv_SE", "C"];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum DateOrder {
+    DayMonthYear,
+    MonthDayYear,
+    YearMonthDay,
+}
+
+/// Formatting conventions of one locale.
+#[derive(Clone, Debug)]
+pub struct Locale {
+    /// Name the conventions were looked up for, e.g. "de_DE".
+    pub name: String,
+    decimal: char,
+    /// Thousands separator, `None` for no grouping.
+    grouping: Option<char>,
+    date_order: DateOrder,
+    date_separator: char,
+    twelve_hour: bool,
+    /// Put a space between a number and "%", as in French and German.
+    space_before_percent: bool,
+}
+
+impl Locale {
+    /// Conventions for `forced` if it is not empty, otherwise for the system locale.
+    pub fn resolve(forced: &str) -> Self {
+        if !forced.trim().is_empty() {
+            return Self::from_name(forced);
+        }
+        let from_env = |var: &str| std::env::var(var).ok().filter(|v| !v.is_empty());
+        let name = from_env("LC_ALL")
+            .or_else(|| from_env("LC_NUMERIC"))
+            .or_else(|| from_env("LC_TIME"))
+            .or_else(|| from_env("LANG"))
+            .unwrap_or_else(|| "en_US".to_string());
+        Self::from_name(&name)
+    }
+
+    /// Conventions for a POSIX locale name such as "de_DE.UTF-8" or "fr_CA@euro".
+    pub fn from_name(name: &str) -> Self {
+        // Strip the encoding and modifier, keeping language and region
+        let base = name.split(['.', '@']).next().unwrap_or_default().trim();
+        let (language, region) = match base.split_once(['_', '-']) {
+            Some((language, region)) => (language.to_lowercase(), region.to_uppercase()),
+            None => (base.to_lowercase(), String::new()),
+        };
+
+        use DateOrder::*;
+        let mut locale = Self {
+            name: base.to_string(),
+            decimal: '.',
+            grouping: Some(','),
+            date_order: MonthDayYear,
+            date_separator: '/',
+            twelve_hour: true,
+            space_before_percent: false,
+        };
+        let mut set = |decimal, grouping, date_order, date_separator, twelve_hour, space_before_percent| {
+            locale.decimal = decimal;
+            locale.grouping = grouping;
+            locale.date_order = date_order;
+            locale.date_separator = date_separator;
+            locale.twelve_hour = twelve_hour;
+            locale.space_before_percent = space_before_percent;
+        };
+        match (language.as_str(), region.as_str()) {
+            ("c" | "posix", _) => set('.', None, YearMonthDay, '-', false, false),
+            ("en", "US" | "PH" | "") => {}
+            ("en", "CA") => set('.', Some(','), YearMonthDay, '-', true, false),
+            ("en", _) => set('.', Some(','), DayMonthYear, '/', false, false),
+            ("de", "CH") => set('.', Some('\''), DayMonthYear, '.', false, false),
+            ("de" | "da" | "nb" | "nn" | "no" | "fi" | "cs" | "sk" | "pl" | "ru" | "uk" | "tr" | "ro", _) => {
+                set(',', Some('.'), DayMonthYear, '.', false, true)
+            }
+            ("fr", "CA") => set(',', Some('\u{a0}'), YearMonthDay, '-', false, true),
+            ("fr", _) => set(',', Some('\u{202f}'), DayMonthYear, '/', false, true),
+            ("es" | "it" | "pt" | "nl" | "el" | "id", _) => set(',', Some('.'), DayMonthYear, '/', false, false),
+            ("sv", _) => set(',', Some('\u{a0}'), YearMonthDay, '-', false, true),
+            ("hu" | "lt", _) => set(',', Some('\u{a0}'), YearMonthDay, '.', false, false),
+            ("ja" | "zh", _) => set('.', Some(','), YearMonthDay, '/', false, false),
+            ("ko", _) => set('.', Some(','), YearMonthDay, '.', true, false),
+            _ => {}
+        }
+        locale
+    }
+
+    /// `value` with `decimals` fraction digits and the locale's separators.
+    pub fn number(&self, value: f64, decimals: usize) -> String {
+        let text = format!("{:.*}", decimals, value.abs());
+        let (int, frac) = text.split_once('.').unwrap_or((text.as_str(), ""));
+
+        let mut out = String::new();
+        if value < 0.0 && text.chars().any(|c| c.is_ascii_digit() && c != '0') {
+            out.push('-');
+        }
+        for (i, digit) in int.chars().enumerate() {
+            if i > 0 && (int.len() - i) % 3 == 0 {
+                if let Some(sep) = self.grouping {
+                    out.push(sep);
+                }
+            }
+            out.push(digit);
+        }
+        if !frac.is_empty() {
+            out.push(self.decimal);
+            out.push_str(frac);
+        }
+        out
+    }
+
+    /// `bytes` as KB below a megabyte, MB above.
+    pub fn file_size(&self, bytes: u64) -> String {
+        if bytes < 1024 * 1024 {
+            format!("{} KB", self.number(bytes as f64 / 1024.0, 1))
+        } else {
+            format!("{} MB", self.number(bytes as f64 / (1024.0 * 1024.0), 2))
+        }
+    }
+
+    /// A whole-number percentage, e.g. "1,250%" or "1.250 %".
+    pub fn percent(&self, percent: f32) -> String {
+        let space = if self.space_before_percent { "\u{a0}" } else { "" };
+        format!("{}{}%", self.number(percent.round() as f64, 0), space)
+    }
+
+    /// Rewrites a number typed in this locale with "." as the decimal point and
+    /// no grouping, ready for `str::parse`.
+    pub fn normalize_number(&self, text: &str) -> String {
+        text.chars()
+            .filter(|&c| Some(c) != self.grouping && c != '\u{a0}' && c != '\u{202f}')
+            .map(|c| if c == self.decimal { '.' } else { c })
+            .collect()
+    }
+
+    /// Local date and time of `time`, down to the minute.
+    pub fn date_time(&self, time: SystemTime) -> String {
+        let local: chrono::DateTime<chrono::Local> = time.into();
+        let sep = self.date_separator;
+        let date = match self.date_order {
+            DateOrder::DayMonthYear => format!("%d{sep}%m{sep}%Y"),
+            DateOrder::MonthDayYear => format!("%m{sep}%d{sep}%Y"),
+            DateOrder::YearMonthDay => format!("%Y{sep}%m{sep}%d"),
+        };
+        let clock = if self.twelve_hour { "%I:%M %p" } else { "%H:%M" };
+        local.format(&format!("{} {}", date, clock)).to_string()
+    }
+}
@@ -0,0 +1,194 @@
+//! Index arithmetic for stepping through a list of images: wrapping at the
+//! ends, finding the current image again after the list changed, and slotting
+//! files in and out. Plain functions of the list and an index, apart from the
+//! UI, so empty lists, single images and a current image that goes away are
+//! handled in one place.
+//!
+//! A list is either sorted, as folder listings are, or `ordered` as a list file
+//! has it. Files an ordered list doesn't have go right after the current image.
+
+use std::path::{Path, PathBuf};
+
+/// Index of the image after (`forward`) or before `current` in a list of `len`.
+/// Past either end it wraps around with `wrap`, and stays at the end otherwise.
+/// None for an empty list.
+pub fn step(len: usize, current: usize, forward: bool, wrap: bool) -> Option<usize> {
+    let last = len.checked_sub(1)?;
+    let current = current.min(last);
+    Some(match (forward, wrap) {
+        (true, true) => if current == last { 0 } else { current + 1 },
+        (true, false) => (current + 1).min(last),
+        (false, true) => if current == 0 { last } else { current - 1 },
+        (false, false) => current.saturating_sub(1),
+    })
+}
+
+/// The images either side of `current`, next first, wrapping around. Each
+/// comes once and `current` never does, so short lists give fewer.
+pub fn neighbors(len: usize, current: usize) -> Vec<usize> {
+    let mut found = Vec::with_capacity(2);
+    for forward in [true, false] {
+        if let Some(idx) = step(len, current, forward, true).filter(|&idx| idx != current && !found.contains(&idx)) {
+            found.push(idx);
+        }
+    }
+    found
+}
+
+/// Index of `key` in `list`.
+pub fn find(list: &[PathBuf], key: &Path, ordered: bool) -> Option<usize> {
+    if ordered {
+        list.iter().position(|p| p == key)
+    } else {
+        list.binary_search_by(|p| p.as_path().cmp(key)).ok()
+    }
+}
+
+/// Where `key` would be inserted into `list`, or None if it is there already.
+fn slot(list: &[PathBuf], key: &Path, current: usize, ordered: bool) -> Option<usize> {
+    if ordered {
+        (!list.iter().any(|p| p == key)).then(|| (current + 1).min(list.len()))
+    } else {
+        list.binary_search_by(|p| p.as_path().cmp(key)).err()
+    }
+}
+
+/// Index of `key` in `list`, inserting it first if it is missing, e.g. the
+/// current image after the list was listed or sorted again.
+pub fn select(list: &mut Vec<PathBuf>, key: PathBuf, current: usize, ordered: bool) -> usize {
+    match slot(list, &key, current, ordered) {
+        Some(idx) => {
+            list.insert(idx, key);
+            idx
+        }
+        None => find(list, &key, ordered).unwrap_or(0),
+    }
+}
+
+/// Adds `key` to `list` unless it is there, e.g. a file written next to the
+/// current image. Returns `current`, moved along if the insert shifted it.
+pub fn insert(list: &mut Vec<PathBuf>, key: PathBuf, current: usize, ordered: bool) -> usize {
+    match slot(list, &key, current, ordered) {
+        Some(idx) => {
+            list.insert(idx, key);
+            if idx <= current && current + 1 < list.len() { current + 1 } else { current }
+        }
+        None => current,
+    }
+}
+
+/// Takes image `idx` out of `list` and returns the index of the image shown in
+/// place of `current`: the one that moved up into its place, or the new last
+/// one when the last was removed. None once the list is empty.
+pub fn remove(list: &mut Vec<PathBuf>, idx: usize, current: usize) -> Option<usize> {
+    if idx < list.len() {
+        list.remove(idx);
+    }
+    let last = list.len().checked_sub(1)?;
+    let current = if idx < current { current - 1 } else { current };
+    Some(current.min(last))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn step_wraps_only_when_asked() {
+        assert_eq!(step(3, 2, true, true), Some(0));
+        assert_eq!(step(3, 0, false, true), Some(2));
+        assert_eq!(step(3, 2, true, false), Some(2));
+        assert_eq!(step(3, 0, false, false), Some(0));
+        assert_eq!(step(3, 1, true, false), Some(2));
+        assert_eq!(step(3, 1, false, true), Some(0));
+    }
+
+    #[test]
+    fn step_clamps_a_stale_index() {
+        assert_eq!(step(3, 7, true, false), Some(2));
+        assert_eq!(step(3, 7, false, false), Some(1));
+    }
+
+    #[test]
+    fn empty_list_has_nowhere_to_go() {
+        assert_eq!(step(0, 0, true, true), None);
+        assert_eq!(step(0, 0, false, false), None);
+        assert!(neighbors(0, 0).is_empty());
+        assert_eq!(remove(&mut Vec::new(), 0, 0), None);
+    }
+
+    #[test]
+    fn single_image_stays_put() {
+        for (forward, wrap) in [(true, true), (true, false), (false, true), (false, false)] {
+            assert_eq!(step(1, 0, forward, wrap), Some(0));
+        }
+        assert!(neighbors(1, 0).is_empty());
+        assert_eq!(neighbors(2, 0), vec![1]);
+        assert_eq!(neighbors(4, 0), vec![1, 3]);
+    }
+
+    #[test]
+    fn select_keeps_the_image_after_a_sort() {
+        let mut list = paths(&["c.png", "a.png", "b.png"]);
+        let current = list[0].clone();
+        list.sort();
+        assert_eq!(select(&mut list, current, 0, false), 2);
+        assert_eq!(list, paths(&["a.png", "b.png", "c.png"]));
+    }
+
+    #[test]
+    fn select_inserts_a_missing_image() {
+        let mut list = paths(&["a.png", "c.png"]);
+        assert_eq!(select(&mut list, PathBuf::from("b.png"), 0, false), 1);
+        assert_eq!(list, paths(&["a.png", "b.png", "c.png"]));
+
+        let mut list = paths(&["z.png", "a.png"]);
+        assert_eq!(select(&mut list, PathBuf::from("a.png"), 0, true), 1);
+        assert_eq!(select(&mut list, PathBuf::from("m.png"), 0, true), 1);
+        assert_eq!(list, paths(&["z.png", "m.png", "a.png"]));
+    }
+
+    #[test]
+    fn insert_into_sorted_list_moves_current_along() {
+        let mut list = paths(&["b.png", "d.png"]);
+        assert_eq!(insert(&mut list, PathBuf::from("a.png"), 1, false), 2);
+        assert_eq!(insert(&mut list, PathBuf::from("e.png"), 2, false), 2);
+        assert_eq!(insert(&mut list, PathBuf::from("d.png"), 2, false), 2);
+        assert_eq!(list, paths(&["a.png", "b.png", "d.png", "e.png"]));
+    }
+
+    #[test]
+    fn insert_into_ordered_list_goes_after_current() {
+        let mut list = paths(&["z.png", "a.png"]);
+        assert_eq!(insert(&mut list, PathBuf::from("m.png"), 0, true), 0);
+        assert_eq!(list, paths(&["z.png", "m.png", "a.png"]));
+        assert_eq!(insert(&mut list, PathBuf::from("b.png"), 2, true), 2);
+        assert_eq!(list, paths(&["z.png", "m.png", "a.png", "b.png"]));
+    }
+
+    #[test]
+    fn remove_current_shows_the_next() {
+        let mut list = paths(&["a.png", "b.png", "c.png"]);
+        assert_eq!(remove(&mut list, 1, 1), Some(1));
+        assert_eq!(list, paths(&["a.png", "c.png"]));
+    }
+
+    #[test]
+    fn remove_last_shows_the_new_last() {
+        let mut list = paths(&["a.png", "b.png", "c.png"]);
+        assert_eq!(remove(&mut list, 2, 2), Some(1));
+        let mut list = paths(&["a.png"]);
+        assert_eq!(remove(&mut list, 0, 0), None);
+    }
+
+    #[test]
+    fn remove_before_current_shifts_it() {
+        let mut list = paths(&["a.png", "b.png", "c.png"]);
+        assert_eq!(remove(&mut list, 0, 2), Some(1));
+        assert_eq!(list[1], PathBuf::from("c.png"));
+    }
+}
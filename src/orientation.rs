@@ -0,0 +1,285 @@
+//! EXIF orientation: applying it when decoding, and rewriting it in JPEG files.
+//!
+//! Cameras store sideways photos unrotated and record how to turn them in the
+//! EXIF Orientation tag. Decoding through `open` applies that tag, so images,
+//! thumbnails and the strip all show photos upright. Rewriting the tag is also
+//! how batch rotations and flips stay lossless for JPEG: the compressed data is
+//! left alone and only the two bytes of the tag change.
+
+use image::metadata::Orientation;
+use image::ImageDecoder;
+use std::path::Path;
+
+/// Marker of the EXIF APP1 segment, and the signature its data starts with.
+const APP1: u8 = 0xE1;
+const EXIF_SIGNATURE: &[u8] = b"Exif\0\0";
+/// EXIF tag number of Orientation.
+const ORIENTATION_TAG: u16 = 0x0112;
+
+/// A rotation or flip applied to whole images.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Transform {
+    RotateLeft,
+    RotateRight,
+    FlipHorizontal,
+    FlipVertical,
+}
+
+impl Transform {
+    pub const ALL: [Transform; 4] = [Self::RotateLeft, Self::RotateRight, Self::FlipHorizontal, Self::FlipVertical];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::RotateLeft => "Rotate left",
+            Self::RotateRight => "Rotate right",
+            Self::FlipHorizontal => "Flip horizontally",
+            Self::FlipVertical => "Flip vertically",
+        }
+    }
+
+    /// The transform that undoes this one.
+    pub fn inverse(self) -> Self {
+        match self {
+            Self::RotateLeft => Self::RotateRight,
+            Self::RotateRight => Self::RotateLeft,
+            flip => flip,
+        }
+    }
+
+    pub fn apply(self, img: &image::DynamicImage) -> image::DynamicImage {
+        match self {
+            Self::RotateLeft => img.rotate270(),
+            Self::RotateRight => img.rotate90(),
+            Self::FlipHorizontal => img.fliph(),
+            Self::FlipVertical => img.flipv(),
+        }
+    }
+
+    /// The orientation that shows a file stored with `current` as `current`
+    /// followed by this transform.
+    pub fn after(self, current: Orientation) -> Orientation {
+        // Every orientation is some clockwise quarter turns followed by an optional
+        // horizontal mirror, matching `DynamicImage::apply_orientation`
+        let (turns, mirrored) = match current {
+            Orientation::NoTransforms => (0, false),
+            Orientation::Rotate90 => (1, false),
+            Orientation::Rotate180 => (2, false),
+            Orientation::Rotate270 => (3, false),
+            Orientation::FlipHorizontal => (0, true),
+            Orientation::Rotate90FlipH => (1, true),
+            Orientation::FlipVertical => (2, true),
+            Orientation::Rotate270FlipH => (3, true),
+        };
+        // Turning a mirrored image clockwise equals turning it counterclockwise
+        // before the mirror. A vertical flip is a half turn plus a horizontal one.
+        let (turns, mirrored) = match self {
+            Self::RotateRight => (if mirrored { turns + 3 } else { turns + 1 }, mirrored),
+            Self::RotateLeft => (if mirrored { turns + 1 } else { turns + 3 }, mirrored),
+            Self::FlipHorizontal => (turns, !mirrored),
+            Self::FlipVertical => (turns + 2, !mirrored),
+        };
+        match (turns % 4, mirrored) {
+            (0, false) => Orientation::NoTransforms,
+            (1, false) => Orientation::Rotate90,
+            (2, false) => Orientation::Rotate180,
+            (3, false) => Orientation::Rotate270,
+            (0, true) => Orientation::FlipHorizontal,
+            (1, true) => Orientation::Rotate90FlipH,
+            (2, true) => Orientation::FlipVertical,
+            _ => Orientation::Rotate270FlipH,
+        }
+    }
+}
+
+/// Decodes `path` like `image::open`, turned upright by its EXIF orientation.
+pub fn open(path: &Path) -> image::ImageResult<image::DynamicImage> {
+    let mut decoder = image::ImageReader::open(path)?.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut img = image::DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+/// Upright size of the image at `path`, read from its header.
+pub fn dimensions(path: &Path) -> image::ImageResult<(u32, u32)> {
+    let mut decoder = image::ImageReader::open(path)?.into_decoder()?;
+    let (w, h) = decoder.dimensions();
+    Ok(if swaps_axes(decoder.orientation()?) { (h, w) } else { (w, h) })
+}
+
+fn swaps_axes(orientation: Orientation) -> bool {
+    matches!(
+        orientation,
+        Orientation::Rotate90 | Orientation::Rotate270 | Orientation::Rotate90FlipH | Orientation::Rotate270FlipH
+    )
+}
+
+/// Applies `transforms`, in order, to the JPEG at `path` by rewriting its EXIF
+/// orientation, without touching the compressed image. Returns `Ok(false)` if
+/// the EXIF data is too damaged or too large to take an Orientation entry.
+pub fn transform_jpeg_losslessly(path: &Path, transforms: &[Transform]) -> Result<bool, String> {
+    let mut data = std::fs::read(path).map_err(|e| e.to_string())?;
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return Err("Not a JPEG file".to_string());
+    }
+
+    let tiff = match find_exif(&data) {
+        Some(tiff) => tiff,
+        None => {
+            // EXIF goes first, but after a JFIF APP0 segment, which must lead
+            let mut at = 2;
+            if data.get(2..4) == Some(&[0xFF, 0xE0]) {
+                at += 2 + segment_length(&data, 2).ok_or("Truncated JPEG")?;
+            }
+            data.splice(at..at, exif_segment());
+            at + 4 + EXIF_SIGNATURE.len()..at + EXIF_SEGMENT_LEN
+        }
+    };
+    let tiff = match orientation_offset(&data[tiff.clone()]) {
+        Some(_) => tiff,
+        None => match add_orientation_entry(&mut data, tiff) {
+            Some(tiff) => tiff,
+            None => return Ok(false),
+        },
+    };
+    let Some(offset) = orientation_offset(&data[tiff.clone()]) else { return Ok(false) };
+
+    let at = tiff.start + offset;
+    let big_endian = data[tiff.start] == b'M';
+    let read = |b: [u8; 2]| if big_endian { u16::from_be_bytes(b) } else { u16::from_le_bytes(b) };
+    let current = Orientation::from_exif(read([data[at], data[at + 1]]) as u8).unwrap_or(Orientation::NoTransforms);
+    let value = transforms.iter().fold(current, |orientation, transform| transform.after(orientation)).to_exif() as u16;
+    let bytes = if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+    data[at..at + 2].copy_from_slice(&bytes);
+
+    // Write beside the original and swap, so a failure never leaves half a file
+    let tmp = path.with_extension("jpg.tmp");
+    std::fs::write(&tmp, &data).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp, path).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        e.to_string()
+    })?;
+    Ok(true)
+}
+
+/// Length field of the segment whose marker starts at `marker`, excluding the marker.
+fn segment_length(data: &[u8], marker: usize) -> Option<usize> {
+    Some(u16::from_be_bytes([*data.get(marker + 2)?, *data.get(marker + 3)?]) as usize)
+}
+
+/// Byte range of the TIFF structure inside the EXIF APP1 segment, if any.
+fn find_exif(data: &[u8]) -> Option<std::ops::Range<usize>> {
+    let mut pos = 2;
+    // Metadata segments come before the first frame header
+    while data.get(pos) == Some(&0xFF) {
+        let marker = *data.get(pos + 1)?;
+        if !(0xE0..=0xEF).contains(&marker) && marker != 0xFE && marker != 0xDB && marker != 0xC4 {
+            return None;
+        }
+        let len = segment_length(data, pos)?;
+        let body = pos + 4..pos + 2 + len;
+        if marker == APP1 && data.get(body.clone())?.starts_with(EXIF_SIGNATURE) {
+            return Some(body.start + EXIF_SIGNATURE.len()..body.end);
+        }
+        pos = body.end;
+    }
+    None
+}
+
+/// Offset of the Orientation value within a TIFF structure, if IFD0 has one.
+fn orientation_offset(tiff: &[u8]) -> Option<usize> {
+    let big_endian = match tiff.get(..4)? {
+        b"MM\0*" => true,
+        b"II*\0" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| {
+        let b = [*tiff.get(at)?, *tiff.get(at + 1)?];
+        Some(if big_endian { u16::from_be_bytes(b) } else { u16::from_le_bytes(b) })
+    };
+    let b = tiff.get(4..8)?;
+    let ifd = if big_endian { u32::from_be_bytes(b.try_into().ok()?) } else { u32::from_le_bytes(b.try_into().ok()?) } as usize;
+    let entries = u16_at(ifd)? as usize;
+    (0..entries)
+        .map(|i| ifd + 2 + i * 12)
+        .find(|&entry| u16_at(entry) == Some(ORIENTATION_TAG))
+        // Tag, type and count take 8 bytes; a single SHORT value sits inline after them
+        .map(|entry| entry + 8)
+        .filter(|&at| at + 2 <= tiff.len())
+}
+
+/// Copies IFD0 of the TIFF structure at `tiff` to its end with an Orientation
+/// entry added, and points the header at the copy. The old IFD0 stays behind
+/// unused, so every other offset in the structure remains valid. Returns the
+/// new range of the TIFF structure.
+fn add_orientation_entry(data: &mut Vec<u8>, tiff: std::ops::Range<usize>) -> Option<std::ops::Range<usize>> {
+    let old = &data[tiff.clone()];
+    let big_endian = old.first()? == &b'M';
+    let u16_bytes = |v: u16| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+    let u32_bytes = |v: u32| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+    let u16_at = |at: usize| -> Option<u16> {
+        let b = [*old.get(at)?, *old.get(at + 1)?];
+        Some(if big_endian { u16::from_be_bytes(b) } else { u16::from_le_bytes(b) })
+    };
+    let b: [u8; 4] = old.get(4..8)?.try_into().ok()?;
+    let ifd = if big_endian { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) } as usize;
+    let count = u16_at(ifd)? as usize;
+    let entries = old.get(ifd + 2..ifd + 2 + count * 12)?;
+    let next_ifd = old.get(ifd + 2 + count * 12..ifd + 6 + count * 12)?;
+
+    let mut entry = Vec::with_capacity(12);
+    entry.extend_from_slice(&u16_bytes(ORIENTATION_TAG));
+    entry.extend_from_slice(&u16_bytes(3)); // SHORT
+    entry.extend_from_slice(&u32_bytes(1));
+    entry.extend_from_slice(&u16_bytes(Orientation::NoTransforms.to_exif() as u16));
+    entry.extend_from_slice(&[0, 0]);
+    // Entries are sorted by tag
+    let insert_at = (0..count).find(|&i| u16_at(ifd + 2 + i * 12).is_some_and(|tag| tag > ORIENTATION_TAG)).unwrap_or(count);
+
+    let mut new = old.to_vec();
+    // IFDs start on a word boundary
+    if new.len() % 2 == 1 {
+        new.push(0);
+    }
+    let new_ifd = new.len();
+    new.extend_from_slice(&u16_bytes(count as u16 + 1));
+    new.extend_from_slice(&entries[..insert_at * 12]);
+    new.extend_from_slice(&entry);
+    new.extend_from_slice(&entries[insert_at * 12..]);
+    new.extend_from_slice(next_ifd);
+    new[4..8].copy_from_slice(&u32_bytes(new_ifd as u32));
+
+    let segment_len = 2 + EXIF_SIGNATURE.len() + new.len();
+    if segment_len > u16::MAX as usize {
+        return None;
+    }
+    let length_at = tiff.start - EXIF_SIGNATURE.len() - 2;
+    data[length_at..length_at + 2].copy_from_slice(&(segment_len as u16).to_be_bytes());
+    let range = tiff.start..tiff.start + new.len();
+    data.splice(tiff, new);
+    Some(range)
+}
+
+/// Size of the segment written by `exif_segment`, markers included.
+const EXIF_SEGMENT_LEN: usize = 4 + 6 + 8 + 2 + 12 + 4;
+
+/// A minimal APP1 segment holding only an Orientation tag with no transform.
+fn exif_segment() -> Vec<u8> {
+    let mut tiff = Vec::new();
+    tiff.extend_from_slice(b"MM\0*");
+    tiff.extend_from_slice(&8u32.to_be_bytes()); // IFD0 right after the header
+    tiff.extend_from_slice(&1u16.to_be_bytes());
+    tiff.extend_from_slice(&ORIENTATION_TAG.to_be_bytes());
+    tiff.extend_from_slice(&3u16.to_be_bytes()); // SHORT
+    tiff.extend_from_slice(&1u32.to_be_bytes());
+    tiff.extend_from_slice(&(Orientation::NoTransforms.to_exif() as u16).to_be_bytes());
+    tiff.extend_from_slice(&[0, 0]);
+    tiff.extend_from_slice(&0u32.to_be_bytes()); // No further IFDs
+
+    let mut segment = vec![0xFF, APP1];
+    segment.extend_from_slice(&((2 + EXIF_SIGNATURE.len() + tiff.len()) as u16).to_be_bytes());
+    segment.extend_from_slice(EXIF_SIGNATURE);
+    segment.extend_from_slice(&tiff);
+    debug_assert_eq!(segment.len(), EXIF_SEGMENT_LEN);
+    segment
+}
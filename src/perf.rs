@@ -0,0 +1,74 @@
+//! Optional debug overlay with load timings for diagnosing slow files.
+//!
+//! Timings are always recorded since an `Instant` pair is practically free; the
+//! overlay itself is only laid out while toggled on with F12.
+
+use eframe::egui;
+use std::time::Duration;
+
+/// Weight of the newest frame in the smoothed FPS reading.
+const FPS_SMOOTHING: f32 = 0.1;
+
+/// Steps in the undo history and the configured limits.
+pub struct UndoDepth {
+    pub images: usize,
+    pub image_limit: usize,
+    pub drawings: usize,
+    pub drawing_limit: usize,
+}
+
+/// How long the stages of the last image load took.
+#[derive(Default)]
+pub struct PerfStats {
+    pub visible: bool,
+    /// `orientation::open` of the current file.
+    pub decode: Option<Duration>,
+    /// RGBA conversion and GPU upload of the main texture.
+    pub upload: Option<Duration>,
+    /// Downscale, blur and upload of the overlay backdrop.
+    pub blur: Option<Duration>,
+    fps: f32,
+}
+
+impl PerfStats {
+    /// Feeds the duration of the last frame into the FPS average.
+    pub fn record_frame(&mut self, dt: f32) {
+        if dt > 0.0 {
+            let fps = 1.0 / dt;
+            self.fps = if self.fps == 0.0 { fps } else { self.fps + (fps - self.fps) * FPS_SMOOTHING };
+        }
+    }
+
+    /// Draws the overlay in the bottom-left corner. `memory` is the estimated bytes
+    /// held by the current image, its frames, undo snapshots and textures.
+    pub fn show(&self, ctx: &egui::Context, memory: usize, undo: UndoDepth) {
+        let fmt = |d: Option<Duration>| d.map_or("-".to_string(), |d| format!("{:.1} ms", d.as_secs_f64() * 1000.0));
+        let text = format!(
+            "decode   {}\nupload   {}\nblur     {}\nfps      {:.0}\nmemory   {:.1} MB\nundo     {}/{} px, {}/{} draw",
+            fmt(self.decode),
+            fmt(self.upload),
+            fmt(self.blur),
+            self.fps,
+            memory as f64 / (1024.0 * 1024.0),
+            undo.images,
+            undo.image_limit,
+            undo.drawings,
+            undo.drawing_limit,
+        );
+        egui::Area::new(egui::Id::new("perf_overlay"))
+            .anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(10.0, -10.0))
+            .order(egui::Order::Foreground)
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::none()
+                    .fill(egui::Color32::from_black_alpha(180))
+                    .rounding(6.0)
+                    .inner_margin(8.0)
+                    .show(ui, |ui| {
+                        ui.label(egui::RichText::new(text).monospace().color(egui::Color32::WHITE));
+                    });
+            });
+        // Keep the FPS reading live while the overlay is shown
+        ctx.request_repaint();
+    }
+}
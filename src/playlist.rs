@@ -0,0 +1,40 @@
+//! Lists of images to step through in a set order, across folders.
+//!
+//! A list is a text file with one path per line, as written by hand or by
+//! `find`, and M3U playlists are read the same way: blank lines and lines
+//! starting with `#` are skipped. Relative paths are taken from the list's own
+//! folder, so a list can move together with its images.
+
+use std::path::{Path, PathBuf};
+
+/// The images of a list file.
+pub struct Playlist {
+    /// Listed files that exist, in list order.
+    pub images: Vec<PathBuf>,
+    /// Listed files that don't exist, skipped.
+    pub missing: Vec<PathBuf>,
+}
+
+/// Reads the list file at `path`.
+pub fn read_playlist(path: &Path) -> Result<Playlist, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    let mut playlist = Playlist { images: Vec::new(), missing: Vec::new() };
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let image = base.join(line);
+        if image.is_file() {
+            playlist.images.push(image);
+        } else {
+            playlist.missing.push(image);
+        }
+    }
+    Ok(playlist)
+}
+
+/// Whether `path` is a playlist that can be opened in place of an image.
+pub fn is_playlist(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| ext.eq_ignore_ascii_case("m3u") || ext.eq_ignore_ascii_case("m3u8"))
+}
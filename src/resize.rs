@@ -0,0 +1,220 @@
+//! The "Resize" window: new dimensions, the resampling filter and a preview.
+//!
+//! The filter follows the direction of the resize until one is picked:
+//! Lanczos3 when shrinking, which keeps fine detail without aliasing, and
+//! Catmull-Rom when enlarging, which stays sharp without Lanczos' ringing.
+//! The preview shows the middle of the result, enlarged without smoothing, so
+//! the difference in sharpness is visible before applying.
+
+use crate::dialog;
+use eframe::egui;
+use image::imageops::FilterType;
+
+/// Side of the preview, in pixels of the resized image.
+const PREVIEW_SIDE: u32 = 96;
+/// Screen points per preview pixel, so single pixels can be told apart.
+const PREVIEW_ZOOM: f32 = 2.0;
+/// Largest side the dialog accepts.
+const MAX_SIDE: u32 = 32768;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ResampleFilter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Lanczos3,
+}
+
+impl ResampleFilter {
+    pub const ALL: [ResampleFilter; 4] = [Self::Nearest, Self::Triangle, Self::CatmullRom, Self::Lanczos3];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Nearest => "Nearest",
+            Self::Triangle => "Bilinear",
+            Self::CatmullRom => "Catmull-Rom",
+            Self::Lanczos3 => "Lanczos3",
+        }
+    }
+
+    fn hint(self) -> &'static str {
+        match self {
+            Self::Nearest => "Blocky; keeps pixel art crisp",
+            Self::Triangle => "Smooth and soft",
+            Self::CatmullRom => "Sharp, with little ringing; good for enlarging",
+            Self::Lanczos3 => "Sharpest; best for shrinking photos",
+        }
+    }
+
+    pub fn filter_type(self) -> FilterType {
+        match self {
+            Self::Nearest => FilterType::Nearest,
+            Self::Triangle => FilterType::Triangle,
+            Self::CatmullRom => FilterType::CatmullRom,
+            Self::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+
+    /// The filter suited to going from `from` pixels to `to`.
+    pub fn default_for(from: [u32; 2], to: [u32; 2]) -> Self {
+        if (to[0] as u64 * to[1] as u64) < (from[0] as u64 * from[1] as u64) {
+            Self::Lanczos3
+        } else {
+            Self::CatmullRom
+        }
+    }
+}
+
+/// What the user chose in the resize window.
+pub enum ResizeAction {
+    Apply { width: u32, height: u32, filter: ResampleFilter },
+    Close,
+}
+
+pub struct ResizeDialog {
+    source: [u32; 2],
+    width: u32,
+    height: u32,
+    keep_aspect: bool,
+    /// Picked by the user; `None` follows the direction of the resize.
+    filter: Option<ResampleFilter>,
+    preview: Option<egui::TextureHandle>,
+    /// Size and filter `preview` was made for.
+    preview_of: Option<([u32; 2], ResampleFilter)>,
+}
+
+impl ResizeDialog {
+    /// Opens the window for an image of `width` x `height`.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            source: [width, height],
+            width,
+            height,
+            keep_aspect: true,
+            filter: None,
+            preview: None,
+            preview_of: None,
+        }
+    }
+
+    fn filter(&self) -> ResampleFilter {
+        self.filter.unwrap_or_else(|| ResampleFilter::default_for(self.source, [self.width, self.height]))
+    }
+
+    /// Draws the window for `img`, the image being resized. `escaped` is set
+    /// when Escape was pressed for this window.
+    pub fn show(&mut self, ctx: &egui::Context, img: &image::DynamicImage, escaped: bool) -> Option<ResizeAction> {
+        // Start over if the image changed size underneath, e.g. by a rotation
+        if [img.width(), img.height()] != self.source {
+            *self = Self::new(img.width(), img.height());
+        }
+        let mut action = None;
+        let mut open = true;
+        let [source_w, source_h] = self.source;
+        let aspect = source_w as f64 / source_h as f64;
+
+        egui::Window::new("Resize")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::Grid::new("resize_grid").num_columns(2).show(ui, |ui| {
+                    ui.label("Width:");
+                    if ui.add(egui::DragValue::new(&mut self.width).range(1..=MAX_SIDE).suffix(" px")).changed() && self.keep_aspect {
+                        self.height = ((self.width as f64 / aspect).round() as u32).clamp(1, MAX_SIDE);
+                    }
+                    ui.end_row();
+
+                    ui.label("Height:");
+                    if ui.add(egui::DragValue::new(&mut self.height).range(1..=MAX_SIDE).suffix(" px")).changed() && self.keep_aspect {
+                        self.width = ((self.height as f64 * aspect).round() as u32).clamp(1, MAX_SIDE);
+                    }
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.horizontal(|ui| {
+                        if ui.checkbox(&mut self.keep_aspect, "Keep aspect ratio").changed() && self.keep_aspect {
+                            self.height = ((self.width as f64 / aspect).round() as u32).clamp(1, MAX_SIDE);
+                        }
+                        let percent = self.width as f64 / source_w as f64 * 100.0;
+                        ui.label(egui::RichText::new(format!("{:.0}%", percent)).color(egui::Color32::GRAY));
+                    });
+                    ui.end_row();
+
+                    ui.label("Filter:");
+                    let automatic = ResampleFilter::default_for(self.source, [self.width, self.height]);
+                    ui.horizontal(|ui| {
+                        for filter in ResampleFilter::ALL {
+                            let resp = ui.selectable_label(self.filter() == filter, filter.label()).on_hover_text(filter.hint());
+                            if resp.clicked() {
+                                // Picking the automatic choice goes back to following the direction
+                                self.filter = (filter != automatic).then_some(filter);
+                            }
+                        }
+                    });
+                    ui.end_row();
+                });
+
+                ui.add_space(6.0);
+                self.update_preview(ctx, img);
+                if let Some(preview) = &self.preview {
+                    ui.add(egui::Image::new(preview).fit_to_exact_size(preview.size_vec2() * PREVIEW_ZOOM));
+                    ui.label(egui::RichText::new("Center of the result, enlarged 2x").small().color(egui::Color32::GRAY));
+                }
+
+                ui.add_space(6.0);
+                ui.horizontal(|ui| {
+                    let unchanged = [self.width, self.height] == self.source;
+                    if ui.add_enabled(!unchanged, egui::Button::new("Apply")).clicked() {
+                        action = Some(ResizeAction::Apply { width: self.width, height: self.height, filter: self.filter() });
+                    }
+                    if ui.button("Cancel").clicked() {
+                        action = Some(ResizeAction::Close);
+                    }
+                });
+            });
+
+        // Enter applies, also straight from a size field
+        let unchanged = [self.width, self.height] == self.source;
+        if action.is_none() && !unchanged && dialog::enter_pressed(ctx) {
+            action = Some(ResizeAction::Apply { width: self.width, height: self.height, filter: self.filter() });
+        }
+        if !open || escaped {
+            action = Some(ResizeAction::Close);
+        }
+        action
+    }
+
+    /// Resizes the middle of `img` with the current settings when they changed.
+    fn update_preview(&mut self, ctx: &egui::Context, img: &image::DynamicImage) {
+        let key = ([self.width, self.height], self.filter());
+        if self.preview_of == Some(key) {
+            return;
+        }
+        self.preview_of = Some(key);
+
+        // The part of the source that lands in the preview, plus a margin so the
+        // filter sees real neighbors at the edges
+        let [source_w, source_h] = self.source;
+        let (scale_x, scale_y) = (self.width as f64 / source_w as f64, self.height as f64 / source_h as f64);
+        let (out_w, out_h) = (PREVIEW_SIDE.min(self.width), PREVIEW_SIDE.min(self.height));
+        let margin = 4;
+        let crop_w = ((out_w as f64 / scale_x).ceil() as u32 + 2 * margin).min(source_w);
+        let crop_h = ((out_h as f64 / scale_y).ceil() as u32 + 2 * margin).min(source_h);
+        let (crop_x, crop_y) = ((source_w - crop_w) / 2, (source_h - crop_h) / 2);
+        let crop = img.crop_imm(crop_x, crop_y, crop_w, crop_h);
+
+        let scaled_w = ((crop_w as f64 * scale_x).round() as u32).max(1);
+        let scaled_h = ((crop_h as f64 * scale_y).round() as u32).max(1);
+        let scaled = crop.resize_exact(scaled_w, scaled_h, key.1.filter_type());
+        let (x, y) = (scaled_w.saturating_sub(out_w) / 2, scaled_h.saturating_sub(out_h) / 2);
+        let rgba = scaled.crop_imm(x, y, out_w.min(scaled_w), out_h.min(scaled_h)).to_rgba8();
+
+        let image = egui::ColorImage::from_rgba_unmultiplied([rgba.width() as usize, rgba.height() as usize], rgba.as_raw());
+        let options = egui::TextureOptions::NEAREST;
+        match &mut self.preview {
+            Some(texture) => texture.set(image, options),
+            None => self.preview = Some(ctx.load_texture("resize_preview", image, options)),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Ad-hoc list of images with no file behind them, such as clipboard pastes.
+//!
+//! While one of these is shown there is no `current_path`, so the arrow keys cycle
+//! through this list instead of the folder. Each entry keeps its own pixels and
+//! unsaved drawings while another image is on screen.
+
+use crate::viewer::DrawingObject;
+
+/// An in-memory image and its unsaved edits.
+pub struct SessionImage {
+    pub name: String,
+    pub image: image::DynamicImage,
+    pub drawings: Vec<DrawingObject>,
+    pub is_edited: bool,
+}
+
+/// Path-less images opened this session, oldest first.
+#[derive(Default)]
+pub struct Session {
+    entries: Vec<SessionImage>,
+    /// Entry currently shown, `None` while viewing a file.
+    pub current: Option<usize>,
+}
+
+impl Session {
+    /// Appends an image and returns its index.
+    pub fn push(&mut self, name: String, image: image::DynamicImage) -> usize {
+        self.entries.push(SessionImage { name, image, drawings: Vec::new(), is_edited: false });
+        self.entries.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&SessionImage> {
+        self.entries.get(idx)
+    }
+
+    /// The entry currently on screen, if any.
+    pub fn current_mut(&mut self) -> Option<&mut SessionImage> {
+        self.entries.get_mut(self.current?)
+    }
+
+    /// Number of entries other than the one on screen with unsaved edits.
+    pub fn edited_elsewhere(&self) -> usize {
+        self.entries.iter().enumerate().filter(|&(idx, entry)| entry.is_edited && Some(idx) != self.current).count()
+    }
+
+    /// Forgets the unsaved edits of every entry, as when closing without them.
+    pub fn discard_edits(&mut self) {
+        for entry in &mut self.entries {
+            entry.is_edited = false;
+        }
+    }
+
+    /// Index after the current one, wrapping around.
+    pub fn next_index(&self) -> Option<usize> {
+        Some((self.current? + 1) % self.entries.len())
+    }
+
+    /// Index before the current one, wrapping around.
+    pub fn prev_index(&self) -> Option<usize> {
+        let current = self.current?;
+        Some(if current == 0 { self.entries.len() - 1 } else { current - 1 })
+    }
+}
@@ -2,17 +2,39 @@
 //!
 //! This module provides a `ShareManager` that wraps the `localsend` crate's `Client`
 //! and handles asynchronous discovery and file transfer in a background thread,
-//! communicating state changes back to the UI thread via channels.
+//! receiving commands over a channel and reporting state changes back to the UI
+//! thread through a bounded event queue.
+//!
+//! Sends are queued and run one at a time in the order requested. Each gets an
+//! id so the UI can follow it through `SendQueue` and retry it on its own.
+//! The client reports no progress within a file, so the transfer rate is
+//! measured per finished file and the time left is estimated from it.
 
 use localsend::Client;
 use localsend::models::device::DeviceInfo;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::runtime::Runtime;
 use tokio::sync::mpsc;
 
+/// Consecutive sync cycles a peer must be missing before `PeerLost` is emitted.
+/// Keeps the peer list stable when discovery momentarily drops a device.
+const PEER_LOST_CYCLES: u32 = 2;
+
+/// Maximum number of events buffered for the UI.
+///
+/// The UI only drains events while the share modal is open, so discovery churn can pile up
+/// in the meantime. 256 comfortably covers a burst of transfers plus peer updates.
+const EVENT_QUEUE_CAPACITY: usize = 256;
+
+/// Weight of the latest file's speed in the smoothed transfer rate. Lower is
+/// steadier but slower to follow a change in network speed.
+const RATE_SMOOTHING: f64 = 0.3;
+
 /// Events sent from the share manager to the UI.
 #[derive(Debug, Clone)]
 pub enum ShareEvent {
@@ -20,21 +42,68 @@ pub enum ShareEvent {
     PeerDiscovered { fingerprint: String, device: DeviceInfo, addr: SocketAddr },
     /// A peer device was removed or became unreachable.
     PeerLost { fingerprint: String },
-    /// File transfer started.
-    TransferStarted { peer_fingerprint: String, file_path: PathBuf },
-    /// File transfer completed successfully.
-    TransferComplete { peer_fingerprint: String },
-    /// File transfer failed.
-    TransferFailed { peer_fingerprint: String, error: String },
+    /// A file was added to the send queue, or put back on it for a retry.
+    TransferQueued { id: u64, peer_fingerprint: String, file_path: PathBuf, bytes: u64 },
+    /// File transfer started at `at`.
+    TransferStarted { id: u64, peer_fingerprint: String, at: Instant },
+    /// File transfer completed successfully at `at`.
+    TransferComplete { id: u64, peer_fingerprint: String, file_path: PathBuf, at: Instant },
+    /// File transfer failed. Carries the original file so the send can be retried.
+    TransferFailed { id: u64, peer_fingerprint: String, file_path: PathBuf, error: String },
     /// An error occurred in the background service.
     Error(String),
 }
 
+impl ShareEvent {
+    /// Whether this event may be dropped when the queue is full.
+    ///
+    /// Peer updates are superseded by later ones (and the UI reads `get_peers()` anyway),
+    /// while transfer outcomes and errors must always reach the user.
+    fn is_lossy(&self) -> bool {
+        matches!(self, ShareEvent::PeerDiscovered { .. } | ShareEvent::PeerLost { .. })
+    }
+}
+
+/// Bounded event buffer shared between the background task and the UI.
+///
+/// When full, the oldest lossy event is dropped to make room. Reliable events are never
+/// dropped, so the queue can only exceed `EVENT_QUEUE_CAPACITY` with transfer outcomes,
+/// which are bounded by user actions.
+#[derive(Clone)]
+struct EventQueue {
+    events: Arc<Mutex<VecDeque<ShareEvent>>>,
+}
+
+impl EventQueue {
+    fn new() -> Self {
+        Self { events: Arc::new(Mutex::new(VecDeque::with_capacity(EVENT_QUEUE_CAPACITY))) }
+    }
+
+    /// Queues an event, evicting the oldest lossy one on overflow.
+    fn push(&self, event: ShareEvent) {
+        let mut events = self.events.lock().unwrap();
+        if events.len() >= EVENT_QUEUE_CAPACITY {
+            if let Some(idx) = events.iter().position(|e| e.is_lossy()) {
+                events.remove(idx);
+            } else if event.is_lossy() {
+                // Full of reliable events; the newcomer is the one to go
+                return;
+            }
+        }
+        events.push_back(event);
+    }
+
+    /// Takes all queued events in order.
+    fn drain(&self) -> Vec<ShareEvent> {
+        self.events.lock().unwrap().drain(..).collect()
+    }
+}
+
 /// Commands sent from the UI to the share manager.
 #[derive(Debug)]
 pub enum ShareCommand {
-    /// Request to send a file to a peer.
-    SendFile { peer_fingerprint: String, file_path: PathBuf },
+    /// Request to send a file to a peer, after the ones requested before it.
+    SendFile { id: u64, peer_fingerprint: String, file_path: PathBuf },
     /// Stop the share manager.
     Shutdown,
 }
@@ -43,10 +112,12 @@ pub enum ShareCommand {
 pub struct ShareManager {
     /// Channel to send commands to the background task.
     command_tx: mpsc::UnboundedSender<ShareCommand>,
-    /// Channel to receive events from the background task.
-    event_rx: Arc<Mutex<mpsc::UnboundedReceiver<ShareEvent>>>,
+    /// Bounded queue of events from the background task.
+    events: EventQueue,
     /// Shared peers list (fingerprint -> (SocketAddr, DeviceInfo)).
     peers: Arc<Mutex<HashMap<String, (SocketAddr, DeviceInfo)>>>,
+    /// Id given to the next queued send.
+    next_id: AtomicU64,
 }
 
 impl ShareManager {
@@ -56,7 +127,8 @@ impl ShareManager {
     /// LocalSend discovery and file transfers.
     pub fn new() -> Result<Self, String> {
         let (command_tx, mut command_rx) = mpsc::unbounded_channel::<ShareCommand>();
-        let (event_tx, event_rx) = mpsc::unbounded_channel::<ShareEvent>();
+        let events = EventQueue::new();
+        let event_tx = events.clone();
         let peers: Arc<Mutex<HashMap<String, (SocketAddr, DeviceInfo)>>> = Arc::new(Mutex::new(HashMap::new()));
         let peers_clone = peers.clone();
 
@@ -65,7 +137,7 @@ impl ShareManager {
             let rt = match Runtime::new() {
                 Ok(rt) => rt,
                 Err(e) => {
-                    let _ = event_tx.send(ShareEvent::Error(format!("Failed to create runtime: {}", e)));
+                    event_tx.push(ShareEvent::Error(format!("Failed to create runtime: {}", e)));
                     return;
                 }
             };
@@ -75,7 +147,7 @@ impl ShareManager {
                 let mut client_obj = match Client::default().await {
                     Ok(c) => c,
                     Err(e) => {
-                        let _ = event_tx.send(ShareEvent::Error(format!("Failed to create LocalSend client: {:?}", e)));
+                        event_tx.push(ShareEvent::Error(format!("Failed to create LocalSend client: {:?}", e)));
                         return;
                     }
                 };
@@ -87,7 +159,7 @@ impl ShareManager {
                 {
                     Ok(new_http) => client_obj.http_client = new_http,
                     Err(e) => {
-                        let _ = event_tx.send(ShareEvent::Error(format!("Failed to configure HTTP client: {:?}", e)));
+                        event_tx.push(ShareEvent::Error(format!("Failed to configure HTTP client: {:?}", e)));
                         return;
                     }
                 }
@@ -96,7 +168,7 @@ impl ShareManager {
 
                 // Start the client (discovery and HTTP server)
                 if let Err(e) = client.start().await {
-                    let _ = event_tx.send(ShareEvent::Error(format!("Failed to start LocalSend client: {:?}", e)));
+                    event_tx.push(ShareEvent::Error(format!("Failed to start LocalSend client: {:?}", e)));
                     return;
                 }
 
@@ -105,16 +177,19 @@ impl ShareManager {
                 let peers_for_sync = peers_clone.clone();
                 let event_tx_sync = event_tx.clone();
                 tokio::spawn(async move {
+                    // fingerprint -> number of consecutive cycles the peer has been missing
+                    let mut missing_cycles: HashMap<String, u32> = HashMap::new();
                     loop {
                         tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
                         
                         let current_peers = client_peers.peers.lock().await;
                         let mut local_peers = peers_for_sync.lock().unwrap();
                         
-                        // Check for new peers
+                        // Check for new peers (rediscovery is immediate)
                         for (fingerprint, (addr, info)) in current_peers.iter() {
+                            missing_cycles.remove(fingerprint);
                             if !local_peers.contains_key(fingerprint) {
-                                let _ = event_tx_sync.send(ShareEvent::PeerDiscovered {
+                                event_tx_sync.push(ShareEvent::PeerDiscovered {
                                     fingerprint: fingerprint.clone(),
                                     device: info.clone(),
                                     addr: *addr,
@@ -123,14 +198,19 @@ impl ShareManager {
                             local_peers.insert(fingerprint.clone(), (*addr, info.clone()));
                         }
                         
-                        // Check for lost peers
-                        let lost: Vec<String> = local_peers.keys()
+                        // Check for lost peers, only dropping them after several missed cycles
+                        let missing: Vec<String> = local_peers.keys()
                             .filter(|k| !current_peers.contains_key(*k))
                             .cloned()
                             .collect();
-                        for fingerprint in lost {
-                            local_peers.remove(&fingerprint);
-                            let _ = event_tx_sync.send(ShareEvent::PeerLost { fingerprint });
+                        for fingerprint in missing {
+                            let cycles = missing_cycles.entry(fingerprint.clone()).or_insert(0);
+                            *cycles += 1;
+                            if *cycles >= PEER_LOST_CYCLES {
+                                missing_cycles.remove(&fingerprint);
+                                local_peers.remove(&fingerprint);
+                                event_tx_sync.push(ShareEvent::PeerLost { fingerprint });
+                            }
                         }
                     }
                 });
@@ -138,21 +218,27 @@ impl ShareManager {
                 // Handle commands from the UI
                 while let Some(cmd) = command_rx.recv().await {
                     match cmd {
-                        ShareCommand::SendFile { peer_fingerprint, file_path } => {
-                            let _ = event_tx.send(ShareEvent::TransferStarted {
+                        ShareCommand::SendFile { id, peer_fingerprint, file_path } => {
+                            event_tx.push(ShareEvent::TransferStarted {
+                                id,
                                 peer_fingerprint: peer_fingerprint.clone(),
-                                file_path: file_path.clone(),
+                                at: Instant::now(),
                             });
                             
                             match client.send_file(peer_fingerprint.clone(), file_path.clone()).await {
                                 Ok(()) => {
-                                    let _ = event_tx.send(ShareEvent::TransferComplete {
+                                    event_tx.push(ShareEvent::TransferComplete {
+                                        id,
                                         peer_fingerprint,
+                                        file_path,
+                                        at: Instant::now(),
                                     });
                                 }
                                 Err(e) => {
-                                    let _ = event_tx.send(ShareEvent::TransferFailed {
+                                    event_tx.push(ShareEvent::TransferFailed {
+                                        id,
                                         peer_fingerprint,
+                                        file_path,
                                         error: format!("{:?}", e),
                                     });
                                 }
@@ -168,15 +254,33 @@ impl ShareManager {
 
         Ok(Self {
             command_tx,
-            event_rx: Arc::new(Mutex::new(event_rx)),
+            events,
             peers,
+            next_id: AtomicU64::new(0),
         })
     }
 
-    /// Sends a file to a peer device.
-    pub fn send_file(&self, peer_fingerprint: String, file_path: PathBuf) -> Result<(), String> {
+    /// Queues files to be sent to a peer device, in order.
+    pub fn send_files(&self, peer_fingerprint: &str, file_paths: Vec<PathBuf>) -> Result<(), String> {
+        for file_path in file_paths {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            self.enqueue(id, peer_fingerprint.to_string(), file_path)?;
+        }
+        Ok(())
+    }
+
+    /// Puts a failed send back on the queue under its id.
+    pub fn retry(&self, item: &SendItem) -> Result<(), String> {
+        self.enqueue(item.id, item.peer_fingerprint.clone(), item.file_path.clone())
+    }
+
+    fn enqueue(&self, id: u64, peer_fingerprint: String, file_path: PathBuf) -> Result<(), String> {
+        // Reported from here rather than the background task, which only reads
+        // the next command once the current transfer is over
+        let bytes = std::fs::metadata(&file_path).map_or(0, |meta| meta.len());
+        self.events.push(ShareEvent::TransferQueued { id, peer_fingerprint: peer_fingerprint.clone(), file_path: file_path.clone(), bytes });
         self.command_tx
-            .send(ShareCommand::SendFile { peer_fingerprint, file_path })
+            .send(ShareCommand::SendFile { id, peer_fingerprint, file_path })
             .map_err(|e| format!("Failed to send command: {}", e))
     }
 
@@ -185,15 +289,18 @@ impl ShareManager {
         self.peers.lock().unwrap().clone()
     }
 
+    /// Returns the user-facing name of a peer, or its short fingerprint if unknown.
+    pub fn display_name(&self, fingerprint: &str) -> String {
+        let peers = self.peers.lock().unwrap();
+        let Some((_addr, info)) = peers.get(fingerprint) else { return short_fingerprint(fingerprint) };
+        let name = peer_name(fingerprint, info);
+        let shared = peers.iter().any(|(other, (_addr, info))| other != fingerprint && peer_name(other, info) == name);
+        disambiguate(fingerprint, name, shared)
+    }
+
     /// Polls for events from the background task (non-blocking).
     pub fn poll_events(&self) -> Vec<ShareEvent> {
-        let mut events = Vec::new();
-        if let Ok(mut rx) = self.event_rx.lock() {
-            while let Ok(event) = rx.try_recv() {
-                events.push(event);
-            }
-        }
-        events
+        self.events.drain()
     }
 
     /// Shuts down the share manager.
@@ -207,3 +314,321 @@ impl Drop for ShareManager {
         self.shutdown();
     }
 }
+
+/// Where a queued send is at.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SendState {
+    Pending,
+    Active,
+    Done,
+    Failed(String),
+}
+
+/// One file in the send queue.
+#[derive(Debug, Clone)]
+pub struct SendItem {
+    pub id: u64,
+    pub peer_fingerprint: String,
+    pub file_path: PathBuf,
+    pub state: SendState,
+    /// Size of the file when it was queued.
+    pub bytes: u64,
+    /// When the transfer started, while it is active.
+    started: Option<Instant>,
+}
+
+/// The UI's view of the send queue, kept up to date from `ShareEvent`s.
+#[derive(Default)]
+pub struct SendQueue {
+    pub items: Vec<SendItem>,
+    /// Smoothed transfer rate in bytes per second, once a file went through.
+    rate: Option<f64>,
+}
+
+impl SendQueue {
+    /// Updates the item a transfer event is about. Other events are ignored.
+    pub fn apply(&mut self, event: &ShareEvent) {
+        let (id, state) = match event {
+            ShareEvent::TransferQueued { id, peer_fingerprint, file_path, bytes } => {
+                if !self.items.iter().any(|item| item.id == *id) {
+                    self.items.push(SendItem {
+                        id: *id,
+                        peer_fingerprint: peer_fingerprint.clone(),
+                        file_path: file_path.clone(),
+                        state: SendState::Pending,
+                        bytes: *bytes,
+                        started: None,
+                    });
+                }
+                (*id, SendState::Pending)
+            }
+            ShareEvent::TransferStarted { id, .. } => (*id, SendState::Active),
+            ShareEvent::TransferComplete { id, .. } => (*id, SendState::Done),
+            ShareEvent::TransferFailed { id, error, .. } => (*id, SendState::Failed(error.clone())),
+            _ => return,
+        };
+        let Some(item) = self.items.iter_mut().find(|item| item.id == id) else { return };
+        item.state = state;
+        match event {
+            ShareEvent::TransferStarted { at, .. } => item.started = Some(*at),
+            ShareEvent::TransferComplete { at, .. } => {
+                if let Some(started) = item.started.take() {
+                    let secs = at.duration_since(started).as_secs_f64().max(0.001);
+                    let sample = item.bytes as f64 / secs;
+                    self.rate = Some(self.rate.map_or(sample, |rate| rate + RATE_SMOOTHING * (sample - rate)));
+                }
+            }
+            _ => item.started = None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Items that are done or failed, and the total.
+    pub fn progress(&self) -> (usize, usize) {
+        let finished = self.items.iter().filter(|item| matches!(item.state, SendState::Done | SendState::Failed(_))).count();
+        (finished, self.items.len())
+    }
+
+    /// True while any item is waiting or being sent.
+    pub fn is_busy(&self) -> bool {
+        self.items.iter().any(|item| matches!(item.state, SendState::Pending | SendState::Active))
+    }
+
+    /// Smoothed transfer rate in bytes per second, once a file was sent.
+    pub fn rate(&self) -> Option<f64> {
+        self.rate
+    }
+
+    /// Estimated time until the waiting and active sends are through, at the
+    /// smoothed rate. The active file is assumed to be going at that rate too.
+    pub fn time_left(&self) -> Option<Duration> {
+        self.time_left_at(Instant::now())
+    }
+
+    fn time_left_at(&self, now: Instant) -> Option<Duration> {
+        let rate = self.rate.filter(|rate| *rate > 0.0)?;
+        if !self.is_busy() {
+            return None;
+        }
+        let remaining: f64 = self.items.iter()
+            .map(|item| match (&item.state, item.started) {
+                (SendState::Pending, _) => item.bytes as f64,
+                (SendState::Active, Some(started)) => (item.bytes as f64 - now.duration_since(started).as_secs_f64() * rate).max(0.0),
+                (SendState::Active, None) => item.bytes as f64,
+                _ => 0.0,
+            })
+            .sum();
+        Some(Duration::from_secs_f64(remaining / rate))
+    }
+
+    /// Drops the items that were sent, keeping failed ones for a retry.
+    pub fn clear_done(&mut self) {
+        self.items.retain(|item| item.state != SendState::Done);
+    }
+}
+
+/// Number of fingerprint characters used to disambiguate peers with the same alias.
+const SHORT_FINGERPRINT_LEN: usize = 6;
+
+fn short_fingerprint(fingerprint: &str) -> String {
+    fingerprint.chars().take(SHORT_FINGERPRINT_LEN).collect()
+}
+
+/// A peer's name before disambiguation: its alias, else its device model, else its fingerprint.
+fn peer_name<'a>(fingerprint: &'a str, info: &'a DeviceInfo) -> &'a str {
+    base_name(fingerprint, &info.alias, info.device_model.as_deref())
+}
+
+fn base_name<'a>(fingerprint: &'a str, alias: &'a str, model: Option<&'a str>) -> &'a str {
+    [Some(alias), model]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .find(|name| !name.is_empty())
+        .unwrap_or(fingerprint)
+}
+
+/// Adds a short fingerprint suffix to a name other peers also go by.
+fn disambiguate(fingerprint: &str, name: &str, shared: bool) -> String {
+    if shared {
+        format!("{} ({})", name, short_fingerprint(fingerprint))
+    } else {
+        name.to_string()
+    }
+}
+
+/// Builds a user-facing name for every peer, keyed by fingerprint.
+///
+/// Peers sharing a name get a short fingerprint suffix so they can be told apart.
+pub fn peer_display_names(peers: &HashMap<String, (SocketAddr, DeviceInfo)>) -> HashMap<String, String> {
+    unique_names(peers.iter().map(|(fingerprint, (_addr, info))| (fingerprint.as_str(), peer_name(fingerprint, info))))
+}
+
+/// Disambiguates `(fingerprint, name)` pairs, keyed by fingerprint.
+fn unique_names<'a>(names: impl Iterator<Item = (&'a str, &'a str)> + Clone) -> HashMap<String, String> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for (_fingerprint, name) in names.clone() {
+        *counts.entry(name).or_insert(0) += 1;
+    }
+    names
+        .map(|(fingerprint, name)| (fingerprint.to_string(), disambiguate(fingerprint, name, counts[name] > 1)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(id: u64, bytes: u64) -> ShareEvent {
+        ShareEvent::TransferQueued { id, peer_fingerprint: "peer".into(), file_path: format!("{id}.png").into(), bytes }
+    }
+
+    fn started(id: u64, at: Instant) -> ShareEvent {
+        ShareEvent::TransferStarted { id, peer_fingerprint: "peer".into(), at }
+    }
+
+    fn complete(id: u64, at: Instant) -> ShareEvent {
+        ShareEvent::TransferComplete { id, peer_fingerprint: "peer".into(), file_path: format!("{id}.png").into(), at }
+    }
+
+    fn failed(id: u64) -> ShareEvent {
+        ShareEvent::TransferFailed { id, peer_fingerprint: "peer".into(), file_path: format!("{id}.png").into(), error: "refused".into() }
+    }
+
+    #[test]
+    fn rate_and_time_left_follow_transfer_times() {
+        let t0 = Instant::now();
+        let secs = |s: f64| t0 + Duration::from_secs_f64(s);
+        let mut queue = SendQueue::default();
+        queue.apply(&queued(0, 1000));
+        queue.apply(&queued(1, 2000));
+        assert_eq!(queue.time_left_at(t0), None);
+
+        queue.apply(&started(0, t0));
+        queue.apply(&complete(0, secs(1.0)));
+        assert_eq!(queue.rate(), Some(1000.0));
+        assert_eq!(queue.time_left_at(secs(1.0)), Some(Duration::from_secs(2)));
+
+        // The active file counts down at the smoothed rate
+        queue.apply(&started(1, secs(1.0)));
+        assert_eq!(queue.time_left_at(secs(1.5)), Some(Duration::from_secs_f64(1.5)));
+        assert_eq!(queue.time_left_at(secs(4.0)), Some(Duration::ZERO));
+
+        // 2000 B/s moves the rate by RATE_SMOOTHING of the difference
+        queue.apply(&complete(1, secs(2.0)));
+        assert!((queue.rate().unwrap() - (1000.0 + RATE_SMOOTHING * 1000.0)).abs() < 1e-6);
+        assert_eq!(queue.time_left_at(secs(2.0)), None);
+    }
+
+    #[test]
+    fn failed_send_is_retried_under_its_id() {
+        let t0 = Instant::now();
+        let mut queue = SendQueue::default();
+        queue.apply(&queued(0, 10));
+        queue.apply(&queued(1, 10));
+        queue.apply(&started(0, t0));
+        assert_eq!(queue.items[0].state, SendState::Active);
+        queue.apply(&failed(0));
+        assert_eq!(queue.items[0].state, SendState::Failed("refused".into()));
+        assert_eq!(queue.progress(), (1, 2));
+
+        queue.apply(&queued(0, 10));
+        assert_eq!(queue.items.len(), 2);
+        assert_eq!(queue.items[0].state, SendState::Pending);
+        assert_eq!(queue.progress(), (0, 2));
+        queue.apply(&started(0, t0));
+        queue.apply(&complete(0, t0));
+        assert_eq!(queue.items[0].state, SendState::Done);
+        assert_eq!(queue.progress(), (1, 2));
+    }
+
+    #[test]
+    fn clear_done_keeps_failed_and_waiting_sends() {
+        let t0 = Instant::now();
+        let mut queue = SendQueue::default();
+        for id in 0..3 {
+            queue.apply(&queued(id, 10));
+        }
+        queue.apply(&started(0, t0));
+        queue.apply(&complete(0, t0));
+        queue.apply(&started(1, t0));
+        queue.apply(&failed(1));
+        assert_eq!(queue.progress(), (2, 3));
+
+        queue.clear_done();
+        let ids: Vec<u64> = queue.items.iter().map(|item| item.id).collect();
+        assert_eq!(ids, [1, 2]);
+        assert_eq!(queue.progress(), (1, 2));
+    }
+
+    fn is_peer_lost(event: &ShareEvent) -> bool {
+        matches!(event, ShareEvent::PeerLost { .. })
+    }
+
+    fn full_of_errors() -> EventQueue {
+        let queue = EventQueue::new();
+        for i in 0..EVENT_QUEUE_CAPACITY {
+            queue.push(ShareEvent::Error(i.to_string()));
+        }
+        queue
+    }
+
+    #[test]
+    fn full_queue_evicts_peer_events_first() {
+        let queue = EventQueue::new();
+        queue.push(ShareEvent::Error("first".into()));
+        queue.push(ShareEvent::PeerLost { fingerprint: "peer".into() });
+        for i in 2..EVENT_QUEUE_CAPACITY {
+            queue.push(ShareEvent::Error(i.to_string()));
+        }
+        queue.push(ShareEvent::Error("last".into()));
+
+        let events = queue.drain();
+        assert_eq!(events.len(), EVENT_QUEUE_CAPACITY);
+        assert!(!events.iter().any(is_peer_lost));
+        assert!(matches!(&events[0], ShareEvent::Error(e) if e == "first"));
+        assert!(matches!(events.last(), Some(ShareEvent::Error(e)) if e == "last"));
+    }
+
+    #[test]
+    fn transfer_events_are_never_dropped() {
+        let queue = full_of_errors();
+        queue.push(failed(0));
+        queue.push(complete(1, Instant::now()));
+
+        let events = queue.drain();
+        assert_eq!(events.len(), EVENT_QUEUE_CAPACITY + 2);
+        assert!(matches!(&events[EVENT_QUEUE_CAPACITY], ShareEvent::TransferFailed { id: 0, .. }));
+        assert!(matches!(&events[EVENT_QUEUE_CAPACITY + 1], ShareEvent::TransferComplete { id: 1, .. }));
+    }
+
+    #[test]
+    fn peer_event_is_dropped_when_full_of_reliable_ones() {
+        let queue = full_of_errors();
+        queue.push(ShareEvent::PeerLost { fingerprint: "peer".into() });
+
+        let events = queue.drain();
+        assert_eq!(events.len(), EVENT_QUEUE_CAPACITY);
+        assert!(!events.iter().any(is_peer_lost));
+    }
+
+    #[test]
+    fn empty_alias_falls_back_to_model_then_fingerprint() {
+        assert_eq!(base_name("abcdef123", "Laptop", Some("ThinkPad")), "Laptop");
+        assert_eq!(base_name("abcdef123", "  ", Some("Pixel 8")), "Pixel 8");
+        assert_eq!(base_name("abcdef123", "", Some(" ")), "abcdef123");
+        assert_eq!(base_name("abcdef123", "", None), "abcdef123");
+    }
+
+    #[test]
+    fn duplicate_names_get_a_fingerprint_suffix() {
+        let peers = [("aaaaaa111", "Phone"), ("bbbbbb222", "Phone"), ("cccccc333", "Laptop")];
+        let names = unique_names(peers.iter().copied());
+        assert_eq!(names["aaaaaa111"], "Phone (aaaaaa)");
+        assert_eq!(names["bbbbbb222"], "Phone (bbbbbb)");
+        assert_eq!(names["cccccc333"], "Laptop");
+    }
+}
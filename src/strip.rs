@@ -0,0 +1,230 @@
+//! Continuous vertical reading mode.
+//!
+//! Stacks the images of the current folder top to bottom at the window width and
+//! scrolls across file boundaries, like a webtoon reader. Layout comes from the
+//! file headers, so only images near the viewport are actually decoded (on a
+//! background thread) and kept as textures; everything else is freed.
+
+use crate::orientation;
+use eframe::egui;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+
+/// Images decoded ahead of / behind the viewport.
+const PREFETCH_MARGIN: usize = 2;
+/// Textures further than this from the viewport are freed.
+const KEEP_MARGIN: usize = 4;
+
+enum Decoded {
+    Image(egui::ColorImage),
+    Failed,
+    /// Scrolled out of range before the worker got to it.
+    Skipped,
+    /// Native size from the file header, `None` if unreadable.
+    Size(Option<egui::Vec2>),
+}
+
+/// State of the vertical strip view.
+pub struct StripView {
+    /// Native size of each image, read from file headers by the worker. `None`
+    /// until it gets there, and if unreadable.
+    sizes: Vec<Option<egui::Vec2>>,
+    /// Headers the worker has yet to read.
+    unread: usize,
+    textures: HashMap<usize, egui::TextureHandle>,
+    pending: HashSet<usize>,
+    failed: HashSet<usize>,
+    /// Indices the worker should still bother decoding.
+    wanted: Arc<Mutex<Range<usize>>>,
+    request_tx: mpsc::Sender<(usize, PathBuf, u32)>,
+    result_rx: mpsc::Receiver<(usize, Decoded)>,
+    /// Index to scroll to on the next frame.
+    scroll_to: Option<usize>,
+    /// Image currently at the top of the viewport.
+    pub top_index: usize,
+}
+
+impl StripView {
+    /// Creates a strip over `paths`, initially scrolled to `start`.
+    pub fn new(paths: &[PathBuf], start: usize) -> Self {
+        // Headers from `start` on first, as those are on screen
+        let start = start.min(paths.len());
+        let headers: Vec<(usize, PathBuf)> = (start..paths.len()).chain(0..start).map(|idx| (idx, paths[idx].clone())).collect();
+        let mut headers = headers.into_iter();
+
+        let wanted = Arc::new(Mutex::new(0..0));
+        let wanted_worker = wanted.clone();
+        let (request_tx, request_rx) = mpsc::channel::<(usize, PathBuf, u32)>();
+        let (result_tx, result_rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            loop {
+                // Decodes go first; headers are read while none are waiting
+                let request = match request_rx.try_recv() {
+                    Ok(request) => request,
+                    Err(mpsc::TryRecvError::Disconnected) => break,
+                    Err(mpsc::TryRecvError::Empty) => match headers.next() {
+                        Some((idx, path)) => {
+                            let size = orientation::dimensions(&path).ok().map(|(w, h)| egui::vec2(w as f32, h as f32));
+                            if result_tx.send((idx, Decoded::Size(size))).is_err() {
+                                break;
+                            }
+                            continue;
+                        }
+                        None => match request_rx.recv() {
+                            Ok(request) => request,
+                            Err(_) => break,
+                        },
+                    },
+                };
+                let (idx, path, width) = request;
+                let decoded = if !wanted_worker.lock().unwrap().contains(&idx) {
+                    Decoded::Skipped
+                } else {
+                    match orientation::open(&path) {
+                        Ok(img) => {
+                            // Decode once at display width to keep GPU memory in check
+                            let img = if img.width() > width {
+                                img.resize(width, u32::MAX, image::imageops::FilterType::Triangle)
+                            } else {
+                                img
+                            };
+                            let rgba = img.to_rgba8();
+                            let size = [rgba.width() as usize, rgba.height() as usize];
+                            Decoded::Image(egui::ColorImage::from_rgba_unmultiplied(size, rgba.as_raw()))
+                        }
+                        Err(_) => Decoded::Failed,
+                    }
+                };
+                if result_tx.send((idx, decoded)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            sizes: vec![None; paths.len()],
+            unread: paths.len(),
+            textures: HashMap::new(),
+            pending: HashSet::new(),
+            failed: HashSet::new(),
+            wanted,
+            request_tx,
+            result_rx,
+            scroll_to: Some(start),
+            top_index: start,
+        }
+    }
+
+    /// Scrolls so image `idx` starts at the top of the viewport.
+    pub fn jump_to(&mut self, idx: usize) {
+        if idx < self.sizes.len() {
+            self.scroll_to = Some(idx);
+        }
+    }
+
+    /// Lays out and paints the strip, decoding what is visible.
+    pub fn show(&mut self, ui: &mut egui::Ui, paths: &[PathBuf]) {
+        let ctx = ui.ctx().clone();
+        self.receive(&ctx);
+
+        let n = self.sizes.len().min(paths.len());
+        if n == 0 {
+            return;
+        }
+
+        let width = ui.available_width().max(1.0);
+        let heights: Vec<f32> = self.sizes[..n].iter()
+            .map(|size| match size {
+                Some(s) if s.x > 0.0 => s.y * width / s.x,
+                _ => width * 0.5,
+            })
+            .collect();
+        let mut tops = Vec::with_capacity(n);
+        let mut total = 0.0;
+        for h in &heights {
+            tops.push(total);
+            total += h;
+        }
+
+        let mut area = egui::ScrollArea::vertical().auto_shrink([false, false]);
+        if let Some(idx) = self.scroll_to.take() {
+            area = area.vertical_scroll_offset(tops[idx.min(n - 1)]);
+        }
+
+        area.show_viewport(ui, |ui, viewport| {
+            ui.set_height(total);
+            let origin = ui.max_rect().min;
+
+            let first = tops.partition_point(|&t| t <= viewport.min.y).saturating_sub(1);
+            let last = tops.partition_point(|&t| t < viewport.max.y).max(first + 1).min(n);
+            self.top_index = first;
+
+            // Decode around the viewport, free everything well outside it
+            let wanted = first.saturating_sub(PREFETCH_MARGIN)..(last + PREFETCH_MARGIN).min(n);
+            *self.wanted.lock().unwrap() = wanted.clone();
+            let keep = first.saturating_sub(KEEP_MARGIN)..(last + KEEP_MARGIN).min(n);
+            self.textures.retain(|i, _| keep.contains(i));
+
+            for idx in wanted {
+                if !self.textures.contains_key(&idx) && !self.pending.contains(&idx) && !self.failed.contains(&idx) {
+                    self.pending.insert(idx);
+                    let _ = self.request_tx.send((idx, paths[idx].clone(), width.round() as u32));
+                }
+            }
+
+            let painter = ui.painter();
+            for idx in first..last {
+                let rect = egui::Rect::from_min_size(origin + egui::vec2(0.0, tops[idx]), egui::vec2(width, heights[idx]));
+                if let Some(tex) = self.textures.get(&idx) {
+                    let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                    painter.image(tex.id(), rect, uv, egui::Color32::WHITE);
+                } else {
+                    painter.rect_filled(rect, 0.0, egui::Color32::from_gray(20));
+                    let label = if self.failed.contains(&idx) { "Failed to load" } else { "Loading..." };
+                    let name = paths[idx].file_name().unwrap_or_default().to_string_lossy();
+                    painter.text(
+                        rect.center(),
+                        egui::Align2::CENTER_CENTER,
+                        format!("{}\n{}", name, label),
+                        egui::FontId::proportional(14.0),
+                        egui::Color32::GRAY,
+                    );
+                }
+            }
+        });
+
+        if !self.pending.is_empty() || self.unread > 0 {
+            ctx.request_repaint();
+        }
+    }
+
+    fn receive(&mut self, ctx: &egui::Context) {
+        let mut moved = false;
+        while let Ok((idx, decoded)) = self.result_rx.try_recv() {
+            if let Decoded::Size(size) = decoded {
+                self.sizes[idx] = size;
+                self.unread -= 1;
+                // A size above the top shifts everything below it
+                moved |= idx < self.top_index && size.is_some();
+                continue;
+            }
+            self.pending.remove(&idx);
+            match decoded {
+                Decoded::Image(color_image) => {
+                    let texture = ctx.load_texture(format!("strip:{}", idx), color_image, egui::TextureOptions::LINEAR);
+                    self.textures.insert(idx, texture);
+                }
+                Decoded::Failed => {
+                    self.failed.insert(idx);
+                }
+                Decoded::Skipped | Decoded::Size(_) => {}
+            }
+        }
+        if moved && self.scroll_to.is_none() {
+            self.scroll_to = Some(self.top_index);
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Shared thumbnail generation and caching for the browse views.
+//!
+//! Thumbnails are decoded on a background thread and downscaled with
+//! `DynamicImage::thumbnail`, which preserves aspect ratio. Views draw them with
+//! `fit_in_cell` so portrait and landscape images sit centered in fixed-size
+//! cells instead of being squished.
+
+use crate::orientation;
+use eframe::egui;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+/// Longest edge of a generated thumbnail, in pixels.
+pub const THUMBNAIL_SIZE: u32 = 160;
+
+/// Lazily generated thumbnail textures keyed by image path.
+pub struct ThumbnailCache {
+    textures: HashMap<PathBuf, egui::TextureHandle>,
+    /// Paths queued on the worker but not uploaded yet.
+    pending: HashSet<PathBuf>,
+    /// Paths that failed to decode, so they aren't retried every frame.
+    failed: HashSet<PathBuf>,
+    request_tx: mpsc::Sender<PathBuf>,
+    result_rx: mpsc::Receiver<(PathBuf, Option<egui::ColorImage>)>,
+    /// How thumbnails are sampled when drawn.
+    options: egui::TextureOptions,
+}
+
+impl ThumbnailCache {
+    /// Creates the cache and spawns its decode worker.
+    pub fn new(options: egui::TextureOptions) -> Self {
+        let (request_tx, request_rx) = mpsc::channel::<PathBuf>();
+        let (result_tx, result_rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            while let Ok(path) = request_rx.recv() {
+                let thumb = orientation::open(&path).ok().map(|img| to_color_image(&make_thumbnail(&img)));
+                if result_tx.send((path, thumb)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            textures: HashMap::new(),
+            pending: HashSet::new(),
+            failed: HashSet::new(),
+            request_tx,
+            result_rx,
+            options,
+        }
+    }
+
+    /// Changes how thumbnails are sampled. Cached ones are regenerated with the new options.
+    pub fn set_options(&mut self, options: egui::TextureOptions) {
+        if self.options != options {
+            self.options = options;
+            self.textures.clear();
+        }
+    }
+
+    /// Returns the thumbnail for `path`, queueing it for generation if missing.
+    pub fn get(&mut self, path: &Path) -> Option<&egui::TextureHandle> {
+        if !self.textures.contains_key(path) && !self.pending.contains(path) && !self.failed.contains(path) {
+            self.pending.insert(path.to_path_buf());
+            let _ = self.request_tx.send(path.to_path_buf());
+        }
+        self.textures.get(path)
+    }
+
+    /// Returns the thumbnail for `path` if it is cached, without queueing it.
+    pub fn cached(&self, path: &Path) -> Option<&egui::TextureHandle> {
+        self.textures.get(path)
+    }
+
+    /// Uploads finished thumbnails. Returns true if any arrived, so callers can repaint.
+    pub fn poll(&mut self, ctx: &egui::Context) -> bool {
+        let mut received = false;
+        while let Ok((path, thumb)) = self.result_rx.try_recv() {
+            self.pending.remove(&path);
+            match thumb {
+                Some(color_image) => {
+                    let name = format!("thumb:{}", path.display());
+                    let texture = ctx.load_texture(name, color_image, self.options);
+                    self.textures.insert(path, texture);
+                }
+                None => {
+                    self.failed.insert(path);
+                }
+            }
+            received = true;
+        }
+        received
+    }
+
+    /// True while thumbnails are still being generated.
+    pub fn is_loading(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Forgets the cached thumbnail for `path`, e.g. after the file was rewritten.
+    pub fn invalidate(&mut self, path: &Path) {
+        self.textures.remove(path);
+        self.failed.remove(path);
+    }
+
+    /// Drops cached thumbnails for which `keep` returns false, bounding memory use.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Path) -> bool) {
+        self.textures.retain(|path, _| keep(path));
+    }
+}
+
+/// Downscales `img` so its longest edge is `THUMBNAIL_SIZE`, preserving aspect ratio.
+pub fn make_thumbnail(img: &image::DynamicImage) -> image::DynamicImage {
+    img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+}
+
+fn to_color_image(img: &image::DynamicImage) -> egui::ColorImage {
+    let rgba = img.to_rgba8();
+    let size = [rgba.width() as usize, rgba.height() as usize];
+    egui::ColorImage::from_rgba_unmultiplied(size, rgba.as_raw())
+}
+
+/// Largest rect with the aspect ratio of `size` that fits centered in `cell` minus `padding`.
+pub fn fit_in_cell(cell: egui::Rect, size: egui::Vec2, padding: f32) -> egui::Rect {
+    let inner = cell.shrink(padding);
+    if size.x <= 0.0 || size.y <= 0.0 || !inner.is_positive() {
+        return egui::Rect::from_center_size(cell.center(), egui::Vec2::ZERO);
+    }
+    let scale = (inner.width() / size.x).min(inner.height() / size.y);
+    egui::Rect::from_center_size(inner.center(), size * scale)
+}
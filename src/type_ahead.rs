@@ -0,0 +1,117 @@
+//! Jumping to an image by typing the start of its name.
+//!
+//! Single letters are shortcuts in the viewer, so type-ahead starts with `/`,
+//! like searching in less or vim. While it is on, typed keys go into the search
+//! instead of reaching the shortcuts. It ends after a pause, or with Enter or
+//! Escape.
+
+use eframe::egui;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Pause after which the search ends.
+const IDLE: Duration = Duration::from_millis(1500);
+
+pub struct TypeAhead {
+    prefix: String,
+    last_input: Instant,
+    /// Whether the last prefix matched an image.
+    found: bool,
+}
+
+/// What the typing in one frame did.
+#[derive(PartialEq, Debug)]
+pub enum TypeAheadInput {
+    None,
+    Changed,
+    Done,
+}
+
+impl TypeAhead {
+    /// Starts a search if `/` was typed this frame, taking its events.
+    pub fn start(ctx: &egui::Context) -> Option<Self> {
+        // Go by the typed text; some layouts need Shift or AltGr for the slash
+        let started = ctx.input_mut(|i| {
+            let typed = i.events.iter().any(|e| matches!(e, egui::Event::Text(text) if text == "/"));
+            if typed {
+                i.events.retain(|e| {
+                    !matches!(e, egui::Event::Text(text) if text == "/")
+                        && !matches!(e, egui::Event::Key { key: egui::Key::Slash, .. })
+                });
+            }
+            typed
+        });
+        if !started {
+            return None;
+        }
+        Some(Self { prefix: String::new(), last_input: Instant::now(), found: true })
+    }
+
+    /// Takes this frame's typed text and unmodified key presses, so shortcuts
+    /// don't see them.
+    pub fn take_input(&mut self, ctx: &egui::Context) -> TypeAheadInput {
+        let mut result = TypeAheadInput::None;
+        ctx.input_mut(|i| {
+            i.events.retain(|e| match e {
+                egui::Event::Text(text) => {
+                    self.prefix.push_str(text);
+                    result = TypeAheadInput::Changed;
+                    false
+                }
+                egui::Event::Key { key, pressed, modifiers, .. } if !modifiers.command && !modifiers.alt => {
+                    if *pressed {
+                        match key {
+                            egui::Key::Backspace if self.prefix.pop().is_some() => result = TypeAheadInput::Changed,
+                            egui::Key::Enter | egui::Key::Escape => result = TypeAheadInput::Done,
+                            _ => {}
+                        }
+                    }
+                    false
+                }
+                _ => true,
+            });
+        });
+        if result != TypeAheadInput::None {
+            self.last_input = Instant::now();
+        }
+        if self.last_input.elapsed() >= IDLE {
+            return TypeAheadInput::Done;
+        }
+        ctx.request_repaint_after(IDLE.saturating_sub(self.last_input.elapsed()));
+        result
+    }
+
+    /// Index of the first of `paths` whose file name starts with the typed
+    /// prefix, ignoring case. Remembers whether there was one for `show`.
+    pub fn find(&mut self, paths: &[PathBuf]) -> Option<usize> {
+        if self.prefix.is_empty() {
+            self.found = true;
+            return None;
+        }
+        let prefix = self.prefix.to_lowercase();
+        let found = paths.iter().position(|path| {
+            path.file_name().is_some_and(|name| name.to_string_lossy().to_lowercase().starts_with(&prefix))
+        });
+        self.found = found.is_some();
+        found
+    }
+
+    /// Shows the typed prefix at the bottom of the window, `bottom` above the
+    /// lowest point it can use.
+    pub fn show(&self, ctx: &egui::Context, bottom: f32) {
+        egui::Area::new(egui::Id::new("type_ahead"))
+            .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -40.0 - bottom))
+            .order(egui::Order::Foreground)
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::none()
+                    .fill(egui::Color32::from_black_alpha(180))
+                    .rounding(6.0)
+                    .inner_margin(6.0)
+                    .show(ui, |ui| {
+                        let color = if self.found { egui::Color32::WHITE } else { egui::Color32::from_rgb(255, 120, 120) };
+                        ui.label(egui::RichText::new(format!("/{}", self.prefix)).monospace().color(color));
+                    });
+            });
+    }
+}
@@ -0,0 +1,5350 @@
+//! The viewer: one image at a time with zoom, drawing and editing, plus the
+//! gallery and strip browse modes.
+
+use eframe::egui;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::adjust::{AdjustAction, AdjustPanel};
+use crate::alpha;
+use crate::animation::{AnimationPlayer, LoopCount};
+use crate::batch::BatchJob;
+use crate::blur::{self, BlurPicker, BlurSettings};
+use crate::compare::{CompareAction, ComparePanel};
+use crate::config::{self, Config, HudCorner, IdleAction, OpenZoom, WindowMode};
+use crate::details::{self, ImageDetails};
+use crate::dialog;
+use crate::dir_scan::{self, DirScan};
+use crate::embedded_drawings;
+use crate::export;
+use crate::file_hash::{self, FileDigest, FileHash};
+use crate::filmstrip::{self, Filmstrip};
+use crate::fonts;
+use crate::gallery::{Gallery, GalleryAction};
+use crate::idle::IdleWatch;
+use crate::image_cache::ImageCache;
+use crate::locale::{self, Locale};
+use crate::navigation;
+use crate::orientation::{self, Transform};
+use crate::perf::{PerfStats, UndoDepth};
+use crate::resize::{ResizeAction, ResizeDialog};
+use crate::session::Session;
+use crate::share_logic::{ShareManager, ShareEvent, SendQueue, SendState, peer_display_names};
+use crate::strip::StripView;
+use crate::thumbnails::{self, ThumbnailCache};
+use crate::type_ahead::{TypeAhead, TypeAheadInput};
+use crate::IMAGE_EXTENSIONS;
+
+// Clamp window size to fit comfortably on screen (prevents Hyprland from tiling)
+// Uses 80% of a 2560x1440 screen as max: 2048x1152
+const MAX_WINDOW_WIDTH: f32 = 2048.0;
+const MAX_WINDOW_HEIGHT: f32 = 1152.0;
+pub const MIN_WINDOW_WIDTH: f32 = 200.0;
+pub const MIN_WINDOW_HEIGHT: f32 = 150.0;
+
+// Allowed zoom range: 5% to 5000%
+const MIN_ZOOM: f32 = 0.05;
+const MAX_ZOOM: f32 = 50.0;
+// Zoom factor of one scroll notch or +/- press
+const ZOOM_STEP: f32 = 1.15;
+
+// Images whose longest edge is below this open magnified so they stay visible
+const MIN_DISPLAY_EDGE: f32 = 64.0;
+
+// Loupe size on screen, and the most image pixels it samples either side of the cursor
+const LOUPE_RADIUS: f32 = 80.0;
+const LOUPE_MAX_SPAN: f32 = 256.0;
+
+// Corner info overlay: gap to the window edges, and the top bar's height it stays below
+const HUD_MARGIN: f32 = 10.0;
+const HUD_TOP_CLEARANCE: f32 = 40.0;
+
+// With scroll_pages, how far to scroll on past an edge before the image changes,
+// and the pause after which that scrolling starts over
+const EDGE_SCROLL_RESISTANCE: f32 = 150.0;
+const EDGE_SCROLL_PAUSE: Duration = Duration::from_millis(500);
+
+// Steps through the folder closer together than SCRUB_GAP, as from a held arrow
+// key, only move the position; the image is loaded once they stop for SCRUB_SETTLE
+const SCRUB_GAP: Duration = Duration::from_millis(100);
+const SCRUB_SETTLE: Duration = Duration::from_millis(150);
+
+/// Window size for an image of `width` x `height`, scaled down to fit comfortably on screen.
+pub fn clamp_to_screen(width: f32, height: f32) -> [f32; 2] {
+    // Zero, negative or NaN dimensions would produce infinite/NaN scales
+    if !(width > 0.0 && height > 0.0) {
+        return [MIN_WINDOW_WIDTH, MIN_WINDOW_HEIGHT];
+    }
+    let scale = (MAX_WINDOW_WIDTH / width).min(MAX_WINDOW_HEIGHT / height).min(1.0);
+    [(width * scale).max(MIN_WINDOW_WIDTH), (height * scale).max(MIN_WINDOW_HEIGHT)]
+}
+
+/// Zoom at which an image edge of `image_len` pixels spans `available` screen pixels.
+fn fit_axis_zoom(available: f32, image_len: f32) -> f32 {
+    if !(image_len > 0.0 && available > 0.0) {
+        return 1.0;
+    }
+    (available / image_len).clamp(MIN_ZOOM, MAX_ZOOM)
+}
+
+/// Parses a zoom entry like "300%", "300" or "1.5x" into a zoom factor. Numbers
+/// are read with the separators of `locale`.
+fn parse_zoom_entry(text: &str, locale: &Locale) -> Option<f32> {
+    let text = locale.normalize_number(text);
+    let text = text.trim();
+    let zoom = if let Some(factor) = text.strip_suffix(['x', 'X']) {
+        factor.trim().parse::<f32>().ok()?
+    } else {
+        text.trim_end_matches('%').trim().parse::<f32>().ok()? / 100.0
+    };
+    (zoom.is_finite() && zoom > 0.0).then_some(zoom)
+}
+
+/// The image on the system clipboard.
+fn clipboard_image() -> Result<image::RgbaImage, String> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_image())
+        .map_err(|e| e.to_string())
+        .and_then(|data| {
+            image::RgbaImage::from_raw(data.width as u32, data.height as u32, data.bytes.into_owned())
+                .ok_or_else(|| "Clipboard image has an unexpected size".to_string())
+        })
+}
+
+/// Pan offset after scaling the zoom by `zoom_ratio` so the image point under
+/// `anchor` stays put. Both `offset` and `anchor` are relative to the viewport
+/// center; without an anchor the zoom is centered on the viewport.
+fn zoom_anchor_offset(offset: egui::Vec2, zoom_ratio: f32, anchor: Option<egui::Vec2>) -> egui::Vec2 {
+    if !zoom_ratio.is_finite() || zoom_ratio <= 0.0 {
+        return offset;
+    }
+    let anchor = anchor.unwrap_or(egui::Vec2::ZERO);
+    anchor - (anchor - offset) * zoom_ratio
+}
+
+/// Where image point `p` ends up when `transform` is applied to an image of `size`.
+fn transform_point(transform: Transform, size: egui::Vec2, p: egui::Pos2) -> egui::Pos2 {
+    match transform {
+        Transform::RotateRight => egui::pos2(size.y - p.y, p.x),
+        Transform::RotateLeft => egui::pos2(p.y, size.x - p.x),
+        Transform::FlipHorizontal => egui::pos2(size.x - p.x, p.y),
+        Transform::FlipVertical => egui::pos2(p.x, size.y - p.y),
+    }
+}
+
+/// Sets the pixels of `canvas` whose centers `inside` says are covered, within
+/// `bounds`, to `color`.
+fn burn_pixels(canvas: &mut image::RgbaImage, bounds: egui::Rect, color: image::Rgba<u8>, inside: impl Fn(egui::Pos2) -> bool) {
+    let (width, height) = (canvas.width() as f32, canvas.height() as f32);
+    let (x0, x1) = (bounds.min.x.floor().clamp(0.0, width) as u32, bounds.max.x.ceil().clamp(0.0, width) as u32);
+    let (y0, y1) = (bounds.min.y.floor().clamp(0.0, height) as u32, bounds.max.y.ceil().clamp(0.0, height) as u32);
+    for y in y0..y1 {
+        for x in x0..x1 {
+            if inside(egui::pos2(x as f32 + 0.5, y as f32 + 0.5)) {
+                canvas.put_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+/// Burns in the outline of the rectangle spanned by `a` and `b`, `width` wide
+/// and centered on the edge as egui strokes it. A zero side gives a filled bar.
+fn burn_rect_outline(canvas: &mut image::RgbaImage, a: egui::Pos2, b: egui::Pos2, width: f32, color: image::Rgba<u8>) {
+    let half = (width / 2.0).max(0.5);
+    let rect = egui::Rect::from_two_pos(a, b);
+    let (outer, inner) = (rect.expand(half), rect.shrink(half));
+    burn_pixels(canvas, outer, color, |p| outer.contains(p) && !inner.contains(p));
+}
+
+/// Burns in the outline of the circle of `radius` around `center`, `width` wide
+/// and centered on the radius as egui strokes it.
+fn burn_circle_outline(canvas: &mut image::RgbaImage, center: egui::Pos2, radius: f32, width: f32, color: image::Rgba<u8>) {
+    let half = (width / 2.0).max(0.5);
+    let bounds = egui::Rect::from_center_size(center, egui::Vec2::splat((radius + half) * 2.0));
+    burn_pixels(canvas, bounds, color, |p| (p.distance(center) - radius).abs() <= half);
+}
+
+/// Distance from `p` to the segment `a`-`b`.
+fn distance_to_segment(p: egui::Pos2, a: egui::Pos2, b: egui::Pos2) -> f32 {
+    let ab = b - a;
+    let len_sq = ab.length_sq();
+    if len_sq == 0.0 {
+        return p.distance(a);
+    }
+    let t = ((p - a).dot(ab) / len_sq).clamp(0.0, 1.0);
+    p.distance(a + ab * t)
+}
+
+/// Where to move the selected drawing in the stacking order.
+#[derive(Clone, Copy)]
+enum Reorder {
+    Front,
+    Forward,
+    Backward,
+    Back,
+}
+
+impl Reorder {
+    fn label(self) -> &'static str {
+        match self {
+            Reorder::Front => "Bring to Front",
+            Reorder::Forward => "Bring Forward",
+            Reorder::Backward => "Send Backward",
+            Reorder::Back => "Send to Back",
+        }
+    }
+}
+
+/// Frameless toolbar button showing `icon`, or `caption` as text if the icon fails
+/// to load. The caption is also the label reported to screen readers.
+fn icon_button(ui: &mut egui::Ui, icon: egui::ImageSource<'static>, caption: &str, tint: egui::Color32, size: egui::Vec2) -> egui::Response {
+    let image = egui::Image::new(icon).tint(tint);
+    let button = if image.load_for_size(ui.ctx(), size).is_err() {
+        egui::Button::new(egui::RichText::new(caption).color(tint))
+    } else {
+        egui::Button::image(image)
+    };
+    let response = ui.add(button.frame(false).min_size(size));
+    response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, ui.is_enabled(), caption));
+    response
+}
+
+/// Largest zoom showing all of an image of `size` in `available`.
+fn fit_zoom(available: egui::Vec2, size: egui::Vec2) -> f32 {
+    fit_axis_zoom(available.x, size.x).min(fit_axis_zoom(available.y, size.y))
+}
+
+/// Zoom to open an image at: native size, except tiny images which are magnified.
+fn initial_zoom(width: f32, height: f32) -> f32 {
+    let longest = width.max(height);
+    if longest.is_nan() || longest <= 0.0 {
+        return 1.0;
+    }
+    (MIN_DISPLAY_EDGE / longest).clamp(1.0, MAX_ZOOM)
+}
+
+/// `left` rounded to whole units for a time-left estimate, e.g. "40 s" or "3 min".
+fn format_time_left(left: Duration) -> String {
+    let secs = left.as_secs_f64().ceil() as u64;
+    match secs {
+        0..60 => format!("{} s", secs),
+        60..3600 => format!("{} min", secs.div_ceil(60)),
+        _ => format!("{} h {} min", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// Explains why `path` couldn't be decoded, telling formats this build can't
+/// read apart from damaged files.
+fn load_error_message(path: &Path, err: &image::ImageError) -> String {
+    use image::error::{ImageError, UnsupportedErrorKind};
+    use std::io::Read;
+
+    // What the file holds, from its first bytes; the extension may be wrong
+    let mut header = Vec::new();
+    let content = std::fs::File::open(path)
+        .and_then(|file| file.take(64).read_to_end(&mut header))
+        .ok()
+        .and_then(|_| image::guess_format(&header).ok());
+    let by_extension = image::ImageFormat::from_path(path).ok();
+    let not_built_in = |format: image::ImageFormat| {
+        let (name, feature) = format_name_and_feature(format);
+        format!("{} support is not built in. Rebuild with the \"{}\" feature of the image crate to open it.", name, feature)
+    };
+
+    match err {
+        ImageError::Unsupported(e) if matches!(e.kind(), UnsupportedErrorKind::Format(_)) => {
+            match content.or(by_extension) {
+                Some(format) if !format.reading_enabled() => not_built_in(format),
+                Some(_) => format!("Failed to load: {}", e),
+                None => "Not a recognized image format".to_string(),
+            }
+        }
+        ImageError::Decoding(_) => match (content, by_extension) {
+            (Some(actual), _) if !actual.reading_enabled() => not_built_in(actual),
+            (Some(actual), Some(claimed)) if actual != claimed => {
+                format!(
+                    "The file holds {} data but is named as {}. Rename it to open it.",
+                    format_name_and_feature(actual).0,
+                    format_name_and_feature(claimed).0,
+                )
+            }
+            _ => format!("File is corrupt or truncated: {}", err),
+        },
+        ImageError::IoError(io) if io.kind() == std::io::ErrorKind::UnexpectedEof => {
+            format!("File is corrupt or truncated: {}", err)
+        }
+        ImageError::Limits(_) => format!("Image is too large to decode: {}", err),
+        _ => format!("Failed to load: {}", err),
+    }
+}
+
+/// Display name of `format` and the `image` crate feature that decodes it.
+fn format_name_and_feature(format: image::ImageFormat) -> (&'static str, &'static str) {
+    use image::ImageFormat as F;
+    match format {
+        F::Png => ("PNG", "png"),
+        F::Jpeg => ("JPEG", "jpeg"),
+        F::Gif => ("GIF", "gif"),
+        F::WebP => ("WebP", "webp"),
+        F::Bmp => ("BMP", "bmp"),
+        F::Ico => ("ICO", "ico"),
+        F::Tiff => ("TIFF", "tiff"),
+        F::Tga => ("TGA", "tga"),
+        F::Avif => ("AVIF", "avif"),
+        F::Dds => ("DDS", "dds"),
+        F::Hdr => ("Radiance HDR", "hdr"),
+        F::OpenExr => ("OpenEXR", "exr"),
+        F::Pnm => ("PNM", "pnm"),
+        F::Qoi => ("QOI", "qoi"),
+        F::Farbfeld => ("Farbfeld", "ff"),
+        _ => ("This format", "matching"),
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum DrawingTool {
+    Pencil,
+    Shape,
+    Text,
+    Select, // Pick an existing object to reorder it
+}
+
+impl DrawingTool {
+    pub const ALL: [DrawingTool; 4] = [Self::Pencil, Self::Shape, Self::Text, Self::Select];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Pencil => "✏ Pencil",
+            Self::Shape => "⬜ Shape",
+            Self::Text => "T Text",
+            Self::Select => "↖ Select",
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum ShapeType {
+    Rectangle,
+    Circle,
+    Line,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum FontFamily {
+    Proportional,
+    Monospace,
+}
+
+#[derive(Clone)]
+pub(crate) struct DrawingObject {
+    tool: DrawingTool,
+    points: Vec<egui::Pos2>, // Image-space coordinates
+    color: egui::Color32,
+    size: f32,
+    // For shapes/text: start and end points or specific fields
+    // For now, let's keep it simple:
+    // Pencil: list of points
+    // Shape: points[0] = start, points[1] = end
+    // Text: points[0] = position
+    shape_type: Option<ShapeType>,
+    text: Option<String>,
+    font_family: Option<FontFamily>,
+    font_bold: bool,
+    // Pencil only: per-point widths for tapered strokes, empty for constant `size`
+    widths: Vec<f32>,
+}
+
+impl DrawingObject {
+    /// Stroke width of the pencil segment from point `i` to `i + 1`.
+    fn segment_width(&self, i: usize) -> f32 {
+        match (self.widths.get(i), self.widths.get(i + 1)) {
+            (Some(a), Some(b)) => (a + b) / 2.0,
+            _ => self.size,
+        }
+    }
+
+    /// What the object is, for the history panel.
+    fn label(&self) -> String {
+        match (self.tool, self.shape_type) {
+            (DrawingTool::Pencil, _) => "Pencil stroke".to_string(),
+            (DrawingTool::Shape, Some(ShapeType::Rectangle)) => "Rectangle".to_string(),
+            (DrawingTool::Shape, Some(ShapeType::Circle)) => "Circle".to_string(),
+            (DrawingTool::Shape, _) => "Line".to_string(),
+            (DrawingTool::Text, _) => {
+                let text = self.text.as_deref().unwrap_or_default();
+                let mut short: String = text.chars().take(HISTORY_TEXT_CHARS).collect();
+                if short.len() < text.len() {
+                    short.push('…');
+                }
+                format!("Text \u{201c}{}\u{201d}", short)
+            }
+            (DrawingTool::Select, _) => "Drawing".to_string(),
+        }
+    }
+
+    /// Image-space box around the object, used for selection.
+    fn bounds(&self, ctx: &egui::Context) -> egui::Rect {
+        let pad = self.size / 2.0;
+        match (self.tool, self.shape_type) {
+            (DrawingTool::Text, _) => {
+                let Some(&pos) = self.points.first() else { return egui::Rect::NOTHING };
+                let family = match self.font_family.unwrap_or(FontFamily::Proportional) {
+                    FontFamily::Proportional => egui::FontFamily::Proportional,
+                    FontFamily::Monospace => egui::FontFamily::Monospace,
+                };
+                let text = self.text.clone().unwrap_or_default();
+                let size = ctx.fonts(|f| f.layout_no_wrap(text, egui::FontId::new(self.size, family), self.color).size());
+                egui::Rect::from_min_size(pos, size)
+            }
+            // Circles are drawn around their first point
+            (DrawingTool::Shape, Some(ShapeType::Circle)) if self.points.len() >= 2 => {
+                let radius = self.points[0].distance(self.points[1]);
+                egui::Rect::from_center_size(self.points[0], egui::Vec2::splat(radius * 2.0)).expand(pad)
+            }
+            _ => egui::Rect::from_points(&self.points).expand(pad),
+        }
+    }
+
+    /// True if `p` (image space) is on the object, within `tolerance` image pixels.
+    fn hit_test(&self, p: egui::Pos2, tolerance: f32, ctx: &egui::Context) -> bool {
+        let on_segments = |points: &[egui::Pos2], width: &dyn Fn(usize) -> f32| {
+            if points.len() == 1 {
+                return points[0].distance(p) <= width(0) / 2.0 + tolerance;
+            }
+            points.windows(2).enumerate()
+                .any(|(i, seg)| distance_to_segment(p, seg[0], seg[1]) <= width(i) / 2.0 + tolerance)
+        };
+        match (self.tool, self.shape_type) {
+            (DrawingTool::Pencil, _) => on_segments(&self.points, &|i| self.segment_width(i)),
+            (DrawingTool::Shape, Some(ShapeType::Line)) => on_segments(&self.points, &|_| self.size),
+            // Filled-in hit area so hollow shapes and text are easy to grab
+            _ => self.bounds(ctx).expand(tolerance).contains(p),
+        }
+    }
+
+    /// Resizes the object by `factor` around the image origin, e.g. to follow a
+    /// resize of the base image. Line widths and text follow the mean of the axes.
+    fn scale(&mut self, factor: egui::Vec2) {
+        self.map_points(|p| (p.to_vec2() * factor).to_pos2());
+        let mean = (factor.x + factor.y) / 2.0;
+        self.size *= mean;
+        for width in &mut self.widths {
+            *width *= mean;
+        }
+    }
+
+    /// Moves every point through `f`, e.g. to follow a rotation of the base image.
+    fn map_points(&mut self, f: impl Fn(egui::Pos2) -> egui::Pos2) {
+        for point in &mut self.points {
+            *point = f(*point);
+        }
+    }
+
+    /// Moves the object along with the pixels of a `size` image under `transform`.
+    fn transform(&mut self, transform: Transform, size: egui::Vec2) {
+        self.map_points(|p| transform_point(transform, size, p));
+    }
+}
+
+// Pointer speed (screen px/s) at which a tapered stroke reaches its thinnest
+const TAPER_SPEED: f32 = 3000.0;
+// Thinnest a tapered stroke gets, relative to the brush size
+const TAPER_MIN_FACTOR: f32 = 0.3;
+// Screen px within which placed text snaps to a center or thirds line
+const TEXT_SNAP_DISTANCE: f32 = 8.0;
+// Guide positions as fractions of the image width and height
+const TEXT_GUIDES: [f32; 3] = [1.0 / 3.0, 0.5, 2.0 / 3.0];
+// Seconds the outline of an edited area takes to fade out
+const EDIT_HIGHLIGHT_SECS: f32 = 1.5;
+// Largest side of the flattened preview in the lossy conversion dialog, in points
+const FLATTEN_PREVIEW_SIZE: f32 = 160.0;
+// Characters of a text drawing quoted in its history entry
+const HISTORY_TEXT_CHARS: usize = 24;
+
+/// A `DrawingObject` as embedded in a saved PNG, with egui's types spelled out.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct StoredDrawing {
+    tool: DrawingTool,
+    points: Vec<[f32; 2]>,
+    /// Unmultiplied sRGBA.
+    color: [u8; 4],
+    size: f32,
+    shape_type: Option<ShapeType>,
+    text: Option<String>,
+    font_family: Option<FontFamily>,
+    #[serde(default)]
+    font_bold: bool,
+    #[serde(default)]
+    widths: Vec<f32>,
+}
+
+impl From<&DrawingObject> for StoredDrawing {
+    fn from(d: &DrawingObject) -> Self {
+        Self {
+            tool: d.tool,
+            points: d.points.iter().map(|p| [p.x, p.y]).collect(),
+            color: d.color.to_srgba_unmultiplied(),
+            size: d.size,
+            shape_type: d.shape_type,
+            text: d.text.clone(),
+            font_family: d.font_family,
+            font_bold: d.font_bold,
+            widths: d.widths.clone(),
+        }
+    }
+}
+
+impl From<StoredDrawing> for DrawingObject {
+    fn from(d: StoredDrawing) -> Self {
+        let [r, g, b, a] = d.color;
+        Self {
+            tool: d.tool,
+            points: d.points.into_iter().map(|[x, y]| egui::pos2(x, y)).collect(),
+            color: egui::Color32::from_rgba_unmultiplied(r, g, b, a),
+            size: d.size,
+            shape_type: d.shape_type,
+            text: d.text,
+            font_family: d.font_family,
+            font_bold: d.font_bold,
+            widths: d.widths,
+        }
+    }
+}
+
+/// `pos` moved onto the nearest center or thirds line of an image of `size` on
+/// each axis within `tolerance`, plus the guide lines it landed on (x, y).
+fn snap_to_guides(pos: egui::Pos2, size: egui::Vec2, tolerance: f32) -> (egui::Pos2, [Option<f32>; 2]) {
+    let snap = |value: f32, extent: f32| {
+        TEXT_GUIDES.iter()
+            .map(|fraction| fraction * extent)
+            .filter(|guide| (guide - value).abs() <= tolerance)
+            .min_by(|a, b| (a - value).abs().total_cmp(&(b - value).abs()))
+    };
+    let guides = [snap(pos.x, size.x), snap(pos.y, size.y)];
+    (egui::pos2(guides[0].unwrap_or(pos.x), guides[1].unwrap_or(pos.y)), guides)
+}
+
+/// Bounding box of the pixels that differ between `old` and `new`, in image
+/// pixels. The whole of `new` when the size or pixel format changed.
+fn changed_region(old: &image::DynamicImage, new: &image::DynamicImage) -> Option<egui::Rect> {
+    let (w, h) = (new.width() as usize, new.height() as usize);
+    let whole = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(w as f32, h as f32));
+    if (old.width(), old.height()) != (new.width(), new.height()) || old.color() != new.color() {
+        return Some(whole);
+    }
+    let bytes_per_pixel = new.color().bytes_per_pixel() as usize;
+    let row_len = w * bytes_per_pixel;
+    let (mut min, mut max) = ([usize::MAX; 2], [0; 2]);
+    for (y, (a, b)) in old.as_bytes().chunks_exact(row_len).zip(new.as_bytes().chunks_exact(row_len)).enumerate() {
+        if a == b {
+            continue;
+        }
+        let first = a.iter().zip(b).position(|(a, b)| a != b).unwrap_or(0) / bytes_per_pixel;
+        let last = (row_len - 1 - a.iter().zip(b).rev().position(|(a, b)| a != b).unwrap_or(0)) / bytes_per_pixel;
+        min = [min[0].min(first), min[1].min(y)];
+        max = [max[0].max(last + 1), y + 1];
+    }
+    (min[1] != usize::MAX).then(|| {
+        egui::Rect::from_min_max(egui::pos2(min[0] as f32, min[1] as f32), egui::pos2(max[0] as f32, max[1] as f32))
+    })
+}
+
+/// Width factor for a tapered pencil point: tablet/touch pressure when the
+/// platform reports it, otherwise slower movement draws thicker lines.
+fn taper_factor(ctx: &egui::Context) -> f32 {
+    let pressure = ctx.input(|i| {
+        i.events.iter().rev().find_map(|e| match e {
+            egui::Event::Touch { force: Some(force), .. } => Some(*force),
+            _ => None,
+        })
+    });
+    let factor = match pressure {
+        Some(force) => force,
+        None => 1.0 - ctx.input(|i| i.pointer.velocity().length()) / TAPER_SPEED,
+    };
+    factor.clamp(TAPER_MIN_FACTOR, 1.0)
+}
+
+/// A destructive action waiting on the user. Whether one is asked at all is
+/// decided by the `confirm_*` settings in `Config`.
+enum Confirmation {
+    /// Closing the window would drop unsaved drawings.
+    CloseUnsaved,
+    /// Showing `path`, where the strip was left, would drop unsaved edits.
+    LeaveUnsaved { path: PathBuf },
+    /// Converting would replace the existing file at `path`.
+    Overwrite { path: PathBuf },
+    /// Writing `action` keeps only the first frame of the animation on screen.
+    FlattenAnimation { action: FlattenAction, dont_ask: bool },
+    /// Converting to `path` can't keep everything; `losses` says what goes.
+    LossyConvert { path: PathBuf, losses: Vec<String>, loses_alpha: bool },
+    /// Reverting would drop unsaved edits.
+    Revert,
+    /// Deleting moves the current file to the trash.
+    Delete,
+    /// Saving can't write over the file; `close` closes the window after saving elsewhere.
+    ReadOnly { block: export::WriteBlock, close: bool },
+}
+
+/// Windows that close on Escape, most in front first.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Dialog {
+    Confirmation,
+    Text,
+    Resize,
+    Share,
+    Settings,
+    History,
+    Info,
+}
+
+impl Dialog {
+    /// Whether the window holds keyboard focus and blocks the view behind it.
+    fn is_modal(self) -> bool {
+        matches!(self, Self::Confirmation | Self::Text)
+    }
+}
+
+/// One step of the undo history. `label` names it in the history panel.
+enum Edit {
+    /// A drawing was added to the end of `drawings`.
+    Drawing { label: String },
+    /// The pixels changed; holds the image and animation frames from before,
+    /// and the drawings too if the edit moved them.
+    Image { label: &'static str, image: image::DynamicImage, animation: Option<AnimationPlayer>, drawings: Option<Vec<DrawingObject>> },
+    /// The image and its drawings were rotated or flipped.
+    Transform(Transform),
+    /// The drawing at `from` moved to `to` in the stacking order.
+    Reorder { label: &'static str, from: usize, to: usize },
+}
+
+impl Edit {
+    fn label(&self) -> &str {
+        match self {
+            Edit::Drawing { label } => label,
+            Edit::Image { label, .. } => label,
+            Edit::Transform(transform) => transform.label(),
+            Edit::Reorder { label, .. } => label,
+        }
+    }
+}
+
+/// An undone step, kept for redo until the next edit. Undoing an `Edit::Image`
+/// swaps its snapshot in, so it then holds the image from after the edit.
+struct Undone {
+    edit: Edit,
+    /// The drawing an undone `Edit::Drawing` took away.
+    drawing: Option<DrawingObject>,
+}
+
+/// The steps that can be undone and redone, and which of them the file on
+/// disk matches.
+struct History {
+    /// Undo steps, newest last.
+    edits: Vec<Edit>,
+    /// Redo steps, most recently undone last.
+    undone: Vec<Undone>,
+    /// Number of undo steps at which the image matches the file, or None once
+    /// undo and redo can't get back to it.
+    saved_at: Option<usize>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self { edits: Vec::new(), undone: Vec::new(), saved_at: Some(0) }
+    }
+}
+
+impl History {
+    /// Adds `edit`, which drops the redo steps, keeping within the limits in `config`.
+    fn record(&mut self, edit: Edit, config: &Config) {
+        if self.saved_at > Some(self.edits.len()) {
+            self.saved_at = None;
+        }
+        self.undone.clear();
+        self.edits.push(edit);
+        self.trim(config);
+    }
+
+    /// Drops the oldest steps of each kind beyond the undo limits in `config`.
+    /// Drawings whose steps are dropped stay, and undo still removes them once the
+    /// history runs out. Rotations and flips cost nothing and are all kept.
+    fn trim(&mut self, config: &Config) {
+        let images = self.edits.iter().filter(|edit| matches!(edit, Edit::Image { .. })).count();
+        let drawings = self.edits.iter().filter(|edit| matches!(edit, Edit::Drawing { .. })).count();
+        let mut excess_images = images.saturating_sub(config.undo_image_snapshots);
+        let mut excess_drawings = drawings.saturating_sub(config.undo_drawing_steps);
+        let mut kept = Vec::with_capacity(self.edits.len());
+        for edit in std::mem::take(&mut self.edits) {
+            let excess = match edit {
+                Edit::Image { .. } => &mut excess_images,
+                Edit::Drawing { .. } => &mut excess_drawings,
+                Edit::Transform(_) | Edit::Reorder { .. } => &mut 0,
+            };
+            if *excess == 0 {
+                kept.push(edit);
+                continue;
+            }
+            *excess -= 1;
+            // Undo now stops after this step, out of reach of the states up to it
+            self.saved_at = self.saved_at.filter(|&at| at > kept.len()).map(|at| at - 1);
+        }
+        self.edits = kept;
+    }
+
+    /// Whether the image is as in the file, going by the steps.
+    fn is_saved(&self) -> bool {
+        self.saved_at == Some(self.edits.len())
+    }
+
+    /// The rotations and flips since the state the file matches, if those are
+    /// all the steps since.
+    fn transforms_since_saved(&self) -> Option<Vec<Transform>> {
+        self.edits.get(self.saved_at?..)?.iter()
+            .map(|edit| match edit {
+                Edit::Transform(transform) => Some(*transform),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Notes that the file now matches the image.
+    fn mark_saved(&mut self) {
+        self.saved_at = Some(self.edits.len());
+    }
+
+    /// Notes a change that isn't a step of its own, so no step matches the file.
+    fn forget_saved(&mut self) {
+        self.saved_at = None;
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Checks a conversion passes before writing, in order. Confirming one resumes
+/// the conversion at the next.
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum ConvertCheck {
+    FlattenAnimation,
+    Losses,
+    Overwrite,
+}
+
+/// Write that triggered a `Confirmation::FlattenAnimation`.
+enum FlattenAction {
+    Save,
+    SaveAndClose,
+    Convert(PathBuf),
+}
+
+struct DrawingSettings {
+    tool: DrawingTool,
+    shape: ShapeType,
+    color: egui::Color32,
+    size: f32,
+    font_size: f32,
+    font_family: FontFamily,
+    font_bold: bool,
+    taper: bool, // Vary pencil width with pressure/speed
+}
+
+impl DrawingSettings {
+    /// The startup tool, color and size from the config.
+    fn from_config(config: &Config) -> Self {
+        let [r, g, b] = config.drawing_color;
+        Self {
+            tool: config.drawing_tool,
+            color: egui::Color32::from_rgb(r, g, b),
+            size: config.drawing_size.clamp(1.0, 50.0),
+            ..Self::default()
+        }
+    }
+
+    /// Grows or shrinks the size of the current tool by one step per scroll
+    /// notch, within the range of its slider.
+    fn scroll_size(&mut self, scroll_delta: f32) {
+        let factor = if scroll_delta > 0.0 { 1.15 } else { 1.0 / 1.15 };
+        let (size, min, max) = match self.tool {
+            DrawingTool::Pencil => (&mut self.size, 1.0, 50.0),
+            DrawingTool::Shape => (&mut self.size, 1.0, 20.0),
+            DrawingTool::Text => (&mut self.font_size, 10.0, 100.0),
+            DrawingTool::Select => return,
+        };
+        // Always move by at least one unit so small sizes don't get stuck
+        let scaled = *size * factor;
+        let stepped = if factor > 1.0 { scaled.max(*size + 1.0) } else { scaled.min(*size - 1.0) };
+        *size = stepped.round().clamp(min, max);
+    }
+}
+
+impl Default for DrawingSettings {
+    fn default() -> Self {
+        Self {
+            tool: DrawingTool::Pencil,
+            shape: ShapeType::Rectangle,
+            color: egui::Color32::RED,
+            size: 5.0,
+            font_size: 20.0,
+            font_family: FontFamily::Proportional,
+            font_bold: false,
+            taper: false,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ImageMetadata {
+    filename: String,
+    resolution: String, 
+    file_size: String,
+    format: String,
+    modified: String,
+    frame_count: Option<usize>,     // Animations only
+    loop_count: Option<LoopCount>,  // Animations only
+    sha256: Option<FileDigest>,     // Filled in while the expanded info panel is open
+    details: Option<ImageDetails>,  // Read when the info panel is first expanded
+    // From the EXIF data, for photos
+    camera_model: Option<String>,
+    taken: Option<String>,
+    shutter_speed: Option<String>,
+    aperture: Option<String>,
+    iso: Option<String>,
+    focal_length: Option<String>,
+}
+
+/// How an `ImageViewer` is hosted.
+#[derive(Default)]
+pub struct ViewerOptions {
+    /// Image to open right away.
+    pub initial_path: Option<PathBuf>,
+    /// Show opened images alone instead of listing their folders, so there is
+    /// nothing to step through but opening is instant in huge folders.
+    pub no_scan: bool,
+    /// How the host created the window. Floating and borderless windows are
+    /// resized to fit each image; resizable ones are left to the user.
+    pub window_mode: WindowMode,
+    /// Running inside another app's window. The viewer then leaves the window size,
+    /// quitting and close requests to the host.
+    pub embedded: bool,
+    /// Images to step through in this order, as read from a list file, instead
+    /// of the folder of the opened image. `initial_path` picks the one to start
+    /// at, otherwise the first is shown.
+    pub list: Vec<PathBuf>,
+    /// Photo frame: start with the slideshow and the overlays hidden, and go back
+    /// to it after `idle_minutes` without input. The host makes the window fullscreen.
+    pub frame: bool,
+}
+
+/// The image viewer. Runs as its own `eframe::App`, or inside another app by
+/// calling `show` from its `update`.
+pub struct ImageViewer {
+    embedded: bool,
+    no_scan: bool, // Single-image mode: opened images are never browsed with their folder
+    listed: bool, // `image_list` is from a list file, in its order, rather than a sorted folder listing
+    frame_mode: bool, // Photo frame: idle runs the slideshow, whatever `idle_action` says
+    idle: IdleWatch,
+    window_mode: WindowMode,
+
+    config: Config,
+
+    texture: Option<egui::TextureHandle>,
+    blurred_texture: Option<egui::TextureHandle>,
+    fading_blur: Option<(egui::TextureHandle, f32)>, // Previous image's blur and its remaining weight, fading out
+    blur_picker: Option<BlurPicker>, // Staged blur settings while the Settings window is open
+    edit_highlight: Option<(egui::Rect, f32)>, // Image-space area changed by the last pixel edit and its opacity, fading out
+    error_message: Option<String>,
+    
+    current_path: Option<PathBuf>,
+    image_list: Vec<PathBuf>,
+    current_index: usize,
+    thumbnails: ThumbnailCache,
+    decoded: ImageCache, // Recently shown and neighboring images, decoded
+
+    // Image Data
+    current_image: Option<image::DynamicImage>,
+    animation: Option<AnimationPlayer>,
+
+    // Transformation
+    zoom: f32,
+    target_zoom: f32,
+    offset: egui::Vec2,
+    target_offset: egui::Vec2,
+    last_frame_time: Instant,
+    
+    // UI State
+    strip_view: Option<StripView>, // Continuous vertical reading mode when Some
+    gallery: Option<Gallery>,      // Grid browse mode when Some
+    type_ahead: Option<TypeAhead>, // Jump by file name while typing after `/`
+    show_settings: bool,
+    show_history: bool, // Undo history panel
+    perf: PerfStats, // Load timings, shown with F12
+    session: Session, // Pasted images without a file
+    dir_scan: Option<DirScan>, // Folder listing in progress; `image_list` is empty until it lands
+    trashed_during_scan: Vec<PathBuf>, // Deleted before the listing landed, so it may still name them
+    zoom_entry: String,
+    locale: Locale, // Number and date conventions, from the system or `Config::locale`
+    focus_toolbar: bool, // Move keyboard focus into the top bar next frame (F6)
+    top_bar_opacity: f32,
+    is_drawing_mode: bool,
+    is_image_edited: bool,
+    converted_copy: Option<PathBuf>, // Converted since the last edit, so the unsaved edits are in this file
+    pending_confirmation: Option<Confirmation>,
+    flatten_preview: Option<(image::DynamicImage, egui::TextureHandle, [u8; 3])>, // Thumbnail, its flattened texture and the background used
+    drawing_settings: DrawingSettings,
+    
+    // Drawing Data
+    drawings: Vec<DrawingObject>,
+    current_stroke: Option<DrawingObject>,
+    selected_drawing: Option<usize>, // Index into `drawings` picked with the select tool
+    history: History, // Undo and redo steps
+    adjust_panel: Option<AdjustPanel>, // Color adjustment window when Some
+    resize_dialog: Option<ResizeDialog>, // Resize window when Some
+    compare_panel: Option<ComparePanel>, // Comparison with a clipboard image when Some
+    batch_job: Option<BatchJob>, // Rotation or flip of the files marked in the gallery
+    downsampled_from: Option<(u32, u32)>, // Original size when the image was shrunk to `max_image_dimension`
+    premultiplied_alpha: bool, // The decoded image still holds premultiplied color, straightened on upload
+    skip_size_limit: bool, // Decode the next image at full size regardless of `max_image_dimension`
+    show_loupe: bool, // Magnify the image under the cursor (L)
+    filmstrip: Option<Filmstrip>, // Thumbnails of the folder along the bottom (T)
+    brush_feedback_until: Option<Instant>, // Show the brush size at the cursor until then, after scrolling it
+    edge_scroll: Option<(f32, Instant)>, // Scrolling past the top (positive) or bottom edge, and when it last happened
+    last_step: Option<Instant>, // When next_image or prev_image last moved through the folder
+    scrub_since: Option<Instant>, // Stepping fast; `current_index` is ahead of the loaded image until the steps stop
+    loupe_texture: Option<egui::TextureHandle>,
+    
+    // Text Entry State
+    pending_text_pos: Option<egui::Pos2>, // Image Space
+    text_entry_string: String,
+
+    // Metadata State
+    metadata: Option<ImageMetadata>,
+    file_hash: Option<FileHash>, // SHA-256 of the current file being computed
+    show_info_panel: bool,
+    escaped_dialog: Option<Dialog>, // The dialog Escape closes this frame
+    info_expanded: bool, // Info panel also shows EXIF, hash, resolution and histogram
+    
+    // Navigation Arrow State
+    left_arrow_opacity: f32,
+    right_arrow_opacity: f32,
+    
+    // Pending window resize (for Wayland compatibility)
+    pending_resize: Option<egui::Vec2>,
+    pending_resize_frame: u8,
+
+    // LocalSend Share State
+    share_manager: Option<ShareManager>,
+    show_share_modal: bool,
+    share_status: Option<String>,
+    send_queue: SendQueue,
+}
+
+impl ImageViewer {
+    pub fn new(ctx: &egui::Context, options: ViewerOptions) -> Self {
+        egui_extras::install_image_loaders(ctx);
+        fonts::install(ctx);
+        
+        let config = Config::load();
+        let locale = Locale::resolve(&config.locale);
+        let thumbnails = ThumbnailCache::new(config.thumbnail_texture_options());
+        let drawing_settings = DrawingSettings::from_config(&config);
+        let is_drawing_mode = config.start_in_drawing_mode;
+        let mut viewer = Self {
+            embedded: options.embedded,
+            no_scan: options.no_scan,
+            listed: false,
+            frame_mode: options.frame,
+            idle: IdleWatch::new(options.frame),
+            window_mode: options.window_mode,
+            config,
+
+            texture: None,
+            blurred_texture: None,
+            fading_blur: None,
+            blur_picker: None,
+            edit_highlight: None,
+            error_message: None,
+            current_path: None,
+            image_list: Vec::new(),
+            current_index: 0,
+            thumbnails,
+            decoded: ImageCache::new(),
+            
+            current_image: None,
+            animation: None,
+
+            zoom: 1.0,
+            target_zoom: 1.0,
+            offset: egui::Vec2::ZERO,
+            target_offset: egui::Vec2::ZERO,
+            last_frame_time: Instant::now(),
+            
+            strip_view: None,
+            gallery: None,
+            type_ahead: None,
+            show_settings: false,
+            show_history: false,
+            perf: PerfStats::default(),
+            session: Session::default(),
+            dir_scan: None,
+            trashed_during_scan: Vec::new(),
+            zoom_entry: String::new(),
+            locale,
+            focus_toolbar: false,
+            top_bar_opacity: 0.0,
+            is_drawing_mode,
+            is_image_edited: false,
+            converted_copy: None,
+            pending_confirmation: None,
+            flatten_preview: None,
+            drawing_settings,
+            
+            drawings: Vec::new(),
+            current_stroke: None,
+            selected_drawing: None,
+            history: History::default(),
+            adjust_panel: None,
+            resize_dialog: None,
+            compare_panel: None,
+            batch_job: None,
+            downsampled_from: None,
+            premultiplied_alpha: false,
+            skip_size_limit: false,
+            show_loupe: false,
+            filmstrip: None,
+            brush_feedback_until: None,
+            edge_scroll: None,
+            last_step: None,
+            scrub_since: None,
+            loupe_texture: None,
+            
+            pending_text_pos: None,
+            text_entry_string: String::new(),
+            metadata: None,
+            file_hash: None,
+            show_info_panel: false,
+            escaped_dialog: None,
+            info_expanded: false,
+            left_arrow_opacity: 0.0,
+            right_arrow_opacity: 0.0,
+            pending_resize: None,
+            pending_resize_frame: 0,
+            share_manager: None,
+            show_share_modal: false,
+            share_status: None,
+            send_queue: SendQueue::default(),
+        };
+
+        if !options.list.is_empty() {
+            viewer.open_list(ctx, options.list, options.initial_path);
+        } else if let Some(path) = options.initial_path {
+            viewer.open_path(ctx, path);
+        }
+
+        viewer
+    }
+
+    /// File of the image shown, or `None` for pasted images and the empty state.
+    pub fn current_path(&self) -> Option<&Path> {
+        self.current_path.as_deref()
+    }
+
+    /// Pixels of the image shown, without drawings.
+    pub fn current_image(&self) -> Option<&image::DynamicImage> {
+        self.current_image.as_ref()
+    }
+
+    /// True if there are edits or drawings that haven't been saved.
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.is_image_edited
+    }
+
+    /// Opens a user-chosen image, rescanning its folder and recording it as recent.
+    pub fn open_path(&mut self, ctx: &egui::Context, path: PathBuf) {
+        // Spell the path the way the folder listing will, so relative and
+        // symlinked arguments are found in it
+        let path = dir_scan::list_key(&path).unwrap_or(path);
+        self.image_list.clear();
+        self.listed = false;
+        self.dir_scan = None;
+        self.current_index = 0;
+        self.config.add_recent_file(&path);
+        self.config.last_directory = Some(dir_scan::folder_of(&path).to_path_buf());
+        let _ = self.config.save();
+        self.load_image_and_context(ctx, path);
+    }
+
+    /// Steps through `list` in its order instead of a folder, starting at `start`
+    /// if it is listed.
+    fn open_list(&mut self, ctx: &egui::Context, list: Vec<PathBuf>, start: Option<PathBuf>) {
+        // Spelled like folder listings, so the current image is found in it
+        self.image_list = list.into_iter().map(|path| dir_scan::list_key(&path).unwrap_or(path)).collect();
+        self.listed = true;
+        self.dir_scan = None;
+        let start = start.and_then(|path| dir_scan::list_key(&path));
+        self.current_index = start.and_then(|start| self.image_list.iter().position(|p| *p == start)).unwrap_or(0);
+        let Some(path) = self.image_list.get(self.current_index).cloned() else { return };
+        self.load_image_and_context(ctx, path);
+    }
+
+    /// Opens an entry from the recent files list, dropping it if the file is gone.
+    fn open_recent(&mut self, ctx: &egui::Context, path: PathBuf) {
+        if path.is_file() {
+            self.open_path(ctx, path);
+        } else {
+            self.config.prune_recent_files();
+            let _ = self.config.save();
+        }
+    }
+
+    fn open_file_dialog(&mut self, ctx: &egui::Context) {
+        let mut dialog = rfd::FileDialog::new()
+            .set_title("Open Image")
+            .add_filter("Images", IMAGE_EXTENSIONS);
+        if let Some(dir) = self.config.last_directory.as_ref().filter(|dir| dir.is_dir()) {
+            dialog = dialog.set_directory(dir);
+        }
+        let picked = dialog.pick_file();
+        if let Some(path) = picked {
+            self.open_path(ctx, path);
+        }
+    }
+
+    /// Shown in place of the image while `error_message` is set. A file that failed
+    /// to load (say, one still being written) can be retried or skipped.
+    fn show_error_state(&mut self, ui: &mut egui::Ui, err: &str) {
+        let ctx = ui.ctx().clone();
+        let failed_load = self.texture.is_none().then(|| self.current_path.clone()).flatten();
+
+        ui.vertical_centered(|ui| {
+            ui.add_space((ui.available_height() * 0.35).max(20.0));
+            ui.colored_label(egui::Color32::RED, err);
+            ui.add_space(12.0);
+            ui.spacing_mut().item_spacing.y = 6.0;
+            match &failed_load {
+                Some(path) => {
+                    if ui.button("Retry").on_hover_text("Load the file again").clicked() {
+                        self.load_texture(&ctx, path);
+                    }
+                    if ui.add_enabled(self.image_list.len() > 1, egui::Button::new("Skip to next")).clicked() {
+                        self.next_image(&ctx);
+                    }
+                    if ui.button("Open another...").clicked() {
+                        self.open_file_dialog(&ctx);
+                    }
+                }
+                // The image itself is fine, the error came from saving or pasting
+                None => {
+                    if ui.button("Back to the image").clicked() {
+                        self.error_message = None;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Landing screen shown when no image is loaded.
+    fn show_empty_state(&mut self, ui: &mut egui::Ui) {
+        let ctx = ui.ctx().clone();
+        let mut to_open = None;
+
+        ui.vertical_centered(|ui| {
+            ui.add_space((ui.available_height() * 0.15).max(20.0));
+            ui.heading(egui::RichText::new("sakura").size(32.0).strong());
+            ui.add_space(16.0);
+
+            if ui.button(egui::RichText::new("Open an image").size(16.0)).clicked() {
+                self.open_file_dialog(&ctx);
+            }
+            ui.label(egui::RichText::new("or drop an image file onto this window").weak());
+
+            if !self.config.recent_files.is_empty() {
+                ui.add_space(20.0);
+                ui.label(egui::RichText::new("Recent").strong());
+                for path in &self.config.recent_files {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    if ui.link(name).on_hover_text(path.display().to_string()).clicked() {
+                        to_open = Some(path.clone());
+                    }
+                }
+            }
+
+            ui.add_space(20.0);
+            ui.label(egui::RichText::new("Shortcuts").strong());
+            egui::Grid::new("shortcuts_grid").num_columns(2).spacing([16.0, 4.0]).show(ui, |ui| {
+                let shortcuts = [
+                    ("Ctrl+O", "Open an image"),
+                    ("Q / Ctrl+Q", "Quit"),
+                    ("Ctrl+V / Shift+Ins", "Paste an image"),
+                    ("← / →", "Previous / next image"),
+                    ("/ + name", "Jump to the image whose name starts with what you type"),
+                    (", / .", "Previous / next animation frame"),
+                    ("Scroll", if self.config.scroll_pages { "Pan, then previous / next image past the edge" } else { "Zoom" }),
+                    ("Drag", "Pan"),
+                    ("+ / -", "Zoom in / out around the center"),
+                    ("F / 0", "Fit the whole image in the window"),
+                    ("W / Shift+W", "Fit width / height"),
+                    ("S", "Continuous vertical strip"),
+                    ("G", "Thumbnail grid"),
+                    ("Ctrl+Click / Space", "Mark files in the grid to rotate or flip"),
+                    ("D / Esc", "Toggle / leave drawing mode"),
+                    ("Scroll / Ctrl+Scroll", "Brush size / zoom while drawing"),
+                    ("Alt+Click", "Place text without snapping to guides"),
+                    ("Ctrl+Z", "Undo"),
+                    ("Ctrl+Shift+Z", "Redo"),
+                    ("Ctrl+H", "Edit history, to undo back to any step"),
+                    ("Ctrl+S", "Save over the file, keeping drawings editable"),
+                    ("Backspace", "Revert to the file on disk"),
+                    ("Delete", "Move the image to the trash"),
+                    ("I", "Info panel: compact, expanded, hidden"),
+                    ("R / Shift+R", "Rotate right / left"),
+                    ("H / V", "Flip horizontally / vertically"),
+                    ("L", "Magnifier loupe"),
+                    ("T", "Filmstrip of the folder along the bottom"),
+                    ("C", "Compare with the clipboard image"),
+                    ("Ctrl+Shift+E", "Export visible view"),
+                    ("F6", "Focus the toolbar (then Tab)"),
+                    ("F12", "Performance overlay"),
+                ];
+                for (keys, action) in shortcuts {
+                    ui.label(egui::RichText::new(keys).monospace());
+                    ui.label(action);
+                    ui.end_row();
+                }
+            });
+        });
+
+        if let Some(path) = to_open {
+            self.open_recent(&ctx, path);
+        }
+    }
+
+    /// Clears per-image view and edit state before showing another image.
+    fn reset_image_state(&mut self) {
+        // Keep the unsaved edits of a pasted image we are leaving
+        if let Some(entry) = self.session.current_mut() {
+            if let Some(img) = &self.current_image {
+                entry.image = img.clone();
+            }
+            entry.drawings = std::mem::take(&mut self.drawings);
+            entry.is_edited = self.is_image_edited;
+        }
+        self.session.current = None;
+
+        // Reset transform when loading new image
+        self.zoom = 1.0;
+        self.target_zoom = 1.0;
+        self.offset = egui::Vec2::ZERO;
+        self.target_offset = egui::Vec2::ZERO;
+        self.is_image_edited = false;
+        self.converted_copy = None;
+        self.edit_highlight = None;
+        self.drawings.clear();
+        self.current_stroke = None;
+        self.selected_drawing = None;
+        self.history.clear();
+        self.adjust_panel = None;
+        self.resize_dialog = None;
+        self.downsampled_from = None;
+        self.premultiplied_alpha = false;
+        self.pending_text_pos = None;
+        self.text_entry_string.clear();
+        self.metadata = None;
+        self.file_hash = None;
+        self.animation = None;
+    }
+
+    fn load_image_and_context(&mut self, ctx: &egui::Context, path: PathBuf) {
+        self.reset_image_state();
+        self.scrub_since = None;
+
+        // Populate image list if needed. Large folders take a while, so list in the
+        // background and show the image right away.
+        if self.image_list.is_empty() && self.no_scan {
+            self.image_list = vec![path.clone()];
+            self.current_index = 0;
+        } else if self.image_list.is_empty() {
+            if self.dir_scan.is_none() {
+                self.dir_scan = Some(DirScan::start(dir_scan::folder_of(&path)));
+            }
+        } else {
+             // If we already have a list, update index
+            self.sync_current_index(&path);
+        }
+
+        self.current_path = Some(path.clone());
+        self.load_texture(ctx, &path);
+        self.prefetch_neighbors();
+    }
+
+    /// Starts decoding the images either side of the current one in the
+    /// background, so stepping to them is instant.
+    fn prefetch_neighbors(&mut self) {
+        let neighbors = navigation::neighbors(self.image_list.len(), self.current_index);
+        self.decoded.prefetch(neighbors.into_iter().map(|idx| self.image_list[idx].as_path()));
+    }
+
+    /// Points `current_index` at `path`. A file the listing skipped (say, a symlink
+    /// without an image extension) is slotted in by name, so stepping carries on
+    /// from it instead of from the first image.
+    fn sync_current_index(&mut self, path: &Path) {
+        let Some(key) = dir_scan::list_key(path) else { return };
+        if self.image_list.is_empty() {
+            return;
+        }
+        // Not in a list file: it goes right after the image it was reached from
+        self.current_index = navigation::select(&mut self.image_list, key, self.current_index, self.listed);
+    }
+
+    /// Takes over the folder listing once the background scan finishes.
+    fn poll_dir_scan(&mut self) {
+        let Some(list) = self.dir_scan.as_ref().and_then(|scan| scan.poll()) else { return };
+        self.dir_scan = None;
+        let trashed = std::mem::take(&mut self.trashed_during_scan);
+        self.image_list = list;
+        self.image_list.retain(|p| !trashed.contains(p));
+        self.current_index = 0;
+        if let Some(path) = self.current_path.clone() {
+            self.sync_current_index(&path);
+            self.prefetch_neighbors();
+        }
+    }
+
+    fn load_texture(&mut self, ctx: &egui::Context, path: &Path) {
+        let started = Instant::now();
+        let decoded = match self.decoded.get(path) {
+            Some(img) => Ok(img),
+            None => orientation::open(path).inspect(|img| self.decoded.insert(path, img)),
+        };
+        self.perf.decode = Some(started.elapsed());
+        match decoded {
+            Ok(img) if img.width() == 0 || img.height() == 0 => {
+                self.error_message = Some("Failed to load: image has no pixels".to_string());
+                self.texture = None;
+                self.blurred_texture = None;
+                self.current_image = None;
+            }
+            Ok(img) => {
+                let mut metadata = self.extract_metadata(path, &img);
+                self.premultiplied_alpha = alpha::is_premultiplied(path, &img);
+                // Shrink huge images so they don't hold gigabytes in memory and on the GPU
+                let limit = self.config.max_image_dimension;
+                let img = if !std::mem::take(&mut self.skip_size_limit) && limit > 0 && img.width().max(img.height()) > limit {
+                    self.downsampled_from = Some((img.width(), img.height()));
+                    img.resize(limit, limit, image::imageops::FilterType::Triangle)
+                } else {
+                    img
+                };
+                // Frames of a downsampled animation wouldn't match the image, so show it still
+                if self.downsampled_from.is_none() {
+                    self.animation = AnimationPlayer::load(path);
+                }
+                // Drawings embedded on save come back as objects over the pixels they covered
+                let mut img = img;
+                if self.downsampled_from.is_none() && export::is_png_path(path) {
+                    if let Some((base, drawings)) = embedded_drawings::extract::<StoredDrawing>(path, &img) {
+                        img = base;
+                        self.drawings = drawings.into_iter().map(DrawingObject::from).collect();
+                    }
+                }
+                if let Some(anim) = &self.animation {
+                    metadata.frame_count = Some(anim.frame_count());
+                    metadata.loop_count = Some(anim.loop_count);
+                }
+                self.metadata = Some(metadata);
+                self.show_image(ctx, img);
+            }
+            Err(e) => {
+                self.error_message = Some(load_error_message(path, &e));
+                self.texture = None;
+                self.blurred_texture = None;
+                self.current_image = None;
+            }
+        }
+    }
+    
+    /// Makes `img` the current image, sizing the zoom and window to it.
+    fn show_image(&mut self, ctx: &egui::Context, img: image::DynamicImage) {
+        self.fit_window_to(img.width() as f32, img.height() as f32);
+
+        let size = egui::vec2(img.width() as f32, img.height() as f32);
+        let native = initial_zoom(size.x, size.y);
+        // Fit to the size the window is about to get, if it follows the image
+        let available = self.pending_resize.unwrap_or_else(|| ctx.screen_rect().size());
+        self.zoom = match self.config.open_zoom {
+            OpenZoom::Native => native,
+            OpenZoom::Fit => fit_zoom(available, size).min(native),
+            OpenZoom::Fill => fit_axis_zoom(available.x, size.x).max(fit_axis_zoom(available.y, size.y)),
+        };
+        self.target_zoom = self.zoom;
+        ctx.request_repaint();
+
+        self.current_image = Some(img);
+        self.update_texture_from_image(ctx);
+    }
+
+    /// Adds the clipboard image to the session list and shows it.
+    fn paste_image(&mut self, ctx: &egui::Context) {
+        match clipboard_image() {
+            Ok(rgba) if rgba.width() > 0 && rgba.height() > 0 => {
+                let name = format!("Pasted image {}", self.session.len() + 1);
+                let idx = self.session.push(name, image::DynamicImage::ImageRgba8(rgba));
+                self.show_session_image(ctx, idx);
+            }
+            Ok(_) => self.error_message = Some("Clipboard image is empty".to_string()),
+            Err(e) => self.error_message = Some(format!("Nothing to paste: {}", e)),
+        }
+    }
+
+    /// Opens the compare panel with the clipboard image as the reference, or
+    /// replaces the reference if it is already open.
+    fn compare_with_clipboard(&mut self, ctx: &egui::Context) {
+        let Some(img) = &self.current_image else { return };
+        match clipboard_image() {
+            Ok(reference) if reference.width() > 0 && reference.height() > 0 => {
+                let options = self.config.display_texture_options();
+                let mut panel = ComparePanel::new(ctx, reference, &self.flatten_drawings(img), options);
+                if let Some(old) = &self.compare_panel {
+                    panel.mode = old.mode;
+                    panel.opacity = old.opacity;
+                }
+                self.compare_panel = Some(panel);
+            }
+            Ok(_) => self.error_message = Some("Clipboard image is empty".to_string()),
+            Err(e) => self.error_message = Some(format!("Nothing to compare with: {}", e)),
+        }
+    }
+
+    /// Shows entry `idx` of the session list, restoring its unsaved drawings.
+    fn show_session_image(&mut self, ctx: &egui::Context, idx: usize) {
+        self.reset_image_state();
+        let Some(entry) = self.session.get(idx) else { return };
+        let (img, drawings, is_edited) = (entry.image.clone(), entry.drawings.clone(), entry.is_edited);
+        self.metadata = Some(ImageMetadata {
+            filename: entry.name.clone(),
+            resolution: format!("{} x {}", img.width(), img.height()),
+            file_size: "Not saved".to_string(),
+            format: "Clipboard".to_string(),
+            modified: "N/A".to_string(),
+            frame_count: None,
+            loop_count: None,
+            sha256: None,
+            details: None,
+            camera_model: None,
+            taken: None,
+            shutter_speed: None,
+            aperture: None,
+            iso: None,
+            focal_length: None,
+        });
+        self.session.current = Some(idx);
+        self.current_path = None;
+        self.error_message = None;
+        self.show_image(ctx, img);
+        self.drawings = drawings;
+        self.is_image_edited = is_edited;
+        if is_edited {
+            self.history.forget_saved();
+        }
+    }
+
+    fn extract_metadata(&self, path: &Path, img: &image::DynamicImage) -> ImageMetadata {
+        let resolution = format!("{} x {}", img.width(), img.height());
+        let format = path.extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or("???")
+            .to_uppercase();
+        
+        let (file_size, modified) = self.file_details(path);
+        let exif = details::read_exif(path);
+        let exif_value = |tag| exif.as_ref().and_then(|exif| details::exif_value(exif, tag));
+
+        ImageMetadata {
+            filename: path.file_name().and_then(|s| s.to_str()).unwrap_or("???").to_string(),
+            resolution,
+            file_size,
+            format,
+            modified,
+            frame_count: None,
+            loop_count: None,
+            sha256: None,
+            details: None,
+            camera_model: exif_value(exif::Tag::Model),
+            taken: exif_value(exif::Tag::DateTimeOriginal),
+            shutter_speed: exif_value(exif::Tag::ExposureTime),
+            aperture: exif_value(exif::Tag::FNumber),
+            iso: exif_value(exif::Tag::PhotographicSensitivity),
+            focal_length: exif_value(exif::Tag::FocalLength),
+        }
+    }
+
+    /// Size and modification time of `path`, formatted for the locale.
+    fn file_details(&self, path: &Path) -> (String, String) {
+        match std::fs::metadata(path) {
+            Ok(meta) => (
+                self.locale.file_size(meta.len()),
+                meta.modified().map_or("Unknown".to_string(), |time| self.locale.date_time(time)),
+            ),
+            Err(_) => ("Unknown".to_string(), "Unknown".to_string()),
+        }
+    }
+
+    /// Keeps the SHA-256 in the info panel current: collects a finished hash, and
+    /// starts one when there is none yet or the file changed on disk since.
+    fn update_file_hash(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.current_path.clone() else { return };
+        if self.metadata.is_none() {
+            return;
+        }
+        if let Some(hash) = &self.file_hash {
+            match hash.poll() {
+                Some(digest) => {
+                    if let Some(meta) = &mut self.metadata {
+                        meta.sha256 = Some(digest);
+                    }
+                    self.file_hash = None;
+                }
+                None => {
+                    ctx.request_repaint_after(Duration::from_millis(100));
+                    return;
+                }
+            }
+        }
+
+        let modified = file_hash::modified_time(&path);
+        let hashed = self.metadata.as_ref().and_then(|meta| meta.sha256.as_ref());
+        if hashed.is_none_or(|digest| digest.modified != modified) {
+            if hashed.is_some() {
+                // Rewritten since: the size and date are out of date too
+                let (file_size, modified) = self.file_details(&path);
+                if let Some(meta) = &mut self.metadata {
+                    meta.file_size = file_size;
+                    meta.modified = modified;
+                }
+            }
+            self.file_hash = Some(FileHash::start(&path));
+        }
+        // Check back now and then for changes on disk while the panel is open
+        ctx.request_repaint_after(Duration::from_secs(1));
+    }
+
+    /// Reads EXIF, print resolution and the histogram for the expanded info
+    /// panel, once per image.
+    fn read_details(&mut self) {
+        let (Some(meta), Some(img)) = (&mut self.metadata, &self.current_image) else { return };
+        if meta.details.is_none() {
+            meta.details = Some(ImageDetails::read(self.current_path.as_deref(), img));
+        }
+    }
+
+    fn update_texture_from_image(&mut self, ctx: &egui::Context) {
+        if let Some(img) = &mut self.current_image {
+             let started = Instant::now();
+             // Egui expects straight alpha. Convert the pixels themselves, once, so
+             // edits, the blur and saving all see the same colors as the screen.
+             if std::mem::take(&mut self.premultiplied_alpha) {
+                 alpha::unpremultiply(img);
+             }
+             let rgba = img.to_rgba8();
+             let size = [rgba.width() as usize, rgba.height() as usize];
+             let pixels = rgba.into_raw();
+             let color_image = egui::ColorImage::from_rgba_unmultiplied(size, &pixels);
+             let texture = ctx.load_texture("img", color_image, self.config.display_texture_options());
+             self.texture = Some(texture);
+             self.error_message = None;
+             self.perf.upload = Some(started.elapsed());
+             // The reference stays while moving between images, so compare against the new pixels
+             if let Some(panel) = &mut self.compare_panel {
+                 panel.set_image(&img.to_rgba8(), self.config.display_texture_options());
+             }
+        }
+        self.update_blur_texture(ctx);
+    }
+
+    /// Regenerates the overlay blur from `current_image` at the configured resolution.
+    fn update_blur_texture(&mut self, ctx: &egui::Context) {
+        if let Some(img) = &self.current_image {
+             let started = Instant::now();
+             let b_color_image = blur::blur_image(img, BlurSettings::from_config(&self.config));
+             let b_texture = ctx.load_texture("img_blur", b_color_image, self.config.thumbnail_texture_options());
+             // Crossfade from the old blur instead of popping. While navigating fast,
+             // fade from whichever blur dominates what is on screen.
+             if let Some(old) = self.blurred_texture.replace(b_texture) {
+                 if self.fading_blur.as_ref().is_none_or(|(_, weight)| *weight < 0.5) {
+                     self.fading_blur = Some((old, 1.0));
+                 }
+             }
+             if let Some(picker) = &mut self.blur_picker {
+                 picker.invalidate();
+             }
+             self.perf.blur = Some(started.elapsed());
+        }
+    }
+    
+    /// Rough bytes held for the current image: decoded pixels, animation frames and textures.
+    fn memory_estimate(&self) -> usize {
+        let texture_bytes = |tex: &Option<egui::TextureHandle>| tex.as_ref().map_or(0, |t| t.size()[0] * t.size()[1] * 4);
+        self.current_image.as_ref().map_or(0, |img| img.as_bytes().len())
+            + self.animation.as_ref().map_or(0, |anim| anim.memory_bytes())
+            + texture_bytes(&self.texture)
+            + texture_bytes(&self.blurred_texture)
+            + self.fading_blur.as_ref().map_or(0, |(tex, _)| tex.size()[0] * tex.size()[1] * 4)
+            + self.history.edits.iter().chain(self.history.undone.iter().map(|undone| &undone.edit))
+                .map(|edit| match edit {
+                    Edit::Image { image, animation, .. } => image.as_bytes().len() + animation.as_ref().map_or(0, |anim| anim.memory_bytes()),
+                    Edit::Drawing { .. } | Edit::Transform(_) | Edit::Reorder { .. } => 0,
+                })
+                .sum::<usize>()
+    }
+
+    /// Moves the current file to the trash (Delete), asking first if set to.
+    fn request_delete(&mut self, ctx: &egui::Context) {
+        // While scrubbing the shown name isn't the loaded file yet
+        if self.current_path.is_none() || self.scrub_since.is_some() {
+            return;
+        }
+        if self.config.confirm_delete {
+            self.pending_confirmation = Some(Confirmation::Delete);
+        } else {
+            self.delete_current_image(ctx);
+        }
+    }
+
+    /// Moves the current file to the system trash and shows the image that took
+    /// its place in the folder, or the empty window if it was the last one.
+    fn delete_current_image(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.current_path.clone() else { return };
+        if let Err(e) = trash::delete(&path) {
+            self.error_message = Some(format!("Failed to move {} to the trash: {}", path.file_name().unwrap_or_default().to_string_lossy(), e));
+            return;
+        }
+        self.thumbnails.invalidate(&path);
+        self.decoded.invalidate(&path);
+        let key = dir_scan::list_key(&path);
+        if let Some(idx) = key.as_ref().and_then(|key| navigation::find(&self.image_list, key, self.listed)) {
+            self.current_index = navigation::remove(&mut self.image_list, idx, self.current_index).unwrap_or(0);
+        }
+
+        if self.dir_scan.is_some() {
+            // The listing may have read the folder before the delete; keep it out once it lands
+            self.trashed_during_scan.extend(key);
+            if self.image_list.is_empty() {
+                if let Some(next) = dir_scan::neighbor(&path, true) {
+                    self.load_image_and_context(ctx, next);
+                    return;
+                }
+            }
+        }
+
+        if self.image_list.is_empty() {
+            self.reset_image_state();
+            self.current_path = None;
+            self.texture = None;
+            self.blurred_texture = None;
+            self.current_image = None;
+            self.metadata = None;
+            self.error_message = None;
+        } else {
+            self.current_index = self.current_index.min(self.image_list.len() - 1);
+            let next = self.image_list[self.current_index].clone();
+            self.load_image_and_context(ctx, next);
+        }
+    }
+
+    /// Asks before reverting if that would lose edits, then reverts.
+    fn request_revert(&mut self, ctx: &egui::Context) {
+        if self.current_path.is_none() {
+            return;
+        }
+        if self.is_image_edited && self.config.confirm_revert {
+            self.pending_confirmation = Some(Confirmation::Revert);
+        } else {
+            self.revert(ctx);
+        }
+    }
+
+    /// Reloads the image from disk and drops every edit, drawing and view change.
+    /// Unlike undo there is no way back.
+    pub fn revert(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.current_path.clone() else { return };
+        self.reset_image_state();
+        self.load_texture(ctx, &path);
+    }
+
+    /// Reloads a downsampled image at its original size, turning it as it was and
+    /// scaling drawings to match.
+    fn load_full_resolution(&mut self, ctx: &egui::Context) {
+        let (Some(path), Some(_), Some(img)) = (self.current_path.clone(), self.downsampled_from, &self.current_image) else { return };
+        let shown = egui::vec2(img.width() as f32, img.height() as f32);
+        let mut drawings = std::mem::take(&mut self.drawings);
+        let mut history = std::mem::take(&mut self.history);
+        let is_edited = self.is_image_edited;
+
+        self.reset_image_state();
+        self.skip_size_limit = true;
+        self.load_texture(ctx, &path);
+        // The file isn't rotated or flipped yet; the drawings already are, so they
+        // stay out of the way until it is
+        for edit in &history.edits {
+            if let Edit::Transform(transform) = edit {
+                self.transform_image(ctx, *transform);
+            }
+        }
+        let Some(img) = &self.current_image else { return };
+        // Per axis, from the turned sizes
+        let factor = egui::vec2(img.width() as f32, img.height() as f32) / shown;
+        for drawing in &mut drawings {
+            drawing.scale(factor);
+        }
+        // The steps carry over, as color adjustments and resizes keep the button
+        // disabled; redone drawings would come back unscaled, so redo starts over
+        history.undone.clear();
+        self.history = history;
+        self.drawings = drawings;
+        self.is_image_edited = is_edited;
+    }
+
+    /// Height the filmstrip takes at the bottom of the window, which notes and
+    /// overlays there keep clear of.
+    fn bottom_inset(&self) -> f32 {
+        let shown = self.filmstrip.is_some() && self.strip_view.is_none() && self.gallery.is_none()
+            && !self.image_list.is_empty() && !self.idle.is_idle();
+        if shown { filmstrip::HEIGHT } else { 0.0 }
+    }
+
+    /// Note shown while the image is a downsampled copy, with a way to load it in full.
+    fn show_downsampled_note(&mut self, ctx: &egui::Context) {
+        let Some((width, height)) = self.downsampled_from else { return };
+        let pixel_edits = self.history.edits.iter().any(|edit| matches!(edit, Edit::Image { .. }));
+        let mut load = false;
+        egui::Area::new(egui::Id::new("downsampled_note"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0 - self.bottom_inset()))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                egui::Frame::none()
+                    .fill(egui::Color32::from_black_alpha(180))
+                    .rounding(6.0)
+                    .inner_margin(6.0)
+                    .show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("Downsampled from {} x {}", width, height));
+                            let resp = ui.add_enabled(!pixel_edits, egui::Button::new("Load full size"))
+                                .on_hover_text("Needed to save or convert. Uses much more memory.")
+                                .on_disabled_hover_text("Undo the color adjustments and resizes first; they were made on the smaller copy");
+                            load = resp.clicked();
+                        });
+                    });
+            });
+        if load {
+            self.load_full_resolution(ctx);
+        }
+    }
+
+    /// Paints the corner info overlay inside `rect`, the central panel.
+    fn paint_hud(&self, painter: &egui::Painter, rect: egui::Rect) {
+        let items = self.config.hud_items;
+        let mut parts = Vec::new();
+        if let Some(path) = self.current_path.as_ref().filter(|_| items.name) {
+            parts.push(path.file_name().unwrap_or_default().to_string_lossy().into_owned());
+        }
+        if items.position && !self.image_list.is_empty() {
+            parts.push(format!("{} / {}", self.current_index + 1, self.image_list.len()));
+        }
+        if items.zoom {
+            parts.push(self.locale.percent(self.zoom * 100.0));
+        }
+        if let Some(img) = self.current_image.as_ref().filter(|_| items.resolution) {
+            parts.push(format!("{} x {}", img.width(), img.height()));
+        }
+        if parts.is_empty() {
+            return;
+        }
+
+        let corner = self.config.hud_corner;
+        let mut area = rect.shrink(HUD_MARGIN);
+        area.min.y += HUD_TOP_CLEARANCE;
+        area.max.y -= self.bottom_inset();
+        // Above the downsampled note, which shares the bottom right
+        if corner == HudCorner::BottomRight && self.downsampled_from.is_some() {
+            area.max.y -= 40.0;
+        }
+        let galley = painter.layout_no_wrap(parts.join("   "), egui::FontId::proportional(13.0), egui::Color32::WHITE);
+        let padding = egui::vec2(6.0, 4.0);
+        let anchor = corner.align().pos_in_rect(&area);
+        let bg = corner.align().anchor_size(anchor, galley.size() + 2.0 * padding);
+        painter.rect_filled(bg, 4.0, egui::Color32::from_black_alpha(140));
+        painter.galley(bg.min + padding, galley, egui::Color32::WHITE);
+    }
+
+    /// Resamples the image, and every animation frame, to `width` x `height`,
+    /// scaling the drawings to stay on their content.
+    fn resize_image(&mut self, ctx: &egui::Context, width: u32, height: u32, filter: image::imageops::FilterType) {
+        let Some(img) = &mut self.current_image else { return };
+        let factor = egui::vec2(width as f32 / img.width() as f32, height as f32 / img.height() as f32);
+        let before = std::mem::replace(img, img.resize_exact(width, height, filter));
+        let animation = self.animation.clone();
+        if let Some(anim) = &mut self.animation {
+            anim.map_frames(|frame| frame.resize_exact(width, height, filter));
+        }
+        let edit = Edit::Image { label: "Resize", image: before, animation, drawings: Some(self.drawings.clone()) };
+        self.history.record(edit, &self.config);
+        for drawing in self.drawings.iter_mut().chain(self.current_stroke.as_mut()) {
+            drawing.scale(factor);
+        }
+        if let Some(pos) = &mut self.pending_text_pos {
+            *pos = (pos.to_vec2() * factor).to_pos2();
+        }
+        self.edit_highlight = None;
+        self.is_image_edited = true;
+        self.converted_copy = None;
+        self.fit_window_to(width as f32, height as f32);
+        self.image_changed(ctx);
+    }
+
+    /// Rotates the image and its drawings 90° clockwise (R) or counter-clockwise
+    /// (Shift+R).
+    pub fn rotate(&mut self, ctx: &egui::Context, clockwise: bool) {
+        self.transform(ctx, if clockwise { Transform::RotateRight } else { Transform::RotateLeft });
+    }
+
+    /// Mirrors the image and its drawings left to right (H).
+    pub fn flip_horizontal(&mut self, ctx: &egui::Context) {
+        self.transform(ctx, Transform::FlipHorizontal);
+    }
+
+    /// Mirrors the image and its drawings top to bottom (V).
+    pub fn flip_vertical(&mut self, ctx: &egui::Context) {
+        self.transform(ctx, Transform::FlipVertical);
+    }
+
+    /// Rotates or flips the image and its drawings as an undo step.
+    fn transform(&mut self, ctx: &egui::Context, transform: Transform) {
+        if self.current_image.is_some() {
+            self.transform_image(ctx, transform);
+            self.history.record(Edit::Transform(transform), &self.config);
+            self.is_image_edited = true;
+            self.converted_copy = None;
+        }
+    }
+
+    /// Rotates or flips the image and its drawings, without touching the history.
+    fn transform_image(&mut self, ctx: &egui::Context, transform: Transform) {
+        let Some(img) = &mut self.current_image else { return };
+        // Animations keep playing, transformed frame by frame
+        if let Some(anim) = &mut self.animation {
+            match transform {
+                Transform::RotateRight => anim.rotate90(),
+                Transform::RotateLeft => anim.rotate270(),
+                flip => anim.map_frames(|frame| flip.apply(frame)),
+            }
+        }
+        // Keep annotations on their content
+        let size = egui::vec2(img.width() as f32, img.height() as f32);
+        let map = |p: egui::Pos2| transform_point(transform, size, p);
+        for drawing in self.drawings.iter_mut().chain(self.current_stroke.as_mut()) {
+            drawing.transform(transform, size);
+        }
+        if let Some(pos) = &mut self.pending_text_pos {
+            *pos = map(*pos);
+        }
+        if let Some((region, _)) = &mut self.edit_highlight {
+            *region = egui::Rect::from_two_pos(map(region.min), map(region.max));
+        }
+        *img = transform.apply(img);
+        // Rotations swap width and height; flips leave the window as it is
+        if matches!(transform, Transform::RotateLeft | Transform::RotateRight) {
+            let size = (img.width() as f32, img.height() as f32);
+            self.fit_window_to(size.0, size.1);
+        }
+        self.update_texture_from_image(ctx);
+    }
+
+    /// Schedules the window to take the size of a `width` x `height` image,
+    /// clamped to the screen, when it follows the image size. The size is sent
+    /// over several frames in `show`, as some Wayland compositors miss the first.
+    fn fit_window_to(&mut self, width: f32, height: f32) {
+        if !self.embedded && self.window_mode.fits_images() {
+            let clamped = clamp_to_screen(width, height);
+            self.pending_resize = Some(egui::vec2(clamped[0], clamped[1]));
+            self.pending_resize_frame = 0;
+        }
+    }
+    
+    /// Replaces the pixels of the current image, and of every animation frame, with
+    /// `f` of them as a single undo step named `label`.
+    fn apply_image_edit(&mut self, ctx: &egui::Context, label: &'static str, f: impl Fn(&image::DynamicImage) -> image::DynamicImage) {
+        let Some(img) = self.current_image.take() else { return };
+        let edited = f(&img);
+        self.edit_highlight = self.config.highlight_edits
+            .then(|| changed_region(&img, &edited))
+            .flatten()
+            .map(|region| (region, 1.0));
+        self.current_image = Some(edited);
+        let animation = self.animation.clone();
+        if let Some(anim) = &mut self.animation {
+            anim.map_frames(&f);
+        }
+
+        self.history.record(Edit::Image { label, image: img, animation, drawings: None }, &self.config);
+        self.is_image_edited = true;
+        self.converted_copy = None;
+        self.image_changed(ctx);
+    }
+
+    fn undo_depth(&self) -> UndoDepth {
+        let count = |is_kind: fn(&Edit) -> bool| self.history.edits.iter().filter(|edit| is_kind(edit)).count();
+        UndoDepth {
+            images: count(|edit| matches!(edit, Edit::Image { .. })),
+            image_limit: self.config.undo_image_snapshots,
+            drawings: count(|edit| matches!(edit, Edit::Drawing { .. })),
+            drawing_limit: self.config.undo_drawing_steps,
+        }
+    }
+
+    /// Uploads the animation frame that is current, e.g. after seeking.
+    fn show_animation_frame(&mut self) {
+        if let (Some(anim), Some(texture)) = (&self.animation, &mut self.texture) {
+            texture.set(anim.current_image().clone(), self.config.display_texture_options());
+        }
+    }
+
+    /// Lists the queued sends with their state, the overall progress and the
+    /// transfer rate, with a Retry for each failed one.
+    fn show_send_queue(&mut self, ui: &mut egui::Ui) {
+        let Some(mgr) = &self.share_manager else { return };
+        let (finished, total) = self.send_queue.progress();
+        ui.add(egui::ProgressBar::new(finished as f32 / total as f32)
+            .text(format!("{} of {} sent", finished, total))
+            .animate(self.send_queue.is_busy()));
+        if let (Some(rate), Some(left)) = (self.send_queue.rate(), self.send_queue.time_left()) {
+            ui.label(egui::RichText::new(format!("{}/s, about {} left", self.locale.file_size(rate as u64), format_time_left(left)))
+                .small()
+                .color(egui::Color32::GRAY));
+        }
+
+        let mut retry = None;
+        egui::ScrollArea::vertical().id_salt("send_queue").max_height(160.0).show(ui, |ui| {
+            for item in &self.send_queue.items {
+                ui.horizontal(|ui| {
+                    match &item.state {
+                        SendState::Pending => { ui.label("⏳"); }
+                        SendState::Active => { ui.spinner(); }
+                        SendState::Done => { ui.label(egui::RichText::new("✔").color(egui::Color32::GREEN)); }
+                        SendState::Failed(error) => {
+                            ui.label(egui::RichText::new("✖").color(egui::Color32::RED)).on_hover_text(error);
+                        }
+                    }
+                    let name = item.file_path.file_name().map_or_else(|| item.file_path.display().to_string(), |n| n.to_string_lossy().into_owned());
+                    ui.label(format!("{} → {}", name, mgr.display_name(&item.peer_fingerprint)));
+                    if matches!(item.state, SendState::Failed(_)) && ui.small_button("Retry").clicked() {
+                        retry = Some(item.clone());
+                    }
+                });
+            }
+        });
+        if let Some(item) = retry {
+            if let Err(e) = mgr.retry(&item) {
+                self.share_status = Some(format!("Error: {}", e));
+            }
+        }
+        if finished > 0 && ui.button("Clear sent").clicked() {
+            self.send_queue.clear_done();
+        }
+    }
+
+    /// Paints a round inset beside `pointer` that magnifies the image around
+    /// `image_pos`, sampled pixel for pixel from the image shown.
+    fn show_loupe(&mut self, ctx: &egui::Context, pointer: egui::Pos2, image_pos: egui::Pos2) {
+        let zoom = (self.zoom * self.config.loupe_magnification as f32).max(LOUPE_RADIUS / LOUPE_MAX_SPAN);
+        let span = (LOUPE_RADIUS / zoom).ceil() as i64 + 1;
+        let (x0, y0) = (image_pos.x.floor() as i64 - span, image_pos.y.floor() as i64 - span);
+        let side = (2 * span + 1) as usize;
+
+        // Copy the patch around the cursor; outside the image stays transparent
+        let mut pixels = vec![egui::Color32::TRANSPARENT; side * side];
+        if let Some(anim) = &self.animation {
+            let frame = anim.current_image();
+            let [w, h] = frame.size;
+            for (i, pixel) in pixels.iter_mut().enumerate() {
+                let (x, y) = (x0 + (i % side) as i64, y0 + (i / side) as i64);
+                if (0..w as i64).contains(&x) && (0..h as i64).contains(&y) {
+                    *pixel = frame.pixels[y as usize * w + x as usize];
+                }
+            }
+        } else if let Some(img) = &self.current_image {
+            use image::GenericImageView;
+            for (i, pixel) in pixels.iter_mut().enumerate() {
+                let (x, y) = (x0 + (i % side) as i64, y0 + (i / side) as i64);
+                if (0..img.width() as i64).contains(&x) && (0..img.height() as i64).contains(&y) {
+                    let [r, g, b, a] = img.get_pixel(x as u32, y as u32).0;
+                    *pixel = egui::Color32::from_rgba_unmultiplied(r, g, b, a);
+                }
+            }
+        }
+        let patch = egui::ColorImage { size: [side, side], pixels };
+        // Nearest keeps individual pixels crisp for inspection
+        let texture = match &mut self.loupe_texture {
+            Some(texture) => {
+                texture.set(patch, egui::TextureOptions::NEAREST);
+                texture
+            }
+            None => self.loupe_texture.insert(ctx.load_texture("loupe", patch, egui::TextureOptions::NEAREST)),
+        };
+
+        // Sit up and to the right of the cursor so drawing stays visible, flipping at the window edges
+        let screen = ctx.screen_rect();
+        let gap = LOUPE_RADIUS + 24.0;
+        let mut center = pointer + egui::vec2(gap, -gap);
+        if center.x + LOUPE_RADIUS > screen.max.x {
+            center.x = pointer.x - gap;
+        }
+        if center.y - LOUPE_RADIUS < screen.min.y {
+            center.y = pointer.y + gap;
+        }
+
+        let uv = |offset: egui::Vec2| {
+            let p = image_pos + offset / zoom;
+            egui::pos2((p.x - x0 as f32) / side as f32, (p.y - y0 as f32) / side as f32)
+        };
+        let mut mesh = egui::Mesh::with_texture(texture.id());
+        mesh.colored_vertex(center, egui::Color32::WHITE);
+        mesh.vertices[0].uv = uv(egui::Vec2::ZERO);
+        const SEGMENTS: u32 = 64;
+        for i in 0..=SEGMENTS {
+            let offset = egui::Vec2::angled(i as f32 / SEGMENTS as f32 * std::f32::consts::TAU) * LOUPE_RADIUS;
+            mesh.vertices.push(egui::epaint::Vertex { pos: center + offset, uv: uv(offset), color: egui::Color32::WHITE });
+            if i > 0 {
+                mesh.add_triangle(0, i, i + 1);
+            }
+        }
+
+        let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("loupe")));
+        painter.circle_filled(center, LOUPE_RADIUS, egui::Color32::from_gray(20));
+        painter.add(mesh);
+        // Outline the pixel under the cursor
+        let pixel_min = center + (image_pos.floor() - image_pos) * zoom;
+        painter.rect_stroke(egui::Rect::from_min_size(pixel_min, egui::Vec2::splat(zoom)), 0.0, egui::Stroke::new(1.0, egui::Color32::from_rgb(255, 140, 170)));
+        painter.circle_stroke(center, LOUPE_RADIUS, egui::Stroke::new(2.0, egui::Color32::WHITE));
+    }
+
+    /// Playback controls and a frame scrubber along the bottom edge for animations.
+    fn show_timeline(&mut self, ctx: &egui::Context) {
+        let bottom = self.bottom_inset();
+        let Some(anim) = &mut self.animation else { return };
+        let last = anim.frame_count() - 1;
+        let before = anim.current_frame();
+
+        egui::Area::new(egui::Id::new("timeline"))
+            .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -10.0 - bottom))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                egui::Frame::none()
+                    .fill(egui::Color32::from_black_alpha(180))
+                    .rounding(6.0)
+                    .inner_margin(6.0)
+                    .show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            if ui.button("⏮").on_hover_text("Previous frame (,)").clicked() {
+                                anim.step(false);
+                            }
+                            let playing = anim.playing && !anim.is_finished();
+                            let (icon, label) = if playing { ("⏸", "Pause") } else { ("▶", "Play") };
+                            let resp = ui.button(icon).on_hover_text(label);
+                            resp.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, label));
+                            if resp.clicked() {
+                                anim.toggle_playing();
+                            }
+                            if ui.button("⏭").on_hover_text("Next frame (.)").clicked() {
+                                anim.step(true);
+                            }
+
+                            let mut frame = anim.current_frame();
+                            ui.spacing_mut().slider_width = 240.0;
+                            let resp = ui.add(egui::Slider::new(&mut frame, 0..=last).show_value(false));
+                            if resp.changed() {
+                                // Hold still while scrubbing
+                                anim.playing = false;
+                                anim.seek(frame);
+                            }
+
+                            ui.label(format!(
+                                "{} / {}  {:.2}s / {:.2}s",
+                                anim.current_frame() + 1,
+                                last + 1,
+                                anim.position().as_secs_f32(),
+                                anim.duration().as_secs_f32(),
+                            ));
+                        });
+                    });
+            });
+
+        if anim.current_frame() != before {
+            self.show_animation_frame();
+        }
+    }
+
+    /// Reverts the most recent edit.
+    pub fn undo(&mut self, ctx: &egui::Context) {
+        let undone = match self.history.edits.pop() {
+            Some(Edit::Image { label, image, animation, drawings }) => {
+                let (image, animation, drawings) = self.swap_image(ctx, image, animation, drawings);
+                Undone { edit: Edit::Image { label, image, animation, drawings }, drawing: None }
+            }
+            Some(Edit::Transform(transform)) => {
+                self.transform_image(ctx, transform.inverse());
+                Undone { edit: Edit::Transform(transform), drawing: None }
+            }
+            Some(Edit::Reorder { label, from, to }) => {
+                self.move_drawing(to, from);
+                Undone { edit: Edit::Reorder { label, from, to }, drawing: None }
+            }
+            // Drawings restored with a pasted image or from the file have no history entries of their own
+            edit @ (Some(Edit::Drawing { .. }) | None) => {
+                let Some(drawing) = self.drawings.pop() else { return };
+                if self.selected_drawing >= Some(self.drawings.len()) {
+                    self.selected_drawing = None;
+                }
+                if edit.is_none() {
+                    self.history.forget_saved();
+                }
+                Undone { edit: edit.unwrap_or_else(|| Edit::Drawing { label: drawing.label() }), drawing: Some(drawing) }
+            }
+        };
+        self.history.undone.push(undone);
+        self.is_image_edited = !self.history.is_saved();
+        self.converted_copy = None;
+    }
+
+    /// Applies the most recently undone edit again (Ctrl+Shift+Z).
+    pub fn redo(&mut self, ctx: &egui::Context) {
+        let Some(Undone { edit, drawing }) = self.history.undone.pop() else { return };
+        let edit = match edit {
+            Edit::Image { label, image, animation, drawings } => {
+                let (image, animation, drawings) = self.swap_image(ctx, image, animation, drawings);
+                Edit::Image { label, image, animation, drawings }
+            }
+            Edit::Transform(transform) => {
+                self.transform_image(ctx, transform);
+                edit
+            }
+            Edit::Reorder { from, to, .. } => {
+                self.move_drawing(from, to);
+                edit
+            }
+            Edit::Drawing { .. } => {
+                self.drawings.extend(drawing);
+                edit
+            }
+        };
+        self.history.edits.push(edit);
+        self.is_image_edited = !self.history.is_saved();
+        self.converted_copy = None;
+    }
+
+    /// Puts `image`, its animation frames and, if given, `drawings` in place of
+    /// the current ones, and returns those. Undo and redo of a pixel edit.
+    fn swap_image(
+        &mut self,
+        ctx: &egui::Context,
+        image: image::DynamicImage,
+        animation: Option<AnimationPlayer>,
+        drawings: Option<Vec<DrawingObject>>,
+    ) -> (image::DynamicImage, Option<AnimationPlayer>, Option<Vec<DrawingObject>>) {
+        let before = self.current_image.replace(image).expect("pixel edits are only recorded with an image");
+        // Undoing or redoing a resize
+        let size = self.current_image.as_ref().map_or((0, 0), |img| (img.width(), img.height()));
+        if size != (before.width(), before.height()) {
+            self.fit_window_to(size.0 as f32, size.1 as f32);
+        }
+        let animation = std::mem::replace(&mut self.animation, animation);
+        let drawings = drawings.map(|drawings| {
+            self.selected_drawing = None;
+            std::mem::replace(&mut self.drawings, drawings)
+        });
+        self.edit_highlight = None;
+        self.image_changed(ctx);
+        (before, animation, drawings)
+    }
+
+    /// Undoes or redoes steps until `len` are in the history.
+    fn undo_to(&mut self, ctx: &egui::Context, len: usize) {
+        while self.history.edits.len() > len {
+            self.undo(ctx);
+        }
+        while self.history.edits.len() < len && !self.history.undone.is_empty() {
+            self.redo(ctx);
+        }
+    }
+
+    /// Lists the undo history, oldest first, with the current state marked and
+    /// the undone steps after it. Clicking a step undoes or redoes up to it.
+    fn show_history_panel(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut undo_to = None;
+        egui::Window::new("History")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let current = self.history.edits.len();
+                egui::ScrollArea::vertical().max_height(320.0).stick_to_bottom(true).show(ui, |ui| {
+                    // Drawings older than the history, from a pasted image or the file, are part of the start
+                    if ui.selectable_label(current == 0, "Start").on_hover_text("Undo every step below").clicked() {
+                        undo_to = Some(0);
+                    }
+                    for (i, edit) in self.history.edits.iter().enumerate() {
+                        let resp = ui.selectable_label(i + 1 == current, format!("{}. {}", i + 1, edit.label()));
+                        if i + 1 < current && resp.on_hover_text("Undo the steps after this one").clicked() {
+                            undo_to = Some(i + 1);
+                        }
+                    }
+                    for (i, undone) in self.history.undone.iter().rev().enumerate() {
+                        let text = egui::RichText::new(format!("{}. {}", current + i + 1, undone.edit.label())).color(egui::Color32::GRAY);
+                        if ui.selectable_label(false, text).on_hover_text("Redo up to this step").clicked() {
+                            undo_to = Some(current + i + 1);
+                        }
+                    }
+                });
+                if current == 0 && self.history.undone.is_empty() {
+                    ui.label(egui::RichText::new("Nothing to undo").color(egui::Color32::GRAY));
+                }
+            });
+        if let Some(len) = undo_to {
+            self.undo_to(ctx, len);
+        }
+        if !open || self.escaped_dialog == Some(Dialog::History) {
+            self.show_history = false;
+        }
+    }
+
+    /// Refreshes the textures, the adjust preview source and the info panel details
+    /// after the pixels changed.
+    fn image_changed(&mut self, ctx: &egui::Context) {
+        self.update_texture_from_image(ctx);
+        if let Some(meta) = &mut self.metadata {
+            meta.details = None; // Histogram of the old pixels
+        }
+        if let (Some(_), Some(img)) = (&self.adjust_panel, &self.current_image) {
+            self.adjust_panel = Some(AdjustPanel::new(img, self.config.display_texture_options()));
+        }
+    }
+
+    /// Scales so the whole image fits the window, centered.
+    fn fit_to_window(&mut self, available: egui::Vec2) {
+        if let Some(tex) = &self.texture {
+            self.target_zoom = fit_zoom(available, tex.size_vec2());
+            self.target_offset = egui::Vec2::ZERO;
+        }
+    }
+
+    /// Scales so the image width matches the window, starting at the top edge.
+    /// The height overflows and can be panned, which suits long screenshots.
+    fn fit_to_width(&mut self, available: egui::Vec2) {
+        if let Some(tex) = &self.texture {
+            let size = tex.size_vec2();
+            self.target_zoom = fit_axis_zoom(available.x, size.x);
+            let overflow = (size.y * self.target_zoom - available.y).max(0.0);
+            self.target_offset = egui::vec2(0.0, overflow / 2.0);
+        }
+    }
+
+    /// Scales so the image height matches the window, starting at the left edge.
+    /// The width overflows and can be panned, which suits panoramas.
+    fn fit_to_height(&mut self, available: egui::Vec2) {
+        if let Some(tex) = &self.texture {
+            let size = tex.size_vec2();
+            self.target_zoom = fit_axis_zoom(available.y, size.y);
+            let overflow = (size.x * self.target_zoom - available.x).max(0.0);
+            self.target_offset = egui::vec2(overflow / 2.0, 0.0);
+        }
+    }
+
+    /// Sets the zoom (clamped to the allowed range) keeping the point at the window center fixed.
+    fn set_zoom_centered(&mut self, zoom: f32) {
+        let zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        self.target_offset = zoom_anchor_offset(self.target_offset, zoom / self.target_zoom, None);
+        self.target_zoom = zoom;
+    }
+
+    /// Tool used for new drawings while in drawing mode.
+    pub fn set_drawing_tool(&mut self, tool: DrawingTool) {
+        self.drawing_settings.tool = tool;
+    }
+
+    pub fn set_drawing_mode(&mut self, enabled: bool) {
+        self.is_drawing_mode = enabled;
+        if !enabled {
+            // Keep a stroke that was in progress rather than losing it
+            if let Some(stroke) = self.current_stroke.take() {
+                let label = stroke.label();
+                self.drawings.push(stroke);
+                self.history.record(Edit::Drawing { label }, &self.config);
+            }
+            self.pending_text_pos = None;
+        }
+    }
+
+    /// Switches between the single-image view and the continuous vertical strip.
+    fn toggle_strip_view(&mut self, ctx: &egui::Context) {
+        if self.strip_view.take().is_some() {
+            // Land on whatever image was at the top of the strip
+            let Some(path) = self.image_list.get(self.current_index).cloned() else { return };
+            if self.current_path.as_deref().and_then(dir_scan::list_key) == Some(path.clone()) {
+                return;
+            }
+            // Pasted images keep their edits in the session
+            if self.is_image_edited && self.session.current.is_none() && self.config.confirm_close_unsaved {
+                self.pending_confirmation = Some(Confirmation::LeaveUnsaved { path });
+            } else {
+                self.load_image_and_context(ctx, path);
+            }
+        } else if !self.image_list.is_empty() {
+            self.set_drawing_mode(false);
+            self.strip_view = Some(StripView::new(&self.image_list, self.current_index));
+        }
+    }
+
+    /// Polls the batch job, refreshing what it rewrote, and draws its progress.
+    /// Closes by itself when every file succeeded.
+    fn show_batch_progress(&mut self, ctx: &egui::Context) {
+        let Some(job) = &mut self.batch_job else { return };
+        let changed = job.poll();
+        let current = self.current_path.as_deref().and_then(dir_scan::list_key);
+        let mut reload_current = false;
+        for path in &changed {
+            self.thumbnails.invalidate(path);
+            self.decoded.invalidate(path);
+            reload_current |= current.as_ref() == Some(path);
+        }
+
+        let finished = job.is_finished();
+        let mut close = finished && job.failed.is_empty();
+        if !finished {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+        egui::Window::new(job.transform.label())
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -24.0))
+            .show(ctx, |ui| {
+                let progress = job.processed() as f32 / job.total.max(1) as f32;
+                ui.add(egui::ProgressBar::new(progress).text(format!("{} / {}", job.processed(), job.total)));
+                for (path, e) in &job.failed {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    ui.colored_label(egui::Color32::RED, format!("{}: {}", name, e));
+                }
+                if finished {
+                    close |= ui.button("Close").clicked();
+                } else if job.is_cancelled() {
+                    ui.label("Stopping...");
+                } else if ui.button("Cancel").on_hover_text("Files already done stay changed").clicked() {
+                    job.cancel();
+                }
+            });
+
+        if close {
+            self.batch_job = None;
+        }
+        // An edited image would lose its edits, so it keeps showing the old pixels
+        if reload_current && !self.is_image_edited {
+            if let Some(path) = self.current_path.clone() {
+                self.load_image_and_context(ctx, path);
+            }
+        }
+    }
+
+    /// Switches between the single-image view and the thumbnail grid.
+    fn toggle_gallery(&mut self, ctx: &egui::Context) {
+        if self.gallery.take().is_some() {
+            // Leaving via the toggle goes back to the current image, loading it again if
+            // the folder changed under it while the grid was open, e.g. it was deleted
+            if let Some(path) = self.image_list.get(self.current_index).cloned() {
+                if self.current_path.as_deref().and_then(dir_scan::list_key) != Some(path.clone()) {
+                    self.load_image_and_context(ctx, path);
+                }
+            }
+        } else if !self.image_list.is_empty() {
+            self.set_drawing_mode(false);
+            self.strip_view = None;
+            self.gallery = Some(Gallery::new(self.current_index));
+        }
+    }
+
+    pub fn next_image(&mut self, ctx: &egui::Context) {
+        if let Some(idx) = self.session.next_index() {
+            self.show_session_image(ctx, idx);
+            return;
+        }
+        if self.image_list.is_empty() {
+            // Folder still being listed: step by name without waiting for the whole list
+            if self.dir_scan.is_some() {
+                if let Some(path) = self.current_path.as_deref().and_then(|p| dir_scan::neighbor(p, true)) {
+                    self.load_image_and_context(ctx, path);
+                }
+            }
+            return;
+        }
+        if let Some(strip) = &mut self.strip_view {
+            if let Some(idx) = navigation::step(self.image_list.len(), strip.top_index, true, false) {
+                strip.jump_to(idx);
+            }
+            return;
+        }
+        if let Some(idx) = navigation::step(self.image_list.len(), self.current_index, true, true) {
+            self.step_to(ctx, idx);
+        }
+    }
+
+    pub fn prev_image(&mut self, ctx: &egui::Context) {
+        if let Some(idx) = self.session.prev_index() {
+            self.show_session_image(ctx, idx);
+            return;
+        }
+        if self.image_list.is_empty() {
+            // Folder still being listed: step by name without waiting for the whole list
+            if self.dir_scan.is_some() {
+                if let Some(path) = self.current_path.as_deref().and_then(|p| dir_scan::neighbor(p, false)) {
+                    self.load_image_and_context(ctx, path);
+                }
+            }
+            return;
+        }
+        if let Some(strip) = &mut self.strip_view {
+            if let Some(idx) = navigation::step(self.image_list.len(), strip.top_index, false, false) {
+                strip.jump_to(idx);
+            }
+            return;
+        }
+        if let Some(idx) = navigation::step(self.image_list.len(), self.current_index, false, true) {
+            self.step_to(ctx, idx);
+        }
+    }
+
+    /// Shows the image at `idx` in the folder. A step right after the last one
+    /// only moves there and leaves loading to `settle_scrub`, so holding an
+    /// arrow key decodes just the image it stops on.
+    fn step_to(&mut self, ctx: &egui::Context, idx: usize) {
+        let now = Instant::now();
+        let rapid = self.last_step.is_some_and(|at| now.duration_since(at) < SCRUB_GAP);
+        self.current_index = idx;
+        if rapid {
+            self.scrub_since = Some(now);
+            ctx.request_repaint_after(SCRUB_SETTLE);
+        } else {
+            let path = self.image_list[idx].clone();
+            self.load_image_and_context(ctx, path);
+        }
+        // Measured from after the load, so a slow decode doesn't make the next
+        // repeat of a held key look like a fresh press
+        self.last_step = Some(Instant::now());
+    }
+
+    /// Loads the image scrubbing stopped on once the steps have settled, and
+    /// shows where it is until then.
+    fn settle_scrub(&mut self, ctx: &egui::Context) {
+        let Some(last) = self.last_step.filter(|_| self.scrub_since.is_some()) else { return };
+        let Some(path) = self.image_list.get(self.current_index).cloned() else {
+            self.scrub_since = None;
+            return;
+        };
+        let quiet = last.elapsed();
+        if quiet >= SCRUB_SETTLE {
+            self.load_image_and_context(ctx, path);
+            return;
+        }
+        ctx.request_repaint_after(SCRUB_SETTLE - quiet);
+
+        // Cover the image left behind with the thumbnail, if there is one, and the name
+        let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Middle, egui::Id::new("scrub_placeholder")));
+        let screen = ctx.screen_rect();
+        painter.rect_filled(screen, 0.0, egui::Color32::from_gray(12));
+        if let Some(tex) = self.thumbnails.cached(&path) {
+            let size = tex.size_vec2() * (screen.size() / tex.size_vec2()).min_elem().min(1.0);
+            let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+            painter.image(tex.id(), egui::Rect::from_center_size(screen.center(), size), uv, egui::Color32::WHITE);
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        painter.text(
+            screen.center_bottom() - egui::vec2(0.0, self.bottom_inset() + 40.0),
+            egui::Align2::CENTER_BOTTOM,
+            format!("{}  ({} / {})", name, self.current_index + 1, self.image_list.len()),
+            egui::FontId::proportional(16.0),
+            egui::Color32::WHITE,
+        );
+    }
+
+    /// Brings image `idx` of the folder list into view: selects it in the grid,
+    /// scrolls the strip to it, or opens it.
+    fn jump_to(&mut self, ctx: &egui::Context, idx: usize) {
+        if let Some(gallery) = &mut self.gallery {
+            gallery.select(idx);
+        } else if let Some(strip) = &mut self.strip_view {
+            strip.jump_to(idx);
+        } else if idx != self.current_index || self.session.current.is_some() {
+            self.current_index = idx;
+            let path = self.image_list[idx].clone();
+            self.load_image_and_context(ctx, path);
+        }
+    }
+
+    /// Moves the selected drawing in the stacking order. Later drawings paint, and
+    /// burn in, on top of earlier ones.
+    fn reorder_selected(&mut self, to: Reorder) {
+        let Some(idx) = self.selected_drawing.filter(|&i| i < self.drawings.len()) else { return };
+        let last = self.drawings.len() - 1;
+        let new_idx = match to {
+            Reorder::Front => last,
+            Reorder::Forward => (idx + 1).min(last),
+            Reorder::Backward => idx.saturating_sub(1),
+            Reorder::Back => 0,
+        };
+        if new_idx != idx {
+            self.move_drawing(idx, new_idx);
+            self.history.record(Edit::Reorder { label: to.label(), from: idx, to: new_idx }, &self.config);
+            self.is_image_edited = true;
+            self.converted_copy = None;
+        }
+    }
+
+    /// Moves drawing `from` to `to` in the stacking order, selecting it there.
+    fn move_drawing(&mut self, from: usize, to: usize) {
+        if from < self.drawings.len() && to < self.drawings.len() {
+            let drawing = self.drawings.remove(from);
+            self.drawings.insert(to, drawing);
+            self.selected_drawing = Some(to);
+        }
+    }
+
+    /// Returns a copy of `img` with the current drawings burned in.
+    fn flatten_drawings(&self, img: &image::DynamicImage) -> image::RgbaImage {
+        let mut rgba = img.to_rgba8();
+        
+        // Helper to map color
+        let to_rgba = |c: egui::Color32| image::Rgba([c.r(), c.g(), c.b(), c.a()]);
+
+        for drawing in &self.drawings {
+            let col = to_rgba(drawing.color);
+            match drawing.tool {
+                DrawingTool::Pencil => {
+                    for i in 0..drawing.points.len().saturating_sub(1) {
+                        let start = drawing.points[i];
+                        let end = drawing.points[i+1];
+                        let width = drawing.segment_width(i);
+                        imageproc::drawing::draw_line_segment_mut(
+                            &mut rgba,
+                            (start.x, start.y),
+                            (end.x, end.y),
+                            col
+                        );
+                        // Thick lines hack
+                        if width > 1.0 {
+                             for o in 1..=(width as i32 / 2) {
+                                 let off = o as f32;
+                                 imageproc::drawing::draw_line_segment_mut(&mut rgba, (start.x+off, start.y), (end.x+off, end.y), col);
+                                 imageproc::drawing::draw_line_segment_mut(&mut rgba, (start.x-off, start.y), (end.x-off, end.y), col);
+                                 imageproc::drawing::draw_line_segment_mut(&mut rgba, (start.x, start.y+off), (end.x, end.y+off), col);
+                                 imageproc::drawing::draw_line_segment_mut(&mut rgba, (start.x, start.y-off), (end.x, end.y-off), col);
+                             }
+                        }
+                    }
+                }
+                DrawingTool::Shape => {
+                     if drawing.points.len() >= 2 {
+                         let start = drawing.points[0];
+                         let end = drawing.points[1];
+                         
+                         if let Some(stype) = drawing.shape_type {
+                             match stype {
+                                 ShapeType::Rectangle => {
+                                     burn_rect_outline(&mut rgba, start, end, drawing.size, col);
+                                 },
+                                 ShapeType::Circle => {
+                                     // Around the first point, as on screen
+                                     burn_circle_outline(&mut rgba, start, start.distance(end), drawing.size, col);
+                                 },
+                                 ShapeType::Line => {
+                                      imageproc::drawing::draw_line_segment_mut(&mut rgba, (start.x, start.y), (end.x, end.y), col);
+                                      for o in 1..=(drawing.size as i32 / 2) {
+                                         let off = o as f32;
+                                         imageproc::drawing::draw_line_segment_mut(&mut rgba, (start.x+off, start.y), (end.x+off, end.y), col);
+                                         imageproc::drawing::draw_line_segment_mut(&mut rgba, (start.x, start.y+off), (end.x, end.y+off), col);
+                                     }
+                                 }
+                             }
+                         }
+                     }
+                }
+                DrawingTool::Text => {
+                     if let Some(text) = &drawing.text {
+                         if let Some(pos) = drawing.points.first() {
+                             let scale = ab_glyph::PxScale::from(drawing.size);
+                             let (x, y) = (pos.x as i32, pos.y as i32);
+                             let monospace = drawing.font_family == Some(FontFamily::Monospace);
+                             fonts::draw_text(&mut rgba, col, x, y, scale, text, monospace);
+
+                             // Bold: smear the text one pixel in every direction
+                             if drawing.font_bold {
+                                  let offsets = [(1,0), (-1,0), (0,1), (0,-1), (1,1), (-1,-1), (1,-1), (-1,1)];
+                                  for (ox, oy) in offsets {
+                                      fonts::draw_text(&mut rgba, col, x + ox, y + oy, scale, text, monospace);
+                                  }
+                             }
+                         }
+                     }
+                }
+                DrawingTool::Select => {}
+            }
+        }
+
+        rgba
+    }
+
+    /// Writes the image with its drawings burned in back to its file.
+    ///
+    /// Nothing else is written next to the file. In the viewer the drawings stay
+    /// separate vector objects over the unchanged pixels, so they can still be
+    /// selected, moved and undone after saving, and saving again burns in their
+    /// latest state. With `embed_drawings`, a PNG also carries them so they stay
+    /// objects when it is opened again.
+    pub fn save_current_image(&mut self) -> Result<(), String> {
+        if self.downsampled_from.is_some() {
+            return Err("the image is downsampled; load it at full size before saving".to_string());
+        }
+        if let Some(path) = &self.current_path {
+            if let Some(img) = &self.current_image {
+                if let Some(block) = export::write_block(path) {
+                    return Err(format!("permission denied ({})", block.describe(path)));
+                }
+                // Only turned since the file was written: a JPEG just gets its EXIF
+                // orientation rewritten, so it loses nothing to encoding again
+                let turns = self.history.transforms_since_saved().filter(|_| export::is_jpeg_path(path) && self.drawings.is_empty());
+                let rewritten = match turns {
+                    Some(transforms) => orientation::transform_jpeg_losslessly(path, &transforms)?,
+                    None => false,
+                };
+                if !rewritten {
+                    self.write_image(img, path)?;
+                }
+                self.thumbnails.invalidate(path);
+                self.decoded.invalidate(path);
+                self.is_image_edited = false;
+                self.history.mark_saved();
+                return Ok(());
+            }
+        }
+        Err("No image to save".to_string())
+    }
+
+    /// Writes `img` with its drawings burned in to `path`, in the format its
+    /// extension names.
+    fn write_image(&self, img: &image::DynamicImage, path: &Path) -> Result<(), String> {
+        let frames = self.animation.as_ref().filter(|_| export::is_gif_path(path)).map_or(1, |anim| anim.frame_count());
+        self.check_free_space(path, img, frames)?;
+
+        // Animated GIFs keep all their frames, with the drawings on each one
+        if let Some(anim) = self.animation.as_ref().filter(|_| export::is_gif_path(path)) {
+            let frames: Vec<_> = anim.frames_rgba()
+                .map(|(frame, delay)| (self.flatten_drawings(&image::DynamicImage::ImageRgba8(frame)), delay))
+                .collect();
+            return export::save_gif_animation(frames, anim.loop_count, path);
+        }
+
+        // Burn drawings into a copy; the pixels shown stay without them
+        let flattened = image::DynamicImage::ImageRgba8(self.flatten_drawings(img));
+        export::save_image(&flattened, path, &self.config)?;
+        self.embed_drawings(img, &flattened, path)
+    }
+
+    /// Asks where to save a copy of the image, writes it there and shows it.
+    /// For files that can't be written in place, and pasted images, which have
+    /// no file. Closes the window afterwards if `close` is set.
+    fn save_copy(&mut self, ctx: &egui::Context, close: bool) {
+        if self.downsampled_from.is_some() {
+            self.error_message = Some("Failed to save: the image is downsampled; load it at full size before saving".to_string());
+            return;
+        }
+        let Some(img) = &self.current_image else { return };
+        let name = match (&self.current_path, self.session.current.and_then(|idx| self.session.get(idx))) {
+            (Some(path), _) => path.file_name().map(|name| name.to_string_lossy().into_owned()),
+            (None, Some(entry)) => Some(format!("{}.png", entry.name)),
+            (None, None) => None,
+        };
+        let mut dialog = rfd::FileDialog::new().set_title("Save a Copy");
+        if let Some(name) = name {
+            dialog = dialog.set_file_name(name);
+        }
+        let Some(copy) = dialog.save_file() else { return };
+        if let Err(e) = self.write_image(img, &copy) {
+            self.error_message = Some(format!("Failed to save a copy: {}", e));
+            return;
+        }
+        self.thumbnails.invalidate(&copy);
+        self.decoded.invalidate(&copy);
+        self.is_image_edited = false;
+        self.history.mark_saved();
+        if close {
+            self.session.discard_edits();
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        } else {
+            // The edits live on in the copy, so carry on from there
+            self.open_path(ctx, copy);
+        }
+    }
+
+    /// Asks how to go on when the file can't be written in place. Returns true
+    /// if it asked, and the save should wait for the answer.
+    fn ask_if_read_only(&mut self, close: bool) -> bool {
+        let Some(block) = self.current_path.as_deref().and_then(export::write_block) else { return false };
+        self.pending_confirmation = Some(Confirmation::ReadOnly { block, close });
+        true
+    }
+
+    /// Adds the drawings to the PNG just written to `path` from `img` and its
+    /// `flattened` copy, when `embed_drawings` is on.
+    fn embed_drawings(&self, img: &image::DynamicImage, flattened: &image::DynamicImage, path: &Path) -> Result<(), String> {
+        if !self.config.embed_drawings || self.drawings.is_empty() || !export::is_png_path(path) {
+            return Ok(());
+        }
+        let base = image::DynamicImage::ImageRgba8(img.to_rgba8());
+        let region = changed_region(&base, flattened)
+            .map(|r| [r.min.x as u32, r.min.y as u32, r.width() as u32, r.height() as u32]);
+        let drawings: Vec<StoredDrawing> = self.drawings.iter().map(StoredDrawing::from).collect();
+        embedded_drawings::embed(path, &drawings, &base, region)
+            .map_err(|e| format!("the image was written, but not its drawings: {}", e))
+    }
+
+    /// Errors before writing `frames` frames the size of `img` to `path` if the
+    /// volume is clearly too full to hold them.
+    fn check_free_space(&self, path: &Path, img: &image::DynamicImage, frames: usize) -> Result<(), String> {
+        let needed = export::estimated_size(img.width(), img.height(), frames, path, &self.config);
+        export::check_free_space(path, needed).map_err(|(needed, available)| {
+            format!(
+                "not enough disk space: about {} needed, {} free",
+                self.locale.file_size(needed),
+                self.locale.file_size(available)
+            )
+        })
+    }
+
+    /// Copies the image with its drawings as a base64 `data:` URL in `format`.
+    fn copy_data_url(&mut self, ctx: &egui::Context, format: image::ImageFormat) {
+        let Some(img) = &self.current_image else { return };
+        let flattened = image::DynamicImage::ImageRgba8(self.flatten_drawings(img));
+        match export::data_url(&flattened, format, self.config.data_url_quality, &self.config) {
+            Ok(url) => ctx.copy_text(url),
+            Err(e) => self.error_message = Some(format!("Failed to copy data URL: {}", e)),
+        }
+    }
+
+    /// Saves what is currently visible of the image (drawings included) at screen
+    /// resolution, like a screenshot of the canvas.
+    fn export_view(&mut self, ctx: &egui::Context) {
+        let Some(img) = &self.current_image else { return };
+
+        // Same placement as the central panel: centered in the window, shifted by `offset`
+        let canvas = ctx.screen_rect();
+        let image_size = egui::vec2(img.width() as f32, img.height() as f32);
+        let image_rect = egui::Rect::from_center_size(canvas.center() + self.offset, image_size * self.zoom);
+        let visible = image_rect.intersect(canvas);
+        if !visible.is_positive() {
+            self.error_message = Some("Nothing of the image is visible to export".to_string());
+            return;
+        }
+
+        // Visible part in image pixels
+        let src_min = ((visible.min - image_rect.min) / self.zoom).floor();
+        let src_max = ((visible.max - image_rect.min) / self.zoom).ceil().min(image_size);
+        let (x, y) = (src_min.x.max(0.0) as u32, src_min.y.max(0.0) as u32);
+        let w = (src_max.x as u32).saturating_sub(x).max(1);
+        let h = (src_max.y as u32).saturating_sub(y).max(1);
+
+        let ppp = ctx.pixels_per_point();
+        let out_w = ((visible.width() * ppp).round() as u32).max(1);
+        let out_h = ((visible.height() * ppp).round() as u32).max(1);
+
+        let stem = self.current_path.as_ref()
+            .and_then(|p| p.file_stem())
+            .map_or("image".into(), |s| s.to_string_lossy());
+        let picked = rfd::FileDialog::new()
+            .set_title("Export View")
+            .set_file_name(format!("{}_view.png", stem))
+            .add_filter("PNG", &["png"])
+            .add_filter("JPEG", &["jpg", "jpeg"])
+            .save_file();
+        let Some(out_path) = picked else { return };
+
+        let flattened = image::DynamicImage::ImageRgba8(self.flatten_drawings(img));
+        let filter = if self.zoom >= 1.0 {
+            image::imageops::FilterType::Nearest
+        } else {
+            image::imageops::FilterType::Triangle
+        };
+        let view = flattened.crop_imm(x, y, w, h).resize_exact(out_w, out_h, filter);
+        if let Err(e) = export::save_image(&view, &out_path, &self.config) {
+            self.error_message = Some(format!("Failed to export view: {}", e));
+        } else {
+            self.thumbnails.invalidate(&out_path);
+            self.decoded.invalidate(&out_path);
+        }
+    }
+
+    /// Saves the image with its drawings as a one-page PDF, captioned with the
+    /// file name, resolution and modification date.
+    #[cfg(feature = "pdf")]
+    fn export_pdf(&mut self) {
+        let Some(img) = &self.current_image else { return };
+        let stem = self.current_path.as_ref()
+            .and_then(|p| p.file_stem())
+            .map_or("image".into(), |s| s.to_string_lossy());
+        let picked = rfd::FileDialog::new()
+            .set_title("Export PDF")
+            .set_file_name(format!("{}.pdf", stem))
+            .add_filter("PDF", &["pdf"])
+            .save_file();
+        let Some(out_path) = picked else { return };
+
+        let (title, caption) = match &self.metadata {
+            Some(meta) => (meta.filename.clone(), vec![
+                meta.filename.clone(),
+                format!("{} pixels", meta.resolution),
+                format!("Modified {}", meta.modified),
+            ]),
+            None => (stem.to_string(), Vec::new()),
+        };
+        let flattened = self.flatten_drawings(img);
+        if let Err(e) = export::save_pdf(&flattened, &title, &caption, &out_path) {
+            self.error_message = Some(format!("Failed to export PDF: {}", e));
+        }
+    }
+
+    fn convert_image(&mut self, ctx: &egui::Context, format: image::ImageFormat) {
+        if let Some(path) = &self.current_path {
+            let new_ext = match format {
+                image::ImageFormat::Png => "png",
+                image::ImageFormat::Jpeg => "jpg",
+                _ => "png",
+            };
+            let new_path = path.with_extension(new_ext);
+            self.request_convert(ctx, new_path, ConvertCheck::FlattenAnimation);
+        }
+    }
+
+    /// What unsaved edits there are, for the close confirmation: the drawings
+    /// and color adjustments since the last save, or just "unsaved changes" for
+    /// the rest, such as rotations and resizes. None when there is nothing to lose.
+    fn unsaved_edits(&self) -> Option<String> {
+        if !self.is_image_edited {
+            return None;
+        }
+        // Once no step matches the file, any of the drawings may be new
+        let (steps, drawings) = match self.history.saved_at {
+            Some(at) => {
+                let steps = self.history.edits.get(at..).unwrap_or_default();
+                (steps, steps.iter().filter(|edit| matches!(edit, Edit::Drawing { .. })).count())
+            }
+            None => (&self.history.edits[..], self.drawings.len()),
+        };
+        let drawings = match drawings {
+            0 => None,
+            1 => Some("1 annotation".to_string()),
+            n => Some(format!("{} annotations", n)),
+        };
+        // Resizes are the pixel edits that also move the drawings
+        let pixel_edits = steps.iter().any(|edit| matches!(edit, Edit::Image { drawings: None, .. }));
+        Some(match (drawings, pixel_edits) {
+            (Some(drawings), false) => format!("your {}", drawings),
+            (Some(drawings), true) => format!("your {} and color adjustments", drawings),
+            (None, true) => "your color adjustments".to_string(),
+            (None, false) => "your unsaved changes".to_string(),
+        })
+    }
+
+    /// Runs the confirmations a conversion to `new_path` needs, then writes it.
+    fn request_convert(&mut self, ctx: &egui::Context, new_path: PathBuf, from: ConvertCheck) {
+        if from <= ConvertCheck::FlattenAnimation && self.flattens_animation(&new_path) && self.config.confirm_flatten_animation {
+            self.pending_confirmation = Some(Confirmation::FlattenAnimation { action: FlattenAction::Convert(new_path), dont_ask: false });
+            return;
+        }
+        if from <= ConvertCheck::Losses && self.config.confirm_lossy_convert {
+            if let Some(img) = &self.current_image {
+                let flattened = self.flatten_drawings(img);
+                let losses = export::conversion_losses(img.color(), &flattened, &new_path);
+                if !losses.is_empty() {
+                    let loses_alpha = export::is_jpeg_path(&new_path) && flattened.pixels().any(|p| p.0[3] < 255);
+                    self.pending_confirmation = Some(Confirmation::LossyConvert { path: new_path, losses, loses_alpha });
+                    return;
+                }
+            }
+        }
+        if new_path.exists() && self.config.confirm_overwrite {
+            self.pending_confirmation = Some(Confirmation::Overwrite { path: new_path });
+        } else {
+            self.write_converted(ctx, &new_path);
+        }
+    }
+
+    /// True if writing the current image to `path` would drop animation frames.
+    /// Only GIF can be re-encoded with all its frames.
+    fn flattens_animation(&self, path: &Path) -> bool {
+        self.animation.is_some() && !export::is_gif_path(path)
+    }
+
+    /// Saves over the file (Ctrl+S), asking first if that keeps one frame of an animation.
+    fn request_save(&mut self, ctx: &egui::Context) {
+        let Some(path) = &self.current_path else {
+            // A pasted image has no file yet
+            if self.session.current.is_some() {
+                self.save_copy(ctx, false);
+            }
+            return;
+        };
+        if self.flattens_animation(path) && self.config.confirm_flatten_animation {
+            self.pending_confirmation = Some(Confirmation::FlattenAnimation { action: FlattenAction::Save, dont_ask: false });
+        } else {
+            self.save(ctx);
+        }
+    }
+
+    fn save(&mut self, ctx: &egui::Context) {
+        if self.ask_if_read_only(false) {
+            return;
+        }
+        if let Err(e) = self.save_current_image() {
+            self.error_message = Some(format!("Failed to save: {}", e));
+        }
+        ctx.request_repaint();
+    }
+
+    fn save_and_close(&mut self, ctx: &egui::Context) {
+        if self.ask_if_read_only(true) {
+            return;
+        }
+        if self.current_path.is_none() {
+            self.save_copy(ctx, true);
+            return;
+        }
+        match self.save_current_image() {
+            Ok(_) => {
+                // The confirmation said the other pasted images are lost
+                self.session.discard_edits();
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+            Err(e) => self.error_message = Some(format!("Failed to save: {}", e)),
+        }
+    }
+
+    /// Writes the image with its drawings burned in to `new_path`, then shows the
+    /// new file if `open_after_convert` is set.
+    fn write_converted(&mut self, ctx: &egui::Context, new_path: &Path) {
+        if self.downsampled_from.is_some() {
+            self.error_message = Some("Failed to convert: the image is downsampled; load it at full size first".to_string());
+            return;
+        }
+        let Some(img) = &self.current_image else { return };
+        if let Some(block) = export::write_block(new_path) {
+            self.error_message = Some(format!("Failed to convert: permission denied ({})", block.describe(new_path)));
+            return;
+        }
+        if let Err(e) = self.check_free_space(new_path, img, 1) {
+            self.error_message = Some(format!("Failed to convert: {}", e));
+            return;
+        }
+        let flattened = image::DynamicImage::ImageRgba8(self.flatten_drawings(img));
+        if let Err(e) = export::save_image(&flattened, new_path, &self.config)
+            .and_then(|_| self.embed_drawings(img, &flattened, new_path)) {
+            self.error_message = Some(format!("Failed to convert: {}", e));
+            return;
+        }
+        self.thumbnails.invalidate(new_path);
+        self.decoded.invalidate(new_path);
+
+        // Slot the new file into the folder list where a rescan would put it, or
+        // into a list file's order right after the original
+        if let Some(key) = dir_scan::list_key(new_path) {
+            if !self.image_list.is_empty() {
+                self.current_index = navigation::insert(&mut self.image_list, key, self.current_index, self.listed);
+            }
+        }
+
+        if self.config.open_after_convert {
+            // The drawings live on in the converted file
+            self.is_image_edited = false;
+            self.load_image_and_context(ctx, new_path.to_path_buf());
+        } else if self.is_image_edited {
+            self.converted_copy = Some(new_path.to_path_buf());
+        }
+    }
+
+    /// Keeps `flatten_preview` showing the image with its drawings flattened onto
+    /// the current background, as a conversion would write it.
+    fn update_flatten_preview(&mut self, ctx: &egui::Context) {
+        let background = self.config.flatten_background;
+        if self.flatten_preview.as_ref().is_some_and(|(_, _, used)| *used == background) {
+            return;
+        }
+        let source = match self.flatten_preview.take() {
+            Some((source, ..)) => source,
+            None => {
+                let Some(img) = &self.current_image else { return };
+                thumbnails::make_thumbnail(&image::DynamicImage::ImageRgba8(self.flatten_drawings(img)))
+            }
+        };
+        let rgba = export::flatten_alpha(&source, background).to_rgba8();
+        let size = [rgba.width() as usize, rgba.height() as usize];
+        let color_image = egui::ColorImage::from_rgba_unmultiplied(size, rgba.as_raw());
+        let texture = ctx.load_texture("flatten_preview", color_image, self.config.thumbnail_texture_options());
+        self.flatten_preview = Some((source, texture, background));
+    }
+
+    /// The open dialog that Enter and Escape go to.
+    fn front_dialog(&self) -> Option<Dialog> {
+        if self.pending_confirmation.is_some() {
+            Some(Dialog::Confirmation)
+        } else if self.pending_text_pos.is_some() {
+            Some(Dialog::Text)
+        } else if self.resize_dialog.is_some() {
+            Some(Dialog::Resize)
+        } else if self.show_share_modal {
+            Some(Dialog::Share)
+        } else if self.show_settings {
+            Some(Dialog::Settings)
+        } else if self.show_history {
+            Some(Dialog::History)
+        } else if self.show_info_panel && self.metadata.is_some() {
+            Some(Dialog::Info)
+        } else {
+            None
+        }
+    }
+
+    /// Shows the dialog for a pending confirmation and carries out the action once confirmed.
+    fn show_confirmation(&mut self, ctx: &egui::Context) {
+        if matches!(self.pending_confirmation, Some(Confirmation::LossyConvert { loses_alpha: true, .. })) {
+            self.update_flatten_preview(ctx);
+        } else {
+            self.flatten_preview = None;
+        }
+        let unsaved = self.unsaved_edits();
+        let pasted = self.session.edited_elsewhere();
+        // Undone back to the saved image while asking: nothing left to lose
+        if unsaved.is_none() && pasted == 0 && matches!(self.pending_confirmation, Some(Confirmation::CloseUnsaved)) {
+            self.pending_confirmation = None;
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            return;
+        }
+        let Some(pending) = &mut self.pending_confirmation else { return };
+        let (title, message, confirm_label) = match pending {
+            Confirmation::CloseUnsaved => {
+                let name = |path: Option<&Path>| path.and_then(|p| p.file_name()).unwrap_or_default().to_string_lossy().into_owned();
+                let file = match self.session.current.and_then(|idx| self.session.get(idx)) {
+                    Some(entry) => entry.name.clone(),
+                    None => name(self.current_path.as_deref()),
+                };
+                let others = match pasted {
+                    0 => String::new(),
+                    1 => " and the edits of another pasted image".to_string(),
+                    n => format!(" and the edits of {} other pasted images", n),
+                };
+                match (&self.converted_copy, unsaved) {
+                    (Some(copy), _) if pasted == 0 => ("Save Changes?", format!(
+                        "Your changes are in the converted copy, {}, so closing loses nothing. {} itself is unchanged. Save them to it too?",
+                        name(Some(copy)),
+                        file,
+                    ), "Save"),
+                    (_, Some(unsaved)) => ("Save Changes?", format!("Closing now loses {} on {}{}. Save first?", unsaved, file, others), "Save"),
+                    // Only pasted images other than this one were edited
+                    _ => ("Close Without Saving?", match pasted {
+                        1 => "Closing now loses the edits of a pasted image, which isn't saved to a file.".to_string(),
+                        n => format!("Closing now loses the edits of {} pasted images, which aren't saved to a file.", n),
+                    }, "Close"),
+                }
+            }
+            Confirmation::LeaveUnsaved { path } => (
+                "Save Changes?",
+                format!(
+                    "Going to {} loses {} on {}. Save first?",
+                    path.file_name().unwrap_or_default().to_string_lossy(),
+                    unsaved.unwrap_or_default(),
+                    self.current_path.as_deref().and_then(Path::file_name).unwrap_or_default().to_string_lossy(),
+                ),
+                "Save",
+            ),
+            Confirmation::Overwrite { path } => (
+                "Overwrite File?",
+                format!("{} already exists. Replace it?", path.file_name().unwrap_or_default().to_string_lossy()),
+                "Replace",
+            ),
+            Confirmation::FlattenAnimation { action, .. } => (
+                "Animated Image",
+                match action {
+                    FlattenAction::Save | FlattenAction::SaveAndClose => "This animation can't be re-encoded in its format. Saving keeps only the first frame.".to_string(),
+                    FlattenAction::Convert(path) => format!(
+                        "{} will contain only the first frame of this animation.",
+                        path.file_name().unwrap_or_default().to_string_lossy()
+                    ),
+                },
+                "Keep First Frame",
+            ),
+            Confirmation::LossyConvert { path, losses, .. } => (
+                "Lossy Conversion",
+                format!(
+                    "{} can't hold everything in this image:\n{}",
+                    path.file_name().unwrap_or_default().to_string_lossy(),
+                    losses.iter().map(|loss| format!("• {}", loss)).collect::<Vec<_>>().join("\n"),
+                ),
+                "Convert Anyway",
+            ),
+            Confirmation::Revert => (
+                "Revert Image?",
+                "Reloading the image from disk discards your unsaved changes.".to_string(),
+                "Revert",
+            ),
+            Confirmation::Delete => {
+                let name = self.current_path.as_deref().and_then(|p| p.file_name()).unwrap_or_default().to_string_lossy();
+                let message = if self.is_image_edited {
+                    format!("Move {} to the trash? Your unsaved changes to it are lost.", name)
+                } else {
+                    format!("Move {} to the trash?", name)
+                };
+                ("Delete Image?", message, "Move to Trash")
+            }
+            Confirmation::ReadOnly { block, .. } => {
+                let path = self.current_path.as_deref().unwrap_or(Path::new(""));
+                let way_out = if *block == export::WriteBlock::ReadOnlyFile {
+                    "Save a copy somewhere else, or make the file writable and save over it?"
+                } else {
+                    "Save a copy somewhere else?"
+                };
+                ("Can't Save Here", format!("Can't save: {}.\n{}", block.describe(path), way_out), "Save a Copy…")
+            }
+        };
+        // A second way to go on besides the confirm button
+        let alternative = match pending {
+            Confirmation::CloseUnsaved if self.converted_copy.is_some() && pasted == 0 => Some("Keep Only the Copy"),
+            Confirmation::CloseUnsaved if !self.is_image_edited => None,
+            Confirmation::CloseUnsaved | Confirmation::LeaveUnsaved { .. } => Some("Discard"),
+            Confirmation::ReadOnly { block: export::WriteBlock::ReadOnlyFile, .. } => Some("Make Writable and Save"),
+            _ => None,
+        };
+
+        let (mut confirmed, mut took_alternative, mut cancelled) = (false, false, false);
+        let window = egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                ui.label(message);
+                if let Confirmation::FlattenAnimation { dont_ask, .. } = pending {
+                    ui.checkbox(dont_ask, "Don't ask again");
+                }
+                if let Confirmation::LossyConvert { loses_alpha: true, .. } = pending {
+                    ui.horizontal(|ui| {
+                        ui.label("Background:");
+                        ui.color_edit_button_srgb(&mut self.config.flatten_background);
+                    });
+                    if let Some((_, texture, _)) = &self.flatten_preview {
+                        ui.add(egui::Image::new(texture).max_size(egui::vec2(FLATTEN_PREVIEW_SIZE, FLATTEN_PREVIEW_SIZE)));
+                    }
+                }
+                ui.horizontal(|ui| {
+                    confirmed = ui.button(confirm_label).clicked();
+                    if let Some(label) = alternative {
+                        took_alternative = ui.button(label).clicked();
+                    }
+                    cancelled = ui.button("Cancel").clicked();
+                });
+            });
+        if let Some(window) = window {
+            dialog::trap_focus(ctx, &window.response);
+        }
+        if self.escaped_dialog == Some(Dialog::Confirmation) {
+            cancelled = true;
+        } else if !confirmed && !took_alternative && !cancelled {
+            confirmed = dialog::enter_pressed(ctx);
+        }
+
+        if cancelled {
+            // Stay on the edited image rather than where the strip was left
+            if let (Some(Confirmation::LeaveUnsaved { .. }), Some(path)) = (self.pending_confirmation.take(), self.current_path.clone()) {
+                self.sync_current_index(&path);
+            }
+            return;
+        }
+        if !confirmed && !took_alternative {
+            return;
+        }
+        match self.pending_confirmation.take() {
+            Some(Confirmation::CloseUnsaved) if confirmed && !self.is_image_edited => {
+                // Only other pasted images were edited, with nothing to save them to
+                self.session.discard_edits();
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+            Some(Confirmation::CloseUnsaved) if confirmed => {
+                let flattens = self.current_path.as_deref().is_some_and(|p| self.flattens_animation(p));
+                if flattens && self.config.confirm_flatten_animation {
+                    self.pending_confirmation = Some(Confirmation::FlattenAnimation { action: FlattenAction::SaveAndClose, dont_ask: false });
+                } else {
+                    self.save_and_close(ctx);
+                }
+            }
+            Some(Confirmation::CloseUnsaved) => {
+                self.is_image_edited = false; // Force close
+                self.session.discard_edits();
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+            Some(Confirmation::LeaveUnsaved { path }) => {
+                if confirmed {
+                    self.save(ctx);
+                }
+                if confirmed && self.is_image_edited {
+                    // Not saved, or asking how first: stay on the image
+                    if let Some(current) = self.current_path.clone() {
+                        self.sync_current_index(&current);
+                    }
+                } else {
+                    self.load_image_and_context(ctx, path);
+                }
+            }
+            Some(Confirmation::Overwrite { path }) => self.write_converted(ctx, &path),
+            Some(Confirmation::LossyConvert { path, loses_alpha, .. }) => {
+                if loses_alpha {
+                    let _ = self.config.save();
+                }
+                self.request_convert(ctx, path, ConvertCheck::Overwrite);
+            }
+            Some(Confirmation::Revert) => self.revert(ctx),
+            Some(Confirmation::Delete) => self.delete_current_image(ctx),
+            Some(Confirmation::ReadOnly { close, .. }) if confirmed => self.save_copy(ctx, close),
+            Some(Confirmation::ReadOnly { close, .. }) => {
+                let Some(path) = &self.current_path else { return };
+                if let Err(e) = export::make_writable(path) {
+                    self.error_message = Some(format!("Failed to make {} writable: {}", path.file_name().unwrap_or_default().to_string_lossy(), e));
+                } else if close {
+                    self.save_and_close(ctx);
+                } else {
+                    self.save(ctx);
+                }
+            }
+            Some(Confirmation::FlattenAnimation { action, dont_ask }) => {
+                if dont_ask {
+                    self.config.confirm_flatten_animation = false;
+                    let _ = self.config.save();
+                }
+                match action {
+                    FlattenAction::Save => self.save(ctx),
+                    FlattenAction::SaveAndClose => self.save_and_close(ctx),
+                    FlattenAction::Convert(path) => self.request_convert(ctx, path, ConvertCheck::Losses),
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+
+impl eframe::App for ImageViewer {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.show(ctx);
+    }
+}
+
+impl ImageViewer {
+    /// Draws the viewer into the space left over by the host's own panels and
+    /// handles its input. Call once per frame.
+    pub fn show(&mut self, ctx: &egui::Context) {
+        // --- Global Style Configuration (Glassy Popups) ---
+        let mut style = (*ctx.style()).clone();
+        style.visuals.window_fill = egui::Color32::from_black_alpha(180);
+        // Apply to non-interactive widgets too if they are used as backgrounds
+        ctx.set_style(style);
+
+        // Calculate delta time for smooth animations
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_frame_time).as_secs_f32();
+        self.last_frame_time = now;
+        self.perf.record_frame(dt);
+        self.poll_dir_scan();
+        if self.dir_scan.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+
+        // After a while without input, dim or run the slideshow until the next input
+        let idle_action = if self.frame_mode { IdleAction::Slideshow } else { self.config.idle_action };
+        let idle_after = (idle_action != IdleAction::Nothing).then(|| Duration::from_secs(self.config.idle_minutes.max(1) as u64 * 60));
+        self.idle.update(ctx, idle_after);
+        if self.idle.is_idle() {
+            ctx.set_cursor_icon(egui::CursorIcon::None);
+            match idle_action {
+                IdleAction::Dim => IdleWatch::paint_dim(ctx),
+                IdleAction::Slideshow => {
+                    let interval = Duration::from_secs(self.config.slideshow_seconds.max(1) as u64);
+                    // Moving on would drop unsaved edits or leave a question unanswered
+                    if self.idle.slideshow_step(ctx, interval) && !self.is_image_edited && self.pending_confirmation.is_none() {
+                        self.next_image(ctx);
+                    }
+                }
+                IdleAction::Nothing => {}
+            }
+        }
+        self.settle_scrub(ctx);
+
+        // Smooth zoom and offset interpolation (120+ FPS capable)
+        let zoom_speed = 15.0; // Higher = faster response
+        let dt_min = (zoom_speed * dt).min(1.0);
+        
+        // Zoom
+        let zoom_diff = self.target_zoom - self.zoom;
+        if zoom_diff.abs() > 0.001 {
+            self.zoom += zoom_diff * dt_min;
+            ctx.request_repaint();
+        } else {
+            self.zoom = self.target_zoom;
+        }
+        
+        // Offset
+        let offset_diff = self.target_offset - self.offset;
+        if offset_diff.length_sq() > 0.001 {
+            self.offset += offset_diff * dt_min;
+            ctx.request_repaint();
+        } else {
+            self.offset = self.target_offset;
+        }
+
+        // Overlay blur crossfade, at the same pace
+        if let Some((_, weight)) = &mut self.fading_blur {
+            *weight -= *weight * dt_min;
+            if *weight > 0.01 {
+                ctx.request_repaint();
+            } else {
+                self.fading_blur = None;
+            }
+        }
+
+        // Edited area outline, fading at a steady pace
+        if let Some((_, opacity)) = &mut self.edit_highlight {
+            *opacity -= dt / EDIT_HIGHLIGHT_SECS;
+            if *opacity > 0.0 {
+                ctx.request_repaint();
+            } else {
+                self.edit_highlight = None;
+            }
+        }
+
+        // Animation playback
+        if let Some(anim) = &mut self.animation {
+            if anim.advance(Duration::from_secs_f32(dt)) {
+                if let Some(texture) = &mut self.texture {
+                    texture.set(anim.current_image().clone(), self.config.display_texture_options());
+                }
+            }
+            if let Some(wait) = anim.time_until_next_frame() {
+                ctx.request_repaint_after(wait);
+            }
+        }
+
+        // Handle pending window resize (multi-frame for Wayland compatibility)
+        if let Some(new_size) = self.pending_resize {
+            self.pending_resize_frame += 1;
+            match self.pending_resize_frame {
+                1 | 5 | 10 => {
+                    // Frame 1, 5, 10: Spam the new size to ensure WM catches it
+                    // We DO NOT touch Resizable state, assuming the window is already floating
+                    // or that Hyprland will accept size hints better this way.
+                    ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(new_size));
+                    ctx.request_repaint();
+                }
+                20 => {
+                    // Frame 20: Done
+                    self.pending_resize = None;
+                    self.pending_resize_frame = 0;
+                    ctx.request_repaint();
+                }
+                _ => {
+                    // Keep animation loop alive during the sequence
+                    if self.pending_resize_frame < 21 {
+                         ctx.request_repaint();
+                    }
+                }
+            }
+        }
+
+
+
+
+        // Open files dropped onto the window
+        let dropped = ctx.input(|i| i.raw.dropped_files.iter().find_map(|f| f.path.clone()));
+        if let Some(path) = dropped {
+            self.open_path(ctx, path);
+        }
+
+        // Escape closes the dialog in front before the shortcuts below see it
+        self.escaped_dialog = self.front_dialog().filter(|_| dialog::take_escape(ctx));
+
+        // Type-ahead takes the typed keys before the shortcuts below see them
+        if self.type_ahead.is_none() && self.front_dialog().is_none() && self.pending_text_pos.is_none() && !ctx.wants_keyboard_input() {
+            self.type_ahead = TypeAhead::start(ctx);
+        }
+        if let Some(type_ahead) = &mut self.type_ahead {
+            match type_ahead.take_input(ctx) {
+                TypeAheadInput::Changed => {
+                    if let Some(idx) = type_ahead.find(&self.image_list) {
+                        self.jump_to(ctx, idx);
+                    }
+                }
+                TypeAheadInput::Done => self.type_ahead = None,
+                TypeAheadInput::None => {}
+            }
+        }
+
+        if ctx.input(|i| i.key_pressed(egui::Key::F12)) {
+            self.perf.visible = !self.perf.visible;
+        }
+
+        if ctx.input(|i| i.modifiers.command && i.modifiers.shift && i.key_pressed(egui::Key::E)) {
+            self.export_view(ctx);
+        }
+
+        // Ctrl+V only reaches us as a text paste, so also try for an image then.
+        // Shift+Insert arrives as a plain key event and works with image-only clipboards.
+        let paste_requested = ctx.input(|i| {
+            i.events.iter().any(|e| matches!(e, egui::Event::Paste(_)))
+                || (i.modifiers.shift && i.key_pressed(egui::Key::Insert))
+        });
+        if paste_requested && !ctx.wants_keyboard_input() {
+            self.paste_image(ctx);
+        }
+
+        if ctx.input(|i| i.modifiers.command && i.key_pressed(egui::Key::O)) {
+            self.open_file_dialog(ctx);
+        }
+
+        // Keyboard navigation (the gallery handles its own arrows, and in a modal
+        // dialog they move focus)
+        if self.gallery.is_none() && !self.front_dialog().is_some_and(Dialog::is_modal) {
+            if ctx.input(|i| i.key_pressed(egui::Key::ArrowRight)) {
+                self.next_image(ctx);
+            }
+            if ctx.input(|i| i.key_pressed(egui::Key::ArrowLeft)) {
+                self.prev_image(ctx);
+            }
+        }
+
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(egui::Key::S) && i.modifiers.is_none()) {
+            self.gallery = None;
+            self.toggle_strip_view(ctx);
+        }
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(egui::Key::G) && i.modifiers.is_none()
+            || self.gallery.is_some() && i.key_pressed(egui::Key::Escape))
+        {
+            self.toggle_gallery(ctx);
+        }
+
+        // Drawing mode toggle, ignored while typing into a text field
+        if !ctx.wants_keyboard_input() && self.pending_text_pos.is_none() && self.strip_view.is_none() {
+            if ctx.input(|i| i.key_pressed(egui::Key::D) && i.modifiers.is_none()) {
+                self.set_drawing_mode(!self.is_drawing_mode);
+            } else if self.is_drawing_mode && ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+                self.set_drawing_mode(false);
+            }
+        }
+
+        if !ctx.wants_keyboard_input() && ctx.input(|i| (i.key_pressed(egui::Key::F) || i.key_pressed(egui::Key::Num0)) && i.modifiers.is_none()) {
+            self.fit_to_window(ctx.screen_rect().size());
+        }
+
+        // Single-axis fit modes
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(egui::Key::W) && !i.modifiers.command) {
+            let available = ctx.screen_rect().size();
+            if ctx.input(|i| i.modifiers.shift) {
+                self.fit_to_height(available);
+            } else {
+                self.fit_to_width(available);
+            }
+        }
+
+        // Quit. Sent as a regular close request so unsaved edits still get the
+        // confirmation below instead of being dropped.
+        let quit = ctx.input(|i| {
+            (i.modifiers.command && i.key_pressed(egui::Key::Q))
+                || (i.modifiers.is_none() && i.key_pressed(egui::Key::Q))
+        });
+        if quit && !self.embedded && (!ctx.wants_keyboard_input() || ctx.input(|i| i.modifiers.command)) {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+
+        if ctx.input(|i| i.modifiers.command && i.key_pressed(egui::Key::Z)) {
+            if ctx.input(|i| i.modifiers.shift) {
+                self.redo(ctx);
+            } else {
+                self.undo(ctx);
+            }
+        }
+        if ctx.input(|i| i.modifiers.command && i.key_pressed(egui::Key::H)) {
+            self.show_history = !self.show_history;
+        }
+
+        if self.is_image_edited && ctx.input(|i| i.modifiers.command && i.key_pressed(egui::Key::S)) {
+            self.request_save(ctx);
+        }
+
+        if !ctx.wants_keyboard_input() && self.gallery.is_none() && self.strip_view.is_none()
+            && ctx.input(|i| i.key_pressed(egui::Key::Backspace) && i.modifiers.is_none())
+        {
+            self.request_revert(ctx);
+        }
+        if !ctx.wants_keyboard_input() && self.gallery.is_none() && self.strip_view.is_none()
+            && ctx.input(|i| i.key_pressed(egui::Key::Delete) && i.modifiers.is_none())
+        {
+            self.request_delete(ctx);
+        }
+
+        // Each press shows more detail, then hides the panel again
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(egui::Key::I) && i.modifiers.is_none()) {
+            (self.show_info_panel, self.info_expanded) = match (self.show_info_panel, self.info_expanded) {
+                (false, _) => (true, false),
+                (true, false) => (true, true),
+                (true, true) => (false, false),
+            };
+        }
+
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(egui::Key::L) && i.modifiers.is_none()) {
+            self.show_loupe = !self.show_loupe;
+        }
+        if !ctx.wants_keyboard_input() && self.gallery.is_none() && self.strip_view.is_none() {
+            if let Some(shift) = ctx.input(|i| i.key_pressed(egui::Key::R).then_some(i.modifiers.shift).filter(|_| !i.modifiers.command && !i.modifiers.alt)) {
+                self.rotate(ctx, !shift);
+            }
+            if ctx.input(|i| i.key_pressed(egui::Key::H) && i.modifiers.is_none()) {
+                self.flip_horizontal(ctx);
+            }
+            if ctx.input(|i| i.key_pressed(egui::Key::V) && i.modifiers.is_none()) {
+                self.flip_vertical(ctx);
+            }
+        }
+        if !ctx.wants_keyboard_input() && self.gallery.is_none() && self.strip_view.is_none()
+            && ctx.input(|i| i.key_pressed(egui::Key::T) && i.modifiers.is_none())
+        {
+            self.filmstrip = match self.filmstrip {
+                Some(_) => None,
+                None => Some(Filmstrip::default()),
+            };
+        }
+
+        // Keyboard zoom keeps whatever is at the window center in place
+        if !ctx.wants_keyboard_input() && self.gallery.is_none() && self.strip_view.is_none() {
+            let (zoom_in, zoom_out) = ctx.input(|i| {
+                let plain = !i.modifiers.command && !i.modifiers.alt;
+                (
+                    plain && (i.key_pressed(egui::Key::Plus) || i.key_pressed(egui::Key::Equals)),
+                    plain && i.key_pressed(egui::Key::Minus),
+                )
+            });
+            if zoom_in {
+                self.set_zoom_centered(self.target_zoom * ZOOM_STEP);
+            } else if zoom_out {
+                self.set_zoom_centered(self.target_zoom / ZOOM_STEP);
+            }
+        }
+
+        // C closes the comparison, or opens one against the clipboard
+        if !ctx.wants_keyboard_input()
+            && ctx.input(|i| i.key_pressed(egui::Key::C) && i.modifiers.is_none())
+            && self.compare_panel.take().is_none()
+        {
+            self.compare_with_clipboard(ctx);
+        }
+
+        // Frame stepping for animations
+        if !ctx.wants_keyboard_input() {
+            if let Some(anim) = &mut self.animation {
+                let (back, forward) = ctx.input(|i| (i.key_pressed(egui::Key::Comma), i.key_pressed(egui::Key::Period)));
+                if back || forward {
+                    anim.step(forward);
+                    self.show_animation_frame();
+                }
+            }
+        }
+
+        // Stacking order of the selected drawing: Ctrl+] / Ctrl+[, with Shift for front/back
+        if self.is_drawing_mode && self.selected_drawing.is_some() {
+            let reorder = ctx.input(|i| {
+                if !i.modifiers.command {
+                    return None;
+                }
+                match (i.key_pressed(egui::Key::CloseBracket), i.key_pressed(egui::Key::OpenBracket), i.modifiers.shift) {
+                    (true, _, true) => Some(Reorder::Front),
+                    (true, _, false) => Some(Reorder::Forward),
+                    (_, true, false) => Some(Reorder::Backward),
+                    (_, true, true) => Some(Reorder::Back),
+                    _ => None,
+                }
+            });
+            if let Some(to) = reorder {
+                self.reorder_selected(to);
+            }
+        }
+
+        let edited = self.is_image_edited || self.session.edited_elsewhere() > 0;
+        if ctx.input(|i| i.viewport().close_requested()) && edited && !self.embedded {
+            if self.config.confirm_close_unsaved {
+                ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+                self.pending_confirmation = Some(Confirmation::CloseUnsaved);
+            } else {
+                // Configured to discard unsaved drawings; let the close go through
+                self.is_image_edited = false;
+                self.session.discard_edits();
+            }
+        }
+
+        self.show_confirmation(ctx);
+
+        if self.show_info_panel {
+            if self.info_expanded {
+                self.update_file_hash(ctx);
+                self.read_details();
+            }
+            if let Some(meta) = &self.metadata {
+                let mut open = true;
+                egui::Window::new("Image Info")
+                    .collapsible(false)
+                    .resizable(false)
+                    .open(&mut open)
+                    .show(ctx, |ui| {
+                        egui::Grid::new("info_grid").striped(true).show(ui, |ui| {
+                            ui.label("Filename:"); ui.label(&meta.filename); ui.end_row();
+                            ui.label("Resolution:"); ui.label(&meta.resolution); ui.end_row();
+                            ui.label("Size:"); ui.label(&meta.file_size); ui.end_row();
+                            ui.label("Format:"); ui.label(&meta.format); ui.end_row();
+                            ui.label("Modified:"); ui.label(&meta.modified); ui.end_row();
+                            if let Some(frames) = meta.frame_count {
+                                ui.label("Frames:"); ui.label(frames.to_string()); ui.end_row();
+                            }
+                            if let Some(loops) = meta.loop_count {
+                                ui.label("Loops:"); ui.label(loops.to_string()); ui.end_row();
+                            }
+                            let camera = [
+                                ("Camera:", &meta.camera_model),
+                                ("Taken:", &meta.taken),
+                                ("Shutter speed:", &meta.shutter_speed),
+                                ("Aperture:", &meta.aperture),
+                                ("ISO:", &meta.iso),
+                                ("Focal length:", &meta.focal_length),
+                            ];
+                            for (label, value) in camera {
+                                if let Some(value) = value {
+                                    ui.label(label); ui.label(value); ui.end_row();
+                                }
+                            }
+                            if !self.info_expanded {
+                                return;
+                            }
+                            if let Some((x, y)) = meta.details.as_ref().and_then(|d| d.dpi) {
+                                let dpi = if (x - y).abs() < 0.5 {
+                                    format!("{} dpi", self.locale.number(x, 0))
+                                } else {
+                                    format!("{} x {} dpi", self.locale.number(x, 0), self.locale.number(y, 0))
+                                };
+                                ui.label("Print resolution:"); ui.label(dpi); ui.end_row();
+                            }
+                            for (label, value) in meta.details.iter().flat_map(|d| &d.exif) {
+                                ui.label(format!("{}:", label)); ui.label(value); ui.end_row();
+                            }
+                            if self.file_hash.is_some() {
+                                ui.label("SHA-256:");
+                                ui.horizontal(|ui| {
+                                    ui.spinner();
+                                    ui.label("Computing…");
+                                });
+                                ui.end_row();
+                            } else if let Some(digest) = &meta.sha256 {
+                                ui.label("SHA-256:");
+                                match &digest.hex {
+                                    Ok(hex) => {
+                                        ui.horizontal(|ui| {
+                                            ui.monospace(format!("{}…", &hex[..16])).on_hover_text(hex);
+                                            if ui.small_button("📋").on_hover_text("Copy the full hash").clicked() {
+                                                ui.ctx().copy_text(hex.clone());
+                                            }
+                                        });
+                                    }
+                                    Err(e) => {
+                                        ui.label("Unavailable").on_hover_text(e);
+                                    }
+                                }
+                                ui.end_row();
+                            }
+                        });
+                        if let Some(details) = meta.details.as_ref().filter(|_| self.info_expanded) {
+                            ui.add_space(6.0);
+                            details.histogram.show(ui, egui::vec2(256.0, 80.0));
+                        }
+                        let toggle = if self.info_expanded { "Less" } else { "More" };
+                        if ui.small_button(toggle).on_hover_text("Toggle details (I)").clicked() {
+                            self.info_expanded = !self.info_expanded;
+                        }
+                        if let Some(anim) = &mut self.animation {
+                            if anim.loop_count != LoopCount::Infinite {
+                                ui.checkbox(&mut anim.loop_anyway, "Loop anyway");
+                            }
+                        }
+                    });
+                if !open || self.escaped_dialog == Some(Dialog::Info) {
+                    self.show_info_panel = false;
+                }
+            }
+        }
+
+        if self.show_settings {
+            let mut open = true;
+            let mut changed = false;
+            egui::Window::new("Settings")
+                .collapsible(false)
+                .resizable(false)
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    egui::Grid::new("settings_grid").num_columns(2).show(ui, |ui| {
+                        if self.blur_picker.get_or_insert_with(|| BlurPicker::new(&self.config))
+                            .show(ui, self.current_image.as_ref(), self.texture.as_ref(), &mut self.config)
+                        {
+                            self.update_blur_texture(ctx);
+                            changed = true;
+                        }
+
+                        ui.label("Zoomed in:");
+                        ui.horizontal(|ui| {
+                            for filter in config::TextureFilter::ALL {
+                                if ui.selectable_value(&mut self.config.zoom_in_filter, filter, filter.label()).changed() {
+                                    self.update_texture_from_image(ctx);
+                                    self.show_animation_frame();
+                                    changed = true;
+                                }
+                            }
+                        })
+                        .response
+                        .on_hover_text("How the image looks above 100%. Sharp keeps pixel art crisp");
+                        ui.end_row();
+
+                        ui.label("Zoomed out:");
+                        ui.horizontal(|ui| {
+                            for filter in config::TextureFilter::ALL {
+                                if ui.selectable_value(&mut self.config.zoom_out_filter, filter, filter.label()).changed() {
+                                    self.update_texture_from_image(ctx);
+                                    self.show_animation_frame();
+                                    changed = true;
+                                }
+                            }
+                        })
+                        .response
+                        .on_hover_text("How the image looks below 100%");
+                        ui.end_row();
+
+                        ui.label("Thumbnails:");
+                        ui.horizontal(|ui| {
+                            for filter in config::TextureFilter::ALL {
+                                if ui.selectable_value(&mut self.config.thumbnail_filter, filter, filter.label()).changed() {
+                                    self.thumbnails.set_options(self.config.thumbnail_texture_options());
+                                    self.update_blur_texture(ctx);
+                                    changed = true;
+                                }
+                            }
+                        })
+                        .response
+                        .on_hover_text("Sampling of gallery thumbnails and the overlay blur");
+                        ui.end_row();
+
+                        ui.label("JPEG quality:");
+                        changed |= ui.add(egui::Slider::new(&mut self.config.jpeg_quality, 1..=100)).changed();
+                        ui.end_row();
+
+                        ui.label("JPEG chroma:");
+                        ui.horizontal(|ui| {
+                            for mode in export::ChromaSubsampling::ALL {
+                                changed |= ui.selectable_value(&mut self.config.jpeg_subsampling, mode, mode.label()).changed();
+                            }
+                        })
+                        .response
+                        .on_hover_text("4:4:4 keeps colored text crisp; 4:2:0 gives smaller photos");
+                        ui.end_row();
+
+                        ui.label("PNG:");
+                        changed |= ui.checkbox(&mut self.config.png_indexed, "Indexed when possible")
+                            .on_hover_text("Save images with up to 256 colors as a palette, falling back to RGBA")
+                            .changed();
+                        ui.end_row();
+
+                        ui.label("Drawings:");
+                        changed |= ui.checkbox(&mut self.config.embed_drawings, "Embed in saved PNGs")
+                            .on_hover_text("Keep drawings editable when the file is opened here again. Other viewers show them burned in")
+                            .changed();
+                        ui.end_row();
+
+                        ui.label("JPEG background:");
+                        changed |= ui.color_edit_button_srgb(&mut self.config.flatten_background)
+                            .on_hover_text("What transparent areas become when saving or converting to JPEG")
+                            .changed();
+                        ui.end_row();
+
+                        if !self.embedded {
+                            ui.label("Window:");
+                            let auto = format!("Automatic ({})", WindowMode::Auto.resolve().label());
+                            let selected = if self.config.window_mode == WindowMode::Auto { auto.clone() } else { self.config.window_mode.label().to_string() };
+                            egui::ComboBox::from_id_salt("window_mode_combo")
+                                .selected_text(selected)
+                                .show_ui(ui, |ui| {
+                                    changed |= ui.selectable_value(&mut self.config.window_mode, WindowMode::Auto, auto).changed();
+                                    for mode in &WindowMode::ALL[1..] {
+                                        changed |= ui.selectable_value(&mut self.config.window_mode, *mode, mode.label()).changed();
+                                    }
+                                })
+                                .response
+                                .on_hover_text("Floating fits the window to each image and makes tiling compositors float it. Applies on the next start");
+                            ui.end_row();
+                        }
+
+                        ui.label("Open at:");
+                        ui.horizontal(|ui| {
+                            for zoom in OpenZoom::ALL {
+                                changed |= ui.selectable_value(&mut self.config.open_zoom, zoom, zoom.label())
+                                    .on_hover_text(zoom.hint())
+                                    .changed();
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Corner info:");
+                        ui.horizontal(|ui| {
+                            changed |= ui.checkbox(&mut self.config.show_hud, "Show")
+                                .on_hover_text("Keep the chosen details in a corner of the window, without hovering")
+                                .changed();
+                            ui.add_enabled_ui(self.config.show_hud, |ui| {
+                                egui::ComboBox::from_id_salt("hud_corner_combo")
+                                    .selected_text(self.config.hud_corner.label())
+                                    .show_ui(ui, |ui| {
+                                        for corner in HudCorner::ALL {
+                                            changed |= ui.selectable_value(&mut self.config.hud_corner, corner, corner.label()).changed();
+                                        }
+                                    });
+                            });
+                        });
+                        ui.end_row();
+
+                        ui.label("");
+                        ui.add_enabled_ui(self.config.show_hud, |ui| {
+                            ui.horizontal(|ui| {
+                                let items = &mut self.config.hud_items;
+                                changed |= ui.checkbox(&mut items.name, "Name").changed();
+                                changed |= ui.checkbox(&mut items.position, "Position").on_hover_text("Place in the folder, as 3 / 40").changed();
+                                changed |= ui.checkbox(&mut items.zoom, "Zoom").changed();
+                                changed |= ui.checkbox(&mut items.resolution, "Size").on_hover_text("Width and height in pixels").changed();
+                            });
+                        });
+                        ui.end_row();
+
+                        ui.label("When idle:");
+                        ui.horizontal(|ui| {
+                            for action in IdleAction::ALL {
+                                changed |= ui.selectable_value(&mut self.config.idle_action, action, action.label()).changed();
+                            }
+                            changed |= ui.add_enabled(
+                                    self.config.idle_action != IdleAction::Nothing,
+                                    egui::DragValue::new(&mut self.config.idle_minutes).range(1..=240).prefix("after ").suffix(" min"),
+                                )
+                                .on_hover_text("Any input wakes the viewer")
+                                .changed();
+                        });
+                        ui.end_row();
+
+                        ui.label("Slideshow:");
+                        changed |= ui.add(egui::DragValue::new(&mut self.config.slideshow_seconds).range(1..=3600).suffix(" s per image"))
+                            .on_hover_text("When idle, and in frame mode (--frame)")
+                            .changed();
+                        ui.end_row();
+
+                        ui.label("Mouse:");
+                        ui.horizontal(|ui| {
+                            changed |= ui.checkbox(&mut self.config.click_to_advance, "Click to advance")
+                                .on_hover_text("Left click shows the next image, right click the previous. Dragging still pans")
+                                .changed();
+                            changed |= ui.checkbox(&mut self.config.scroll_pages, "Scroll through pages")
+                                .on_hover_text("Scroll pans up and down; keep scrolling past the edge for the next or previous image. Ctrl+scroll zooms")
+                                .changed();
+                        });
+                        ui.end_row();
+
+                        ui.label("Convert:");
+                        changed |= ui.checkbox(&mut self.config.open_after_convert, "Show the converted file")
+                            .on_hover_text("Switch to the new file after converting instead of staying on the original")
+                            .changed();
+                        ui.end_row();
+
+                        ui.label("Scroll zoom:");
+                        ui.horizontal(|ui| {
+                            changed |= ui.add(egui::Slider::new(&mut self.config.scroll_zoom_step, config::SCROLL_ZOOM_STEPS)
+                                    .custom_formatter(|step, _| format!("{:.0}%", (step - 1.0) * 100.0))
+                                    .custom_parser(|text| text.trim().trim_end_matches('%').trim().parse::<f64>().ok().map(|percent| 1.0 + percent / 100.0)))
+                                .on_hover_text("Zoom change per scroll notch. Lower it for trackpads")
+                                .changed();
+                            changed |= ui.checkbox(&mut self.config.invert_scroll_zoom, "Invert")
+                                .on_hover_text("Scroll down to zoom in")
+                                .changed();
+                        });
+                        ui.end_row();
+
+                        ui.label("Loupe zoom:");
+                        ui.horizontal(|ui| {
+                            for &mag in config::LOUPE_MAGNIFICATIONS {
+                                changed |= ui.selectable_value(&mut self.config.loupe_magnification, mag, format!("{}x", mag)).changed();
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Edits:");
+                        changed |= ui.checkbox(&mut self.config.highlight_edits, "Highlight the changed area")
+                            .on_hover_text("Briefly outline the pixels an applied adjustment changed")
+                            .changed();
+                        ui.end_row();
+
+                        ui.label("Drawing:");
+                        ui.horizontal(|ui| {
+                            for tool in DrawingTool::ALL {
+                                changed |= ui.selectable_value(&mut self.config.drawing_tool, tool, tool.label()).changed();
+                            }
+                        })
+                        .response
+                        .on_hover_text("Tool selected at startup");
+                        ui.end_row();
+
+                        ui.label("");
+                        ui.horizontal(|ui| {
+                            changed |= ui.color_edit_button_srgb(&mut self.config.drawing_color)
+                                .on_hover_text("Color at startup")
+                                .changed();
+                            changed |= ui.add(egui::Slider::new(&mut self.config.drawing_size, 1.0..=50.0).text("Size"))
+                                .on_hover_text("Pencil and shape size at startup")
+                                .changed();
+                        });
+                        ui.end_row();
+
+                        ui.label("");
+                        changed |= ui.checkbox(&mut self.config.start_in_drawing_mode, "Start in drawing mode")
+                            .on_hover_text("Open straight into drawing with the tool above, for quick markup")
+                            .changed();
+                        ui.end_row();
+
+                        ui.label("Pencil spacing:");
+                        changed |= ui.add(egui::Slider::new(&mut self.config.pencil_min_distance, 0.0..=config::MAX_PENCIL_MIN_DISTANCE).suffix(" px"))
+                            .on_hover_text("Least distance in image pixels between recorded pencil points. Higher keeps long strokes light; 0 records every move")
+                            .changed();
+                        ui.end_row();
+
+                        ui.label("Undo steps:");
+                        ui.horizontal(|ui| {
+                            let images = ui.add(egui::DragValue::new(&mut self.config.undo_image_snapshots).range(0..=config::MAX_UNDO_IMAGE_SNAPSHOTS))
+                                .on_hover_text("Pixel edits that can be undone. Each keeps a copy of the whole image in memory");
+                            ui.label("pixel edits");
+                            let drawings = ui.add(egui::DragValue::new(&mut self.config.undo_drawing_steps).range(0..=config::MAX_UNDO_DRAWING_STEPS))
+                                .on_hover_text("Drawing steps that can be undone. These are cheap");
+                            ui.label("drawings");
+                            if images.changed() || drawings.changed() {
+                                changed = true;
+                                self.history.trim(&self.config);
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Max image size:");
+                        ui.horizontal(|ui| {
+                            for &limit in config::MAX_DIMENSION_CHOICES {
+                                let label = if limit == 0 { "Off".to_string() } else { limit.to_string() };
+                                changed |= ui.selectable_value(&mut self.config.max_image_dimension, limit, label).changed();
+                            }
+                        })
+                        .response
+                        .on_hover_text("Larger images are shrunk after decoding to save memory. Applies to the next image.");
+                        ui.end_row();
+
+                        ui.label("Locale:");
+                        let system = format!("System ({})", Locale::resolve("").name);
+                        let selected = if self.config.locale.is_empty() { system.clone() } else { self.config.locale.clone() };
+                        let before = self.config.locale.clone();
+                        egui::ComboBox::from_id_salt("locale_combo")
+                            .selected_text(selected)
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.config.locale, String::new(), system);
+                                for &name in locale::LOCALE_CHOICES {
+                                    ui.selectable_value(&mut self.config.locale, name.to_string(), name);
+                                }
+                            })
+                            .response
+                            .on_hover_text("Separators and date order for file sizes, dates and zoom");
+                        if self.config.locale != before {
+                            self.locale = Locale::resolve(&self.config.locale);
+                            if let (Some(path), Some(meta)) = (&self.current_path, &self.metadata) {
+                                let (file_size, modified) = self.file_details(path);
+                                self.metadata = Some(ImageMetadata { file_size, modified, ..meta.clone() });
+                            }
+                            changed = true;
+                        }
+                        ui.end_row();
+
+                        ui.label("Confirm:");
+                        ui.vertical(|ui| {
+                            changed |= ui.checkbox(&mut self.config.confirm_close_unsaved, "Closing with unsaved changes")
+                                .on_hover_text("Also when leaving the strip on another image. When off, unsaved drawings are discarded")
+                                .changed();
+                            changed |= ui.checkbox(&mut self.config.confirm_overwrite, "Overwriting files").changed();
+                            changed |= ui.checkbox(&mut self.config.confirm_flatten_animation, "Dropping animation frames").changed();
+                            changed |= ui.checkbox(&mut self.config.confirm_lossy_convert, "Conversions that lose data")
+                                .on_hover_text("Transparency, colors or bit depth the target format can't keep")
+                                .changed();
+                            changed |= ui.checkbox(&mut self.config.confirm_revert, "Reverting unsaved changes").changed();
+                            changed |= ui.checkbox(&mut self.config.confirm_delete, "Moving files to the trash").changed();
+                        });
+                        ui.end_row();
+                    });
+                });
+            if changed {
+                let _ = self.config.save();
+            }
+            if !open || self.escaped_dialog == Some(Dialog::Settings) {
+                self.show_settings = false;
+            }
+        } else {
+            self.blur_picker = None;
+        }
+
+        if let Some(panel) = &mut self.adjust_panel {
+            let mut presets_changed = false;
+            let action = panel.show(ctx, &mut self.config.adjust_presets, &mut presets_changed);
+            if presets_changed {
+                let _ = self.config.save();
+            }
+            match action {
+                Some(AdjustAction::Apply(adjustments)) => self.apply_image_edit(ctx, "Color adjustment", |img| adjustments.apply(img)),
+                Some(AdjustAction::Close) => self.adjust_panel = None,
+                None => {}
+            }
+        }
+
+        if self.show_history {
+            self.show_history_panel(ctx);
+        }
+
+        if let (Some(dialog), Some(img)) = (&mut self.resize_dialog, &self.current_image) {
+            match dialog.show(ctx, img, self.escaped_dialog == Some(Dialog::Resize)) {
+                Some(ResizeAction::Apply { width, height, filter }) => {
+                    self.resize_dialog = None;
+                    self.resize_image(ctx, width, height, filter.filter_type());
+                }
+                Some(ResizeAction::Close) => self.resize_dialog = None,
+                None => {}
+            }
+        }
+
+        if self.batch_job.is_some() {
+            self.show_batch_progress(ctx);
+        }
+
+        if let Some(panel) = &mut self.compare_panel {
+            match panel.show(ctx) {
+                Some(CompareAction::Reload) => self.compare_with_clipboard(ctx),
+                Some(CompareAction::Close) => self.compare_panel = None,
+                None => {}
+            }
+        }
+
+        // Share Modal
+        if self.show_share_modal {
+            // Poll events from share manager
+            let mut advance = false;
+            if let Some(ref mgr) = self.share_manager {
+                for event in mgr.poll_events() {
+                    self.send_queue.apply(&event);
+                    match event {
+                        ShareEvent::PeerDiscovered { fingerprint, device: _, addr: _ } => {
+                            // UI will just read from get_peers()
+                            self.share_status = Some(format!("Found: {}", mgr.display_name(&fingerprint)));
+                        }
+                        ShareEvent::PeerLost { fingerprint: _ } => {}
+                        ShareEvent::TransferQueued { .. } => {}
+                        ShareEvent::TransferStarted { peer_fingerprint, .. } => {
+                            self.share_status = Some(format!("Sending to {}...", mgr.display_name(&peer_fingerprint)));
+                        }
+                        ShareEvent::TransferComplete { peer_fingerprint, file_path, .. } => {
+                            self.share_status = Some(format!("Sent to {}", mgr.display_name(&peer_fingerprint)));
+                            advance |= self.config.advance_after_send && self.current_path.as_ref() == Some(&file_path);
+                        }
+                        ShareEvent::TransferFailed { peer_fingerprint, file_path, error, .. } => {
+                            let name = file_path.file_name().unwrap_or_default().to_string_lossy();
+                            self.share_status = Some(format!("Failed to send {} to {}: {}", name, mgr.display_name(&peer_fingerprint), error));
+                        }
+                        ShareEvent::Error(e) => {
+                            self.share_status = Some(format!("Error: {}", e));
+                        }
+                    }
+                }
+            }
+            if advance {
+                self.next_image(ctx);
+            }
+
+            let mut open = true;
+            egui::Window::new("Share via LocalSend")
+                .collapsible(false)
+                .resizable(false)
+                .open(&mut open)
+                .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+                .show(ctx, |ui| {
+                    // Files marked in the gallery, otherwise the image shown
+                    let marked: Vec<PathBuf> = self.gallery.as_ref()
+                        .map(|gallery| gallery.marked.iter().filter_map(|&idx| self.image_list.get(idx).cloned()).collect())
+                        .unwrap_or_default();
+                    let to_send = if marked.is_empty() { self.current_path.iter().cloned().collect() } else { marked };
+                    match to_send.len() {
+                        0 => ui.label("Open an image, or mark some in the gallery, to send it."),
+                        1 => ui.label("Select a device to send the current image:"),
+                        n => ui.label(format!("Select a device to send the {} marked images:", n)),
+                    };
+                    ui.add_space(10.0);
+
+                    if let Some(ref mgr) = self.share_manager {
+                        let peers = mgr.get_peers();
+                        if peers.is_empty() {
+                            ui.spinner();
+                            ui.label("Scanning for devices...");
+                        } else {
+                            // Sort by name so the list doesn't reshuffle between frames
+                            let names = peer_display_names(&peers);
+                            let mut entries: Vec<_> = peers.iter().collect();
+                            entries.sort_by(|a, b| names[a.0].cmp(&names[b.0]));
+
+                            egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                                for (fingerprint, (_addr, info)) in entries {
+                                    let label = format!("{} ({:?})", names[fingerprint], info.device_type);
+                                    if ui.add_enabled(!to_send.is_empty(), egui::Button::new(&label)).clicked() {
+                                        if let Err(e) = mgr.send_files(fingerprint, to_send.clone()) {
+                                            self.share_status = Some(format!("Error: {}", e));
+                                        }
+                                    }
+                                }
+                            });
+                        }
+                    } else {
+                        ui.label("Share service not available.");
+                    }
+
+                    if ui.checkbox(&mut self.config.advance_after_send, "Show the next image after sending")
+                        .on_hover_text("For presenting: send, and the viewer moves on by itself")
+                        .changed()
+                    {
+                        let _ = self.config.save();
+                    }
+
+                    if !self.send_queue.is_empty() {
+                        ui.add_space(10.0);
+                        self.show_send_queue(ui);
+                    }
+
+                    ui.add_space(10.0);
+                    if let Some(status) = &self.share_status {
+                        ui.label(egui::RichText::new(status).italics().color(egui::Color32::LIGHT_GRAY));
+                    }
+                });
+            if !open || self.escaped_dialog == Some(Dialog::Share) {
+                self.show_share_modal = false;
+            }
+            
+            // Keep repainting while modal is open to update peer list
+            ctx.request_repaint();
+        }
+
+        // --- Overlay UI Logic ---
+        // Calculate all positions and hover states BEFORE rendering any Areas
+        // This prevents egui Areas from "stealing" hover state and causing flicker
+        
+        let screen_rect = ctx.screen_rect();
+        let mouse_pos = ctx.input(|i| i.pointer.hover_pos());
+        let anim_speed = 12.0 * dt; // Faster animation for smoother feel
+        
+        // Pre-calculate image rect for blur effects (used by all overlays)
+        let image_rect = self.texture.as_ref().map(|tex| {
+            let size = tex.size_vec2() * self.zoom;
+            egui::Rect::from_center_size(
+                (screen_rect.center().to_vec2() + self.offset).to_pos2(),
+                size
+            )
+        });
+        
+        // --- Top Bar Hover Logic ---
+        let top_bar_height = 40.0;
+        let top_area = if self.is_drawing_mode { 110.0 } else { top_bar_height };
+        
+        let zoom_entry_id = egui::Id::new("zoom_entry");
+        let is_popup_open = ctx.memory(|m| {
+            m.is_popup_open(egui::Id::new("convert_popup")) || m.is_popup_open(egui::Id::new("recent_popup"))
+                || m.has_focus(zoom_entry_id)
+        });
+        let hovering_top = mouse_pos.is_some_and(|p| p.y <= top_area && screen_rect.contains(p));
+        // Keyboard users: F6 jumps into the top bar, and it stays up while it holds focus
+        if ctx.input(|i| i.key_pressed(egui::Key::F6)) {
+            self.focus_toolbar = true;
+        }
+        let top_bar_focused = self.focus_toolbar || ctx.memory(|m| m.focused())
+            .and_then(|id| ctx.read_response(id))
+            .is_some_and(|r| r.layer_id.id == egui::Id::new("top_bar"));
+
+        
+        // Overlays stay hidden while idle, even under a resting pointer
+        let idle = self.idle.is_idle();
+        if (hovering_top || self.is_drawing_mode || is_popup_open || top_bar_focused) && !idle {
+            self.top_bar_opacity = (self.top_bar_opacity + anim_speed).min(1.0);
+        } else {
+            self.top_bar_opacity = (self.top_bar_opacity - anim_speed * 0.5).max(0.0); // Slower fade out
+        }
+        if self.top_bar_opacity > 0.0 && self.top_bar_opacity < 1.0 { ctx.request_repaint(); }
+        
+        // --- Arrow Hover Logic ---
+        let arrow_zone_width = 60.0;
+        
+        let hovering_left = !idle && mouse_pos.is_some_and(|p| {
+            p.x <= arrow_zone_width && p.y > top_area && screen_rect.contains(p)
+        });
+        let hovering_right = !idle && mouse_pos.is_some_and(|p| {
+            p.x >= screen_rect.width() - arrow_zone_width && p.y > top_area && screen_rect.contains(p)
+        });
+        
+        if hovering_left {
+            self.left_arrow_opacity = (self.left_arrow_opacity + anim_speed).min(1.0);
+        } else {
+            self.left_arrow_opacity = (self.left_arrow_opacity - anim_speed).max(0.0);
+        }
+        if self.left_arrow_opacity > 0.0 && self.left_arrow_opacity < 1.0 { ctx.request_repaint(); }
+        
+        if hovering_right {
+            self.right_arrow_opacity = (self.right_arrow_opacity + anim_speed).min(1.0);
+        } else {
+            self.right_arrow_opacity = (self.right_arrow_opacity - anim_speed).max(0.0);
+        }
+        if self.right_arrow_opacity > 0.0 && self.right_arrow_opacity < 1.0 { ctx.request_repaint(); }
+        
+        // --- Render Top Bar ---
+        if self.top_bar_opacity > 0.0 {
+            let top_rect = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(screen_rect.width(), top_bar_height));
+            
+            egui::Area::new(egui::Id::new("top_bar"))
+                .fixed_pos(egui::Pos2::ZERO)
+                .order(egui::Order::Foreground)
+                .interactable(true)
+                .show(ctx, |ui| {
+                    // Paint blur gradient (fades down)
+                    if let (Some(blur_tex), Some(img_rect)) = (&self.blurred_texture, image_rect) {
+                        blur::paint_gradient(ui.painter(), top_rect, self.top_bar_opacity, blur_tex, img_rect, "down");
+                        // The previous image's blur fades out on top
+                        if let Some((old_tex, weight)) = &self.fading_blur {
+                            blur::paint_gradient(ui.painter(), top_rect, self.top_bar_opacity * weight, old_tex, img_rect, "down");
+                        }
+                    }
+                    
+                    // UI content
+                    ui.allocate_new_ui(egui::UiBuilder::new().max_rect(top_rect.shrink(10.0)), |ui| {
+                        ui.horizontal_centered(|ui| {
+                            if let Some(path) = &self.current_path {
+                                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                                let col = egui::Color32::WHITE.linear_multiply(self.top_bar_opacity);
+                                ui.label(egui::RichText::new(name).size(16.0).strong().color(col));
+                                if self.is_image_edited {
+                                    ui.label(egui::RichText::new("Edited").italics().color(egui::Color32::LIGHT_GRAY.linear_multiply(self.top_bar_opacity)));
+                                }
+                            }
+                            
+                            // Right-aligned using last frame's width, but laid out left to right
+                            // so Tab walks the controls in reading order
+                            let width_id = egui::Id::new("toolbar_width");
+                            let toolbar_width = ui.data(|d| d.get_temp::<f32>(width_id)).unwrap_or(0.0);
+                            ui.add_space((ui.available_width() - toolbar_width).max(0.0));
+                            let toolbar = ui.horizontal(|ui| {
+                                let btn_size = egui::vec2(24.0, 24.0);
+                                let tint = egui::Color32::WHITE.linear_multiply(self.top_bar_opacity);
+
+                                // Zoom entry: shows the live zoom, accepts a typed percentage
+                                let editing = ui.memory(|m| m.has_focus(zoom_entry_id));
+                                if !editing {
+                                    self.zoom_entry = self.locale.percent(self.target_zoom * 100.0);
+                                }
+                                let resp = ui.add(
+                                    egui::TextEdit::singleline(&mut self.zoom_entry)
+                                        .id(zoom_entry_id)
+                                        .desired_width(52.0)
+                                        .horizontal_align(egui::Align::Center)
+                                        .text_color(tint),
+                                ).on_hover_text("Zoom (type a percentage, Enter to apply)");
+                                if resp.lost_focus() && !ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                                    if let Some(zoom) = parse_zoom_entry(&self.zoom_entry, &self.locale) {
+                                        self.set_zoom_centered(zoom);
+                                    }
+                                }
+
+                                ui.separator();
+
+                                // Recent Files
+                                let resp = ui.add(egui::Button::new(egui::RichText::new("🕘").size(16.0).color(tint)).frame(false).min_size(btn_size))
+                                    .on_hover_text("Recent Files");
+                                resp.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, "Recent Files"));
+                                if std::mem::take(&mut self.focus_toolbar) { resp.request_focus(); }
+                                if resp.clicked() { ui.ctx().memory_mut(|m| m.open_popup(egui::Id::new("recent_popup"))); }
+                                egui::popup::popup_below_widget(ui, egui::Id::new("recent_popup"), &resp, egui::PopupCloseBehavior::CloseOnClickOutside, |ui| {
+                                    ui.set_min_width(200.0);
+                                    if self.config.recent_files.is_empty() {
+                                        ui.label("No recent files");
+                                    }
+                                    for path in self.config.recent_files.clone() {
+                                        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                                        if ui.button(name).on_hover_text(path.display().to_string()).clicked() {
+                                            self.open_recent(ctx, path);
+                                            ui.close_menu();
+                                        }
+                                    }
+                                    ui.separator();
+                                    if ui.button("Open...").clicked() { ui.close_menu(); self.open_file_dialog(ctx); }
+                                });
+
+                                // Settings
+                                let resp = ui.add(egui::Button::new(egui::RichText::new("⚙").size(16.0).color(tint)).frame(false).min_size(btn_size))
+                                    .on_hover_text("Settings");
+                                resp.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, "Settings"));
+                                if resp.clicked() { self.show_settings = !self.show_settings; }
+
+                                // Share via LocalSend
+                                let icon = egui::include_image!("../materials/share.png");
+                                if icon_button(ui, icon, "Share", tint, btn_size)
+                                    .on_hover_text("Share via LocalSend").clicked() {
+                                    // Initialize share manager if not already done
+                                    if self.share_manager.is_none() {
+                                        match ShareManager::new() {
+                                            Ok(mgr) => self.share_manager = Some(mgr),
+                                            Err(e) => self.share_status = Some(format!("Error: {}", e)),
+                                        }
+                                    }
+                                    self.show_share_modal = true;
+                                }
+
+                                // Info
+                                let icon = egui::include_image!("../materials/info.svg");
+                                if icon_button(ui, icon, "Info", tint, btn_size)
+                                    .on_hover_text("Image Info").clicked() { self.show_info_panel = !self.show_info_panel; }
+
+                                // Rotate
+                                let icon = egui::include_image!("../materials/rotate.png");
+                                if icon_button(ui, icon, "Rotate", tint, btn_size)
+                                    .on_hover_text("Rotate Right (R)").clicked() { self.rotate(ctx, true); }
+                                let resp = ui.add(egui::Button::new(egui::RichText::new("🔄").size(16.0).color(tint)).frame(false).min_size(btn_size))
+                                    .on_hover_text("Rotate Left (Shift+R)");
+                                resp.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, "Rotate Left"));
+                                if resp.clicked() { self.rotate(ctx, false); }
+
+                                // Flip
+                                let resp = ui.add(egui::Button::new(egui::RichText::new("↔").size(16.0).color(tint)).frame(false).min_size(btn_size))
+                                    .on_hover_text("Flip Horizontally (H)");
+                                resp.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, "Flip Horizontally"));
+                                if resp.clicked() { self.flip_horizontal(ctx); }
+                                let resp = ui.add(egui::Button::new(egui::RichText::new("↕").size(16.0).color(tint)).frame(false).min_size(btn_size))
+                                    .on_hover_text("Flip Vertically (V)");
+                                resp.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, "Flip Vertically"));
+                                if resp.clicked() { self.flip_vertical(ctx); }
+
+                                // Revert
+                                let resp = ui.add_enabled(
+                                    self.current_path.is_some(),
+                                    egui::Button::new(egui::RichText::new("⏪").size(16.0).color(tint)).frame(false).min_size(btn_size),
+                                ).on_hover_text("Revert to Saved (Backspace)");
+                                resp.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, "Revert to Saved"));
+                                if resp.clicked() { self.request_revert(ctx); }
+
+                                // Loupe
+                                let resp = ui.add(egui::Button::new(egui::RichText::new("🔍").size(16.0).color(tint)).frame(false).min_size(btn_size).selected(self.show_loupe))
+                                    .on_hover_text("Magnifier (L)");
+                                resp.widget_info(|| egui::WidgetInfo::selected(egui::WidgetType::Checkbox, true, self.show_loupe, "Magnifier"));
+                                if resp.clicked() { self.show_loupe = !self.show_loupe; }
+
+                                // Color adjustments
+                                let resp = ui.add(egui::Button::new(egui::RichText::new("🎨").size(16.0).color(tint)).frame(false).min_size(btn_size))
+                                    .on_hover_text("Adjust Colors");
+                                resp.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, "Adjust Colors"));
+                                if resp.clicked() {
+                                    self.adjust_panel = match (&self.adjust_panel, &self.current_image) {
+                                        (None, Some(img)) => Some(AdjustPanel::new(img, self.config.display_texture_options())),
+                                        _ => None,
+                                    };
+                                }
+
+                                // Undo history
+                                let resp = ui.add(egui::Button::new(egui::RichText::new("🕘").size(16.0).color(tint)).frame(false).min_size(btn_size))
+                                    .on_hover_text("History (Ctrl+H)");
+                                resp.widget_info(|| egui::WidgetInfo::selected(egui::WidgetType::Checkbox, true, self.show_history, "History"));
+                                if resp.clicked() {
+                                    self.show_history = !self.show_history;
+                                }
+
+                                // Compare with the clipboard
+                                let resp = ui.add(egui::Button::new(egui::RichText::new("◑").size(16.0).color(tint)).frame(false).min_size(btn_size))
+                                    .on_hover_text("Compare with Clipboard (C)");
+                                resp.widget_info(|| egui::WidgetInfo::selected(egui::WidgetType::Checkbox, true, self.compare_panel.is_some(), "Compare with Clipboard"));
+                                if resp.clicked() && self.compare_panel.take().is_none() {
+                                    self.compare_with_clipboard(ctx);
+                                }
+
+                                // Convert
+                                let icon = egui::include_image!("../materials/convert2.svg");
+                                let resp = icon_button(ui, icon, "Convert", tint, btn_size)
+                                    .on_hover_text("Convert Image");
+                                if resp.clicked() { ui.ctx().memory_mut(|m| m.open_popup(egui::Id::new("convert_popup"))); }
+                                egui::popup::popup_below_widget(ui, egui::Id::new("convert_popup"), &resp, egui::PopupCloseBehavior::CloseOnClickOutside, |ui| {
+                                    ui.set_min_width(100.0);
+                                    if ui.button("to JPG").clicked() { self.convert_image(ctx, image::ImageFormat::Jpeg); ui.close_menu(); }
+                                    if ui.button("to PNG").clicked() { self.convert_image(ctx, image::ImageFormat::Png); ui.close_menu(); }
+                                    ui.separator();
+                                    if ui.button("Resize...").on_hover_text("Change the image's pixel dimensions").clicked() {
+                                        self.resize_dialog = self.current_image.as_ref().map(|img| ResizeDialog::new(img.width(), img.height()));
+                                        ui.close_menu();
+                                    }
+                                    if ui.button("Export view...").on_hover_text("Save the visible region (Ctrl+Shift+E)").clicked() {
+                                        self.export_view(ctx);
+                                        ui.close_menu();
+                                    }
+                                    ui.separator();
+                                    ui.label("Copy as data URL:");
+                                    ui.horizontal(|ui| {
+                                        if ui.button("PNG").on_hover_text("Lossless, with transparency").clicked() {
+                                            self.copy_data_url(ctx, image::ImageFormat::Png);
+                                            ui.close_menu();
+                                        }
+                                        if ui.button("JPEG").on_hover_text("Smaller; transparency is flattened").clicked() {
+                                            self.copy_data_url(ctx, image::ImageFormat::Jpeg);
+                                            ui.close_menu();
+                                        }
+                                    });
+                                    if ui.add(egui::Slider::new(&mut self.config.data_url_quality, 1..=100).text("JPEG quality")).changed() {
+                                        let _ = self.config.save();
+                                    }
+                                    #[cfg(feature = "pdf")]
+                                    if ui.button("Export PDF...").on_hover_text("One page with the drawings and file details").clicked() {
+                                        self.export_pdf();
+                                        ui.close_menu();
+                                    }
+                                });
+
+                                ui.separator();
+
+                                // Drawing Toggle
+                                let icon = if self.is_drawing_mode {
+                                    egui::include_image!("../materials/pencil-filled.svg")
+                                } else {
+                                    egui::include_image!("../materials/pencil-unfilled.svg")
+                                };
+                                let caption = if self.is_drawing_mode { "Stop" } else { "Draw" };
+                                if icon_button(ui, icon, caption, tint, btn_size)
+                                    .on_hover_text(if self.is_drawing_mode { "Stop Drawing (Esc)" } else { "Toggle Drawing (D)" })
+                                    .clicked() { self.set_drawing_mode(!self.is_drawing_mode); }
+                            });
+                            ui.data_mut(|d| d.insert_temp(width_id, toolbar.response.rect.width()));
+                        });
+                    });
+                    
+                    // Drawing tools
+                    if self.is_drawing_mode {
+                        let tools_rect = egui::Rect::from_min_size(egui::pos2(0.0, 60.0), egui::vec2(screen_rect.width(), 50.0));
+                        ui.painter().rect_filled(tools_rect, 0.0, egui::Color32::from_black_alpha((180.0 * self.top_bar_opacity) as u8));
+                        
+                        ui.allocate_new_ui(egui::UiBuilder::new().max_rect(tools_rect.shrink(5.0)), |ui| {
+                            egui::ScrollArea::horizontal().show(ui, |ui| {
+                                ui.horizontal_centered(|ui| {
+                                    for tool in DrawingTool::ALL {
+                                        ui.selectable_value(&mut self.drawing_settings.tool, tool, tool.label());
+                                    }
+                                    ui.separator();
+                                    
+                                    let colors = [(egui::Color32::RED, "Red"), (egui::Color32::GREEN, "Green"), (egui::Color32::BLUE, "Blue"),
+                                                  (egui::Color32::YELLOW, "Yellow"), (egui::Color32::BLACK, "Black"), (egui::Color32::WHITE, "White")];
+                                    for (c, name) in colors {
+                                        let mut b = egui::Button::new("   ").fill(c);
+                                        let selected = self.drawing_settings.color == c;
+                                        if selected { b = b.stroke(egui::Stroke::new(2.0, egui::Color32::WHITE)); }
+                                        let resp = ui.add(b).on_hover_text(name);
+                                        resp.widget_info(|| egui::WidgetInfo::selected(egui::WidgetType::SelectableLabel, true, selected, name));
+                                        if resp.clicked() { self.drawing_settings.color = c; }
+                                    }
+                                    
+                                    ui.separator();
+                                    match self.drawing_settings.tool {
+                                        DrawingTool::Pencil => {
+                                            ui.add(egui::Slider::new(&mut self.drawing_settings.size, 1.0..=50.0).text("Size"));
+                                            ui.checkbox(&mut self.drawing_settings.taper, "Taper")
+                                                .on_hover_text("Vary width with pen pressure, or with speed when there is none");
+                                        }
+                                        DrawingTool::Shape => {
+                                            ui.selectable_value(&mut self.drawing_settings.shape, ShapeType::Rectangle, "Rect");
+                                            ui.selectable_value(&mut self.drawing_settings.shape, ShapeType::Circle, "Circle");
+                                            ui.selectable_value(&mut self.drawing_settings.shape, ShapeType::Line, "Line");
+                                            ui.add(egui::Slider::new(&mut self.drawing_settings.size, 1.0..=20.0).text("Thickness"));
+                                        }
+                                        DrawingTool::Text => {
+                                            ui.add(egui::Slider::new(&mut self.drawing_settings.font_size, 10.0..=100.0).text("Size"));
+                                            ui.selectable_value(&mut self.drawing_settings.font_family, FontFamily::Proportional, "Sans");
+                                            ui.selectable_value(&mut self.drawing_settings.font_family, FontFamily::Monospace, "Mono");
+                                            ui.checkbox(&mut self.drawing_settings.font_bold, "Bold");
+                                        }
+                                        DrawingTool::Select => {
+                                            ui.add_enabled_ui(self.selected_drawing.is_some(), |ui| {
+                                                let actions = [
+                                                    (Reorder::Front, "Bring to Front", "Ctrl+Shift+]"),
+                                                    (Reorder::Forward, "Forward", "Ctrl+]"),
+                                                    (Reorder::Backward, "Backward", "Ctrl+["),
+                                                    (Reorder::Back, "Send to Back", "Ctrl+Shift+["),
+                                                ];
+                                                for (to, label, keys) in actions {
+                                                    if ui.button(label).on_hover_text(keys).clicked() {
+                                                        self.reorder_selected(to);
+                                                    }
+                                                }
+                                            });
+                                        }
+                                    }
+                                });
+                            });
+                        });
+                    }
+                });
+        }
+        
+        // --- Drawing Mode Indicator ---
+        // Thin border plus a corner badge, painted on a non-interactive layer so it never eats input
+        if self.is_drawing_mode {
+            let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("drawing_indicator")));
+            let accent = egui::Color32::from_rgb(255, 140, 170);
+            painter.rect_stroke(screen_rect.shrink(1.5), 0.0, egui::Stroke::new(3.0, accent));
+
+            let badge_text = "✏ Drawing — Esc to exit";
+            let galley = painter.layout_no_wrap(badge_text.to_string(), egui::FontId::proportional(12.0), egui::Color32::WHITE);
+            let badge_rect = egui::Rect::from_min_size(
+                egui::pos2(10.0, screen_rect.bottom() - galley.size().y - 18.0),
+                galley.size() + egui::vec2(12.0, 8.0),
+            );
+            painter.rect_filled(badge_rect, 4.0, accent.gamma_multiply(0.8));
+            painter.galley(badge_rect.min + egui::vec2(6.0, 4.0), galley, egui::Color32::WHITE);
+        }
+
+        // --- Render Navigation Arrows ---
+        // Arrows: vertical gradient blur strips on left/right edges
+        let arrow_strip_width = 50.0;
+        let arrow_strip_height = 100.0;
+        let arrow_y = (screen_rect.height() - arrow_strip_height) / 2.0;
+        
+        // Left Arrow
+        if self.left_arrow_opacity > 0.0 {
+            let left_rect = egui::Rect::from_min_size(egui::pos2(0.0, arrow_y), egui::vec2(arrow_strip_width, arrow_strip_height));
+            
+            egui::Area::new(egui::Id::new("left_arrow"))
+                .fixed_pos(left_rect.min)
+                .order(egui::Order::Foreground)
+                .interactable(true)
+                .show(ctx, |ui| {
+                    // Paint blur gradient (fades right)
+
+                    
+                    // Chevron
+                    let center = left_rect.center();
+                    let s = 16.0;
+                    let col = egui::Color32::WHITE.linear_multiply(self.left_arrow_opacity);
+                    ui.painter().add(egui::Shape::line(vec![
+                        egui::pos2(center.x + s * 0.3, center.y - s * 0.5),
+                        egui::pos2(center.x - s * 0.3, center.y),
+                        egui::pos2(center.x + s * 0.3, center.y + s * 0.5),
+                    ], egui::Stroke::new(2.5, col)));
+                    
+                    let response = ui.allocate_rect(left_rect, egui::Sense::click());
+                    response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, "Previous image"));
+                    if response.clicked() {
+                        self.prev_image(ctx);
+                    }
+                });
+        }
+        
+        // Right Arrow
+        if self.right_arrow_opacity > 0.0 {
+            let right_rect = egui::Rect::from_min_size(
+                egui::pos2(screen_rect.width() - arrow_strip_width, arrow_y),
+                egui::vec2(arrow_strip_width, arrow_strip_height)
+            );
+            
+            egui::Area::new(egui::Id::new("right_arrow"))
+                .fixed_pos(right_rect.min)
+                .order(egui::Order::Foreground)
+                .interactable(true)
+                .show(ctx, |ui| {
+                    // Paint blur gradient (fades left)
+
+                    
+                    // Chevron
+                    let center = right_rect.center();
+                    let s = 16.0;
+                    let col = egui::Color32::WHITE.linear_multiply(self.right_arrow_opacity);
+                    ui.painter().add(egui::Shape::line(vec![
+                        egui::pos2(center.x - s * 0.3, center.y - s * 0.5),
+                        egui::pos2(center.x + s * 0.3, center.y),
+                        egui::pos2(center.x - s * 0.3, center.y + s * 0.5),
+                    ], egui::Stroke::new(2.5, col)));
+                    
+                    let response = ui.allocate_rect(right_rect, egui::Sense::click());
+                    response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, "Next image"));
+                    if response.clicked() {
+                        self.next_image(ctx);
+                    }
+                });
+        }
+
+        let mut loupe_at = None;
+        let mut advance = None;
+        egui::CentralPanel::default().frame(egui::Frame::none().inner_margin(0.0).outer_margin(0.0)).show(ctx, |ui| {
+            ui.spacing_mut().item_spacing = egui::vec2(0.0, 0.0);
+            ui.spacing_mut().window_margin = egui::Margin::ZERO;
+            if let Some(strip) = &mut self.strip_view {
+                strip.show(ui, &self.image_list);
+                self.current_index = strip.top_index;
+                return;
+            }
+
+            if let Some(gallery) = &mut self.gallery {
+                match gallery.show(ui, &self.image_list, &mut self.thumbnails, self.batch_job.is_some()) {
+                    Some(GalleryAction::Open(idx)) => {
+                        self.gallery = None;
+                        self.current_index = idx;
+                        let path = self.image_list[idx].clone();
+                        self.load_image_and_context(ctx, path);
+                    }
+                    Some(GalleryAction::Transform(indices, transform)) => {
+                        let mut paths: Vec<PathBuf> = indices.iter().filter_map(|&idx| self.image_list.get(idx).cloned()).collect();
+                        // An edited current image is turned in place instead: rewriting its file
+                        // would be undone by the next save of the old pixels
+                        let current = self.current_path.as_deref().and_then(dir_scan::list_key).filter(|_| self.is_image_edited);
+                        if let Some(current) = current.filter(|current| paths.contains(current)) {
+                            paths.retain(|path| *path != current);
+                            self.transform(ctx, transform);
+                        }
+                        if !paths.is_empty() {
+                            self.batch_job = Some(BatchJob::start(paths, transform, self.config.clone()));
+                        }
+                    }
+                    None => {}
+                }
+                return;
+            }
+
+            if let Some(err) = self.error_message.clone() {
+                self.show_error_state(ui, &err);
+                return;
+            }
+
+            if self.texture.is_none() {
+                self.show_empty_state(ui);
+                return;
+            }
+
+            if let Some(texture) = &self.texture {
+                let available_size = ui.available_size();
+                let image_size = texture.size_vec2();
+                
+                // Zoom is absolute: 1.0 = native resolution (1 image pixel = 1 screen pixel)
+                // Can zoom out (< 1.0) or zoom in (> 1.0)
+                let display_size = image_size * self.zoom;
+
+                // Handle Input
+                // SENSE: If drawing, only sense clicks/hovers to avoid panning consuming the drag
+                let sense = if self.is_drawing_mode {
+                    egui::Sense::click()
+                } else if self.config.click_to_advance {
+                    egui::Sense::click_and_drag()
+                } else {
+                    egui::Sense::drag()
+                };
+                let (rect, response) = ui.allocate_exact_size(available_size, sense);
+
+                // Zoom with scroll (smooth animated, centered on mouse). While drawing, plain
+                // scroll sizes the brush instead and Ctrl+scroll zooms.
+                let scroll_delta = ctx.input(|i| i.raw_scroll_delta.y);
+                let sizes_brush = self.is_drawing_mode
+                    && self.drawing_settings.tool != DrawingTool::Select
+                    && !ctx.input(|i| i.modifiers.command);
+                if scroll_delta != 0.0 && sizes_brush {
+                    self.drawing_settings.scroll_size(scroll_delta);
+                    self.brush_feedback_until = Some(Instant::now() + Duration::from_secs(1));
+                } else if scroll_delta != 0.0 && self.config.scroll_pages && !ctx.input(|i| i.modifiers.command) {
+                    // Pan within the image. Once at the edge, scrolling on builds up until
+                    // it is clearly meant to turn the page, not a flick that overshot.
+                    let overflow = ((image_size.y * self.target_zoom - rect.height()) / 2.0).max(0.0);
+                    let current = self.target_offset.y;
+                    // A view dragged past the edge isn't pulled back
+                    let panned = (current + scroll_delta).clamp(current.min(-overflow), current.max(overflow));
+                    let past_edge = current + scroll_delta - panned;
+                    self.target_offset.y = panned;
+
+                    if past_edge == 0.0 {
+                        self.edge_scroll = None;
+                    } else {
+                        let before = match self.edge_scroll {
+                            Some((pushed, at)) if at.elapsed() < EDGE_SCROLL_PAUSE && (pushed > 0.0) == (past_edge > 0.0) => pushed,
+                            _ => 0.0,
+                        };
+                        let pushed = before + past_edge;
+                        // Only on crossing the threshold, so one long scroll turns one page
+                        if before.abs() < EDGE_SCROLL_RESISTANCE && pushed.abs() >= EDGE_SCROLL_RESISTANCE {
+                            // Scrolling down, past the bottom, goes to the next image
+                            advance = Some(pushed < 0.0);
+                        }
+                        self.edge_scroll = Some((pushed, Instant::now()));
+                    }
+                    ctx.request_repaint();
+                } else if scroll_delta != 0.0 {
+                    let old_zoom = self.target_zoom;
+                    let step = self.config.scroll_zoom_step.clamp(*config::SCROLL_ZOOM_STEPS.start(), *config::SCROLL_ZOOM_STEPS.end());
+                    
+                    if (scroll_delta > 0.0) != self.config.invert_scroll_zoom {
+                        self.target_zoom *= step;
+                    } else {
+                        self.target_zoom /= step;
+                    }
+                    self.target_zoom = self.target_zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+                    
+                    // Zoom towards the cursor when it is over the image (where it will be once
+                    // the current animation settles), otherwise around the window center
+                    let target_rect = egui::Rect::from_center_size(
+                        rect.center() + self.target_offset,
+                        image_size * old_zoom,
+                    );
+                    let anchor = ctx.input(|i| i.pointer.hover_pos())
+                        .filter(|&pos| target_rect.contains(pos))
+                        .map(|pos| pos - rect.center());
+                    self.target_offset = zoom_anchor_offset(self.target_offset, self.target_zoom / old_zoom, anchor);
+                    
+                    ctx.request_repaint();
+                }
+
+                // Drag/Pan
+                if response.dragged() {
+                     // Update both for immediate response + keeping target in sync
+                     self.target_offset += response.drag_delta();
+                     self.offset += response.drag_delta();
+                }
+
+                // Presentation clicks. egui only reports a click if the pointer stayed put,
+                // so dragging still pans.
+                if !self.is_drawing_mode && self.config.click_to_advance {
+                    if response.clicked() {
+                        advance = Some(true);
+                    } else if response.secondary_clicked() {
+                        advance = Some(false);
+                    }
+                }
+
+                // Center logic
+                let mut screen_center = rect.center().to_vec2();
+                // Apply visual offset (interpolated)
+                screen_center += self.offset;
+
+                let image_rect = egui::Rect::from_center_size(screen_center.to_pos2(), display_size);
+
+                // --- Input Handling for Drawing ---
+                // We need to handle input BEFORE painting the image if we want to consume clicks, 
+                // but we need the image_rect to map coordinates.
+                if self.is_drawing_mode {
+                     let pointer_pos = ctx.input(|i| i.pointer.hover_pos());
+                     
+                     // Only draw if within image bounds
+                     if let Some(pos) = pointer_pos {
+                         if image_rect.contains(pos) {
+                             // Map screen pos to image space (0,0 to width,height)
+                             let rel_x = (pos.x - image_rect.min.x) / self.zoom;
+                             let rel_y = (pos.y - image_rect.min.y) / self.zoom;
+                             let image_pos = egui::pos2(rel_x, rel_y);
+                             
+                             if self.drawing_settings.tool == DrawingTool::Select {
+                                 if ctx.input(|i| i.pointer.primary_pressed()) {
+                                     // Topmost object under the cursor, with a few screen pixels of slack
+                                     let tolerance = 4.0 / self.zoom;
+                                     self.selected_drawing = self.drawings.iter()
+                                         .rposition(|d| d.hit_test(image_pos, tolerance, ctx));
+                                 }
+                             } else if ctx.input(|i| i.pointer.primary_down()) {
+                                 // Start or Continue Stroke
+                                 if self.drawing_settings.tool == DrawingTool::Text {
+                                     // Text is click-to-place, not drag
+                                     // Logic handled in released or clicked
+                                 } else {
+                                     if self.current_stroke.is_none() {
+                                         // Start new stroke
+                                         // Determine type
+                                         let shape_type = if self.drawing_settings.tool == DrawingTool::Shape {
+                                             Some(self.drawing_settings.shape)
+                                         } else {
+                                             None
+                                         };
+                                         
+                                         let widths = if self.drawing_settings.tool == DrawingTool::Pencil && self.drawing_settings.taper {
+                                             vec![self.drawing_settings.size * taper_factor(ctx)]
+                                         } else {
+                                             Vec::new()
+                                         };
+
+                                         self.current_stroke = Some(DrawingObject {
+                                             tool: self.drawing_settings.tool,
+                                             points: vec![image_pos],
+                                             color: self.drawing_settings.color,
+                                             size: self.drawing_settings.size,
+                                             shape_type,
+                                             text: None,
+                                             font_family: None,
+                                             font_bold: false,
+                                             widths,
+                                         });
+                                         self.is_image_edited = true;
+                                         self.converted_copy = None;
+                                     } else {
+                                         // Update stroke
+                                         if let Some(stroke) = &mut self.current_stroke {
+                                              match stroke.tool {
+                                                  DrawingTool::Pencil => {
+                                                      // Freehand: append points, skipping those too close to
+                                                      // the last to change the line
+                                                      let min_distance = self.config.pencil_min_distance;
+                                                      if stroke.points.last().is_none_or(|&last| last != image_pos && last.distance(image_pos) >= min_distance) {
+                                                          stroke.points.push(image_pos);
+                                                          if let Some(&prev) = stroke.widths.last() {
+                                                              // Smooth so the width doesn't jitter between samples
+                                                              let target = stroke.size * taper_factor(ctx);
+                                                              stroke.widths.push(prev * 0.7 + target * 0.3);
+                                                          }
+                                                      }
+                                                  }
+                                                  DrawingTool::Shape => {
+                                                      // Shape: Update end point (points[1])
+                                                      // points[0] is start, points[1] is current end
+                                                      if stroke.points.len() == 1 {
+                                                          stroke.points.push(image_pos);
+                                                      } else {
+                                                          stroke.points[1] = image_pos;
+                                                      }
+                                                  }
+                                                  _ => {}
+                                              }
+                                         }
+                                     }
+                                 }
+                             } else if ctx.input(|i| i.pointer.any_released()) {
+                                 // Mouse released
+                                 if self.drawing_settings.tool == DrawingTool::Text {
+                                     if ctx.input(|i| i.pointer.primary_released()) {
+                                        // Open Text Popup, snapped to the guides unless Alt is held
+                                        let pos = if ctx.input(|i| i.modifiers.alt) {
+                                            image_pos
+                                        } else {
+                                            snap_to_guides(image_pos, image_size, TEXT_SNAP_DISTANCE / self.zoom).0
+                                        };
+                                        self.pending_text_pos = Some(pos);
+                                        self.text_entry_string.clear();
+                                     }
+                                 } else {
+                                     // Commit stroke
+                                     if let Some(mut stroke) = self.current_stroke.take() {
+                                         // Thinning may have skipped the last few points, so end where the pointer did
+                                         if stroke.tool == DrawingTool::Pencil && stroke.points.last() != Some(&image_pos) {
+                                             stroke.points.push(image_pos);
+                                             if let Some(&last) = stroke.widths.last() {
+                                                 stroke.widths.push(last);
+                                             }
+                                         }
+                                         let label = stroke.label();
+                                         self.drawings.push(stroke);
+                                         self.history.record(Edit::Drawing { label }, &self.config);
+                                     }
+                                 }
+                             }
+                         }
+                     }
+                }
+
+                // Text Popup
+                let mut text_to_commit = None;
+                if let Some(pos) = self.pending_text_pos {
+                    // Convert to screen space for popup positioning
+                    let screen_pos = egui::pos2(
+                        image_rect.min.x + pos.x * self.zoom,
+                        image_rect.min.y + pos.y * self.zoom
+                    );
+                    
+                    let mut open = true;
+                    let mut should_close = self.escaped_dialog == Some(Dialog::Text);
+                    let window = egui::Window::new("Add Text")
+                        .fixed_pos(screen_pos)
+                        .collapsible(false)
+                        .resizable(false)
+                        .order(egui::Order::Foreground)
+                        .open(&mut open)
+                        .show(ctx, |ui| {
+                           let entry = ui.text_edit_singleline(&mut self.text_entry_string);
+                           // Focus the field unless Tab moved on to the buttons
+                           if ui.memory(|m| m.focused().is_none()) {
+                               entry.request_focus();
+                           }
+                           // Pasted text can span lines, but annotations are drawn on one
+                           if entry.changed() && self.text_entry_string.contains(['\n', '\r']) {
+                               self.text_entry_string = self.text_entry_string.split(['\n', '\r'])
+                                   .filter(|line| !line.is_empty())
+                                   .collect::<Vec<_>>()
+                                   .join(" ");
+                           }
+                           if ui.button("Add").clicked() || ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                               if !self.text_entry_string.is_empty() {
+                                   text_to_commit = Some(DrawingObject {
+                                       tool: DrawingTool::Text,
+                                       points: vec![pos],
+                                       color: self.drawing_settings.color,
+                                       size: self.drawing_settings.font_size, // Use font size here
+                                       shape_type: None,
+                                       text: Some(self.text_entry_string.clone()),
+                                       font_family: Some(self.drawing_settings.font_family),
+                                       font_bold: self.drawing_settings.font_bold,
+                                       widths: Vec::new(),
+                                   });
+                               }
+                               // Close
+                               should_close = true; 
+                           }
+                        });
+                    if let Some(window) = window {
+                        dialog::trap_focus(ctx, &window.response);
+                    }
+                    
+                    if !open || should_close {
+                        self.pending_text_pos = None;
+                    }
+                }
+                
+                if let Some(obj) = text_to_commit {
+                    let label = obj.label();
+                    self.drawings.push(obj);
+                    self.history.record(Edit::Drawing { label }, &self.config);
+                    self.is_image_edited = true;
+                    self.converted_copy = None;
+                    self.pending_text_pos = None;
+                }
+
+                // Paint Image
+                let painter = ui.painter_at(rect);
+                // While adjusting, the downscaled preview stands in for the image
+                let shown = self.adjust_panel.as_ref().and_then(|panel| panel.preview.as_ref()).unwrap_or(texture);
+                painter.image(
+                    shown.id(),
+                    image_rect,
+                    egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                    egui::Color32::WHITE
+                );
+                // The comparison layer shares the image's top-left corner and scale
+                if let Some(panel) = &self.compare_panel {
+                    let (layer, size, tint) = panel.layer();
+                    painter.image(
+                        layer.id(),
+                        egui::Rect::from_min_size(image_rect.min, size * self.zoom),
+                        egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                        tint,
+                    );
+                }
+
+                // Paint Drawings
+                let mut shapes = Vec::new();
+                // Helper to map image space to screen space
+                let to_screen = |p: egui::Pos2| -> egui::Pos2 {
+                    egui::pos2(
+                        image_rect.min.x + p.x * self.zoom,
+                        image_rect.min.y + p.y * self.zoom
+                    )
+                };
+
+                let mut paint_object = |drawing: &DrawingObject| {
+                    match drawing.tool {
+                        DrawingTool::Pencil => {
+                            if drawing.points.len() >= 2 && drawing.widths.is_empty() {
+                                let screen_points: Vec<egui::Pos2> = drawing.points.iter().map(|&p| to_screen(p)).collect();
+                                shapes.push(egui::Shape::line(screen_points, egui::Stroke::new(drawing.size * self.zoom, drawing.color)));
+                            } else if drawing.points.len() >= 2 {
+                                // Tapered: per-segment widths, with round joints so segments blend
+                                for i in 0..drawing.points.len() - 1 {
+                                    let (start, end) = (to_screen(drawing.points[i]), to_screen(drawing.points[i + 1]));
+                                    let width = drawing.segment_width(i) * self.zoom;
+                                    shapes.push(egui::Shape::line_segment([start, end], egui::Stroke::new(width, drawing.color)));
+                                    shapes.push(egui::Shape::circle_filled(end, width / 2.0, drawing.color));
+                                }
+                            }
+                        },
+                        DrawingTool::Shape => {
+                             if drawing.points.len() >= 2 {
+                                 let start = to_screen(drawing.points[0]);
+                                 let end = to_screen(drawing.points[1]);
+                                 let stroke = egui::Stroke::new(drawing.size * self.zoom, drawing.color);
+                                 
+                                 if let Some(stype) = drawing.shape_type {
+                                     match stype {
+                                         ShapeType::Rectangle => {
+                                             let rect = egui::Rect::from_two_pos(start, end);
+                                             shapes.push(egui::Shape::rect_stroke(rect, 0.0, stroke));
+                                         },
+                                         ShapeType::Circle => {
+                                             let center = start;
+                                             let radius = start.distance(end);
+                                             shapes.push(egui::Shape::circle_stroke(center, radius, stroke));
+                                         },
+                                         ShapeType::Line => {
+                                             shapes.push(egui::Shape::line_segment([start, end], stroke));
+                                         }
+                                     }
+                                 }
+                             }
+                        },
+                        DrawingTool::Text => {
+                             if let Some(text) = &drawing.text {
+                                 if let Some(pos) = drawing.points.first() {
+                                     let screen_pos = to_screen(*pos);
+                                     
+                                     let family = match drawing.font_family.unwrap_or(FontFamily::Proportional) {
+                                         FontFamily::Proportional => egui::FontFamily::Proportional,
+                                         FontFamily::Monospace => egui::FontFamily::Monospace,
+                                     };
+                                     
+                                     let font_id = egui::FontId::new(drawing.size * self.zoom, family);
+                                     
+                                     // Helper to paint text. Goes through `shapes` so it stacks
+                                     // in drawing order with everything else.
+                                     let mut paint_text_at = |offset: egui::Vec2| {
+                                         shapes.push(ctx.fonts(|f| egui::Shape::text(
+                                             f,
+                                             screen_pos + offset,
+                                             egui::Align2::LEFT_TOP,
+                                             text,
+                                             font_id.clone(),
+                                             drawing.color
+                                         )));
+                                     };
+                                     
+                                     if drawing.font_bold {
+                                         // Simulated bold: draw multiple times with slight offsets
+                                         paint_text_at(egui::vec2(-2.0, 0.0));
+                                         paint_text_at(egui::vec2(2.0, 0.0));
+                                         paint_text_at(egui::vec2(0.0, -2.0));
+                                         paint_text_at(egui::vec2(0.0, 2.0));
+                                         
+                                         // Add diagonals for "Level 2" thickness
+                                         paint_text_at(egui::vec2(-1.4, -1.4));
+                                         paint_text_at(egui::vec2(1.4, -1.4));
+                                         paint_text_at(egui::vec2(-1.4, 1.4));
+                                         paint_text_at(egui::vec2(1.4, 1.4));
+                                     }
+                                     paint_text_at(egui::vec2(0.0, 0.0));
+                                 }
+                             }
+                        },
+                        DrawingTool::Select => {}
+                    }
+                };
+
+                // 1. Committed Drawings
+                for drawing in &self.drawings {
+                     paint_object(drawing);
+                }
+
+                
+                // 2. Current Stroke
+                if let Some(stroke) = &self.current_stroke {
+                     paint_object(stroke);
+                }
+
+                // Selection outline
+                if let Some(selected) = self.selected_drawing.and_then(|i| self.drawings.get(i)) {
+                    if self.is_drawing_mode && self.drawing_settings.tool == DrawingTool::Select {
+                        let bounds = selected.bounds(ctx);
+                        let outline = egui::Rect::from_min_max(to_screen(bounds.min), to_screen(bounds.max)).expand(3.0);
+                        shapes.push(egui::Shape::rect_stroke(outline, 2.0, egui::Stroke::new(1.5, egui::Color32::from_rgb(255, 140, 170))));
+                    }
+                }
+
+                // Area changed by the last pixel edit
+                if let Some((region, opacity)) = self.edit_highlight {
+                    let outline = egui::Rect::from_min_max(to_screen(region.min), to_screen(region.max));
+                    let opacity = opacity.clamp(0.0, 1.0);
+                    shapes.push(egui::Shape::rect_filled(outline, 0.0, egui::Color32::from_white_alpha(24).gamma_multiply(opacity)));
+                    shapes.push(egui::Shape::rect_stroke(outline, 0.0, egui::Stroke::new(1.5, egui::Color32::from_rgb(255, 140, 170).gamma_multiply(opacity))));
+                }
+                
+                painter.extend(shapes);
+
+                // Alignment guides for text: the lines the pending text sits on, or
+                // the ones a click would snap to
+                if self.is_drawing_mode && self.drawing_settings.tool == DrawingTool::Text {
+                    let target = match self.pending_text_pos {
+                        Some(pos) => Some((pos, 0.01)),
+                        None => ctx.input(|i| i.pointer.hover_pos())
+                            .filter(|&p| image_rect.contains(p) && !ctx.input(|i| i.modifiers.alt))
+                            .map(|p| (((p - image_rect.min) / self.zoom).to_pos2(), TEXT_SNAP_DISTANCE / self.zoom)),
+                    };
+                    if let Some((pos, tolerance)) = target {
+                        let [guide_x, guide_y] = snap_to_guides(pos, image_size, tolerance).1;
+                        let stroke = egui::Stroke::new(1.0, egui::Color32::from_rgb(255, 140, 170));
+                        if let Some(x) = guide_x {
+                            let x = to_screen(egui::pos2(x, 0.0)).x;
+                            painter.extend(egui::Shape::dashed_line(&[egui::pos2(x, image_rect.top()), egui::pos2(x, image_rect.bottom())], stroke, 6.0, 4.0));
+                        }
+                        if let Some(y) = guide_y {
+                            let y = to_screen(egui::pos2(0.0, y)).y;
+                            painter.extend(egui::Shape::dashed_line(&[egui::pos2(image_rect.left(), y), egui::pos2(image_rect.right(), y)], stroke, 6.0, 4.0));
+                        }
+                    }
+                }
+
+                // Brush size feedback after scrolling it
+                if self.brush_feedback_until.is_some_and(|until| Instant::now() < until) {
+                    if let Some(pointer) = ctx.input(|i| i.pointer.hover_pos()) {
+                        let (label, diameter) = match self.drawing_settings.tool {
+                            DrawingTool::Text => (format!("Font {:.0}", self.drawing_settings.font_size), None),
+                            _ => (format!("Size {:.0}", self.drawing_settings.size), Some(self.drawing_settings.size * self.zoom)),
+                        };
+                        if let Some(diameter) = diameter {
+                            painter.circle_stroke(pointer, diameter / 2.0, egui::Stroke::new(1.0, egui::Color32::WHITE));
+                            painter.circle_stroke(pointer, diameter / 2.0 + 1.0, egui::Stroke::new(1.0, egui::Color32::BLACK));
+                        }
+                        let text_pos = pointer + egui::vec2(0.0, diameter.unwrap_or(0.0) / 2.0 + 14.0);
+                        let galley = painter.layout_no_wrap(label, egui::FontId::proportional(13.0), egui::Color32::WHITE);
+                        let bg = egui::Align2::CENTER_CENTER.anchor_size(text_pos, galley.size()).expand(4.0);
+                        painter.rect_filled(bg, 4.0, egui::Color32::from_black_alpha(180));
+                        painter.galley(bg.min + egui::vec2(4.0, 4.0), galley, egui::Color32::WHITE);
+                    }
+                    ctx.request_repaint();
+                }
+
+                if self.config.show_hud {
+                    self.paint_hud(&painter, rect);
+                }
+
+                if self.show_loupe {
+                    if let Some(pointer) = ctx.input(|i| i.pointer.hover_pos()).filter(|&p| ui.rect_contains_pointer(image_rect) && rect.contains(p)) {
+                        loupe_at = Some((pointer, ((pointer - image_rect.min) / self.zoom).to_pos2()));
+                    }
+                }
+            }
+        });
+
+        if let Some((pointer, image_pos)) = loupe_at {
+            self.show_loupe(ctx, pointer, image_pos);
+        }
+        match advance {
+            Some(true) => self.next_image(ctx),
+            Some(false) => self.prev_image(ctx),
+            None => {}
+        }
+
+        if self.strip_view.is_none() && self.gallery.is_none() {
+            let mut jump = None;
+            if let Some(strip) = self.filmstrip.as_mut().filter(|_| !idle) {
+                jump = strip.show(ctx, &self.image_list, self.current_index, &mut self.thumbnails, self.scrub_since.is_none());
+            }
+            if let Some(idx) = jump {
+                self.jump_to(ctx, idx);
+            }
+            self.show_timeline(ctx);
+            self.show_downsampled_note(ctx);
+        }
+
+        if let Some(type_ahead) = &self.type_ahead {
+            type_ahead.show(ctx, self.bottom_inset());
+        }
+        if self.perf.visible {
+            self.perf.show(ctx, self.memory_estimate(), self.undo_depth());
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn initial_zoom_magnifies_a_single_pixel_up_to_the_limit() {
+        assert_eq!(initial_zoom(1.0, 1.0), MAX_ZOOM);
+    }
+
+    #[test]
+    fn initial_zoom_keeps_a_long_thin_image_at_native_size() {
+        assert_eq!(initial_zoom(10000.0, 1.0), 1.0);
+        assert_eq!(initial_zoom(1.0, 10000.0), 1.0);
+    }
+
+    #[test]
+    fn initial_zoom_magnifies_small_images_to_the_display_edge() {
+        assert_eq!(initial_zoom(16.0, 8.0), MIN_DISPLAY_EDGE / 16.0);
+        assert_eq!(initial_zoom(MIN_DISPLAY_EDGE, 1.0), 1.0);
+    }
+
+    #[test]
+    fn initial_zoom_ignores_empty_and_nan_sizes() {
+        assert_eq!(initial_zoom(0.0, 0.0), 1.0);
+        assert_eq!(initial_zoom(f32::NAN, f32::NAN), 1.0);
+        assert_eq!(initial_zoom(-5.0, -5.0), 1.0);
+    }
+
+    // Image point under `screen` for a pan `offset` at `zoom`, both relative to the viewport center
+    fn image_point(offset: egui::Vec2, zoom: f32, screen: egui::Vec2) -> egui::Vec2 {
+        (screen - offset) / zoom
+    }
+
+    fn assert_close(a: egui::Vec2, b: egui::Vec2) {
+        assert!((a - b).length() <= 1e-3 * b.length().max(1.0), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn cursor_zoom_keeps_the_point_under_the_cursor_at_extreme_ratios() {
+        let offset = egui::vec2(30.0, -12.0);
+        let cursor = egui::vec2(-400.0, 250.0);
+        for (from, to) in [(MIN_ZOOM, MAX_ZOOM), (MAX_ZOOM, MIN_ZOOM), (1.0, MAX_ZOOM), (MAX_ZOOM, 1.0), (1.0, 1.0)] {
+            let zoomed = zoom_anchor_offset(offset, to / from, Some(cursor));
+            assert_close(image_point(zoomed, to, cursor), image_point(offset, from, cursor));
+        }
+    }
+
+    #[test]
+    fn cursor_zoom_ignores_degenerate_ratios() {
+        let offset = egui::vec2(30.0, -12.0);
+        for ratio in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert_eq!(zoom_anchor_offset(offset, ratio, Some(egui::vec2(5.0, 5.0))), offset);
+        }
+    }
+
+    const RED: image::Rgba<u8> = image::Rgba([255, 0, 0, 255]);
+
+    fn painted(canvas: &image::RgbaImage, x: u32, y: u32) -> bool {
+        *canvas.get_pixel(x, y) == RED
+    }
+
+    #[test]
+    fn zero_sized_rectangles_burn_in_as_bars() {
+        let mut canvas = image::RgbaImage::new(40, 40);
+        burn_rect_outline(&mut canvas, egui::pos2(10.0, 5.0), egui::pos2(10.0, 30.0), 4.0, RED);
+        assert!(painted(&canvas, 9, 20) && painted(&canvas, 11, 20));
+        assert!(!painted(&canvas, 13, 20));
+
+        let mut canvas = image::RgbaImage::new(40, 40);
+        burn_rect_outline(&mut canvas, egui::pos2(20.0, 20.0), egui::pos2(20.0, 20.0), 2.0, RED);
+        assert!(painted(&canvas, 20, 20));
+    }
+
+    #[test]
+    fn rectangle_stroke_is_centered_on_the_edge() {
+        let mut canvas = image::RgbaImage::new(40, 40);
+        burn_rect_outline(&mut canvas, egui::pos2(10.0, 10.0), egui::pos2(30.0, 30.0), 4.0, RED);
+        // Two pixels either side of the left edge at x = 10, and nothing in the middle
+        assert!(painted(&canvas, 8, 20) && painted(&canvas, 11, 20));
+        assert!(!painted(&canvas, 7, 20) && !painted(&canvas, 12, 20));
+        assert!(!painted(&canvas, 20, 20));
+    }
+
+    #[test]
+    fn circle_is_drawn_around_the_first_point() {
+        let mut canvas = image::RgbaImage::new(60, 60);
+        let (center, edge) = (egui::pos2(30.0, 30.0), egui::pos2(30.0, 50.0));
+        burn_circle_outline(&mut canvas, center, center.distance(edge), 2.0, RED);
+        assert!(painted(&canvas, 30, 49) && painted(&canvas, 10, 30) && painted(&canvas, 49, 30));
+        assert!(!painted(&canvas, 30, 30) && !painted(&canvas, 30, 40));
+    }
+
+    const TRANSFORMS: [Transform; 4] = [Transform::RotateLeft, Transform::RotateRight, Transform::FlipHorizontal, Transform::FlipVertical];
+
+    #[test]
+    fn transform_point_follows_the_pixels() {
+        let size = egui::vec2(40.0, 30.0);
+        let top_left = egui::pos2(0.0, 0.0);
+        assert_eq!(transform_point(Transform::RotateRight, size, top_left), egui::pos2(30.0, 0.0));
+        assert_eq!(transform_point(Transform::RotateLeft, size, top_left), egui::pos2(0.0, 40.0));
+        assert_eq!(transform_point(Transform::FlipHorizontal, size, top_left), egui::pos2(40.0, 0.0));
+        assert_eq!(transform_point(Transform::FlipVertical, size, top_left), egui::pos2(0.0, 30.0));
+    }
+
+    #[test]
+    fn transform_point_round_trips_through_the_inverse() {
+        let size = egui::vec2(40.0, 30.0);
+        let p = egui::pos2(7.5, 21.0);
+        for transform in TRANSFORMS {
+            let moved = transform_point(transform, size, p);
+            let turned = if matches!(transform, Transform::RotateLeft | Transform::RotateRight) { egui::vec2(size.y, size.x) } else { size };
+            assert_eq!(transform_point(transform.inverse(), turned, moved), p, "{:?}", transform);
+        }
+    }
+
+    #[test]
+    fn four_turns_come_back_around() {
+        let p = egui::pos2(7.5, 21.0);
+        for transform in [Transform::RotateLeft, Transform::RotateRight] {
+            let mut size = egui::vec2(40.0, 30.0);
+            let mut q = p;
+            for _ in 0..4 {
+                q = transform_point(transform, size, q);
+                size = egui::vec2(size.y, size.x);
+            }
+            assert_eq!(q, p);
+        }
+    }
+
+    #[test]
+    fn rectangle_stays_on_its_content_through_transforms() {
+        // A marked pixel at (15, 3) of a 20x10 image, boxed by a rectangle drawn around it
+        let mut canvas = image::RgbaImage::new(20, 10);
+        canvas.put_pixel(15, 3, RED);
+        let image = image::DynamicImage::ImageRgba8(canvas);
+        let rect = DrawingObject {
+            tool: DrawingTool::Shape,
+            points: vec![egui::pos2(13.0, 1.0), egui::pos2(18.0, 6.0)],
+            color: egui::Color32::RED,
+            size: 1.0,
+            shape_type: Some(ShapeType::Rectangle),
+            text: None,
+            font_family: None,
+            font_bold: false,
+            widths: Vec::new(),
+        };
+
+        let sequences = TRANSFORMS.map(|t| vec![t]).into_iter()
+            .chain([vec![Transform::RotateRight, Transform::RotateRight, Transform::FlipHorizontal, Transform::RotateLeft]]);
+        for sequence in sequences {
+            let (mut image, mut rect) = (image.clone(), rect.clone());
+            for &transform in &sequence {
+                rect.transform(transform, egui::vec2(image.width() as f32, image.height() as f32));
+                image = transform.apply(&image);
+            }
+            let canvas = image.to_rgba8();
+            let (x, y, _) = canvas.enumerate_pixels().find(|(_, _, p)| **p == RED).unwrap();
+            let bounds = egui::Rect::from_two_pos(rect.points[0], rect.points[1]);
+            assert!(bounds.contains(egui::pos2(x as f32 + 0.5, y as f32 + 0.5)), "{:?}: {:?} misses ({}, {})", sequence, bounds, x, y);
+        }
+    }
+
+    #[test]
+    fn keyboard_zoom_keeps_the_window_center_fixed() {
+        let mut offset = egui::vec2(120.0, -45.0);
+        let mut zoom = 1.0;
+        let centered = image_point(offset, zoom, egui::Vec2::ZERO);
+        for zoom_in in [true, true, true, false, true, false, false, false, false] {
+            let next = if zoom_in { zoom * ZOOM_STEP } else { zoom / ZOOM_STEP };
+            offset = zoom_anchor_offset(offset, next / zoom, None);
+            zoom = next;
+            assert_close(image_point(offset, zoom, egui::Vec2::ZERO), centered);
+        }
+    }
+}